@@ -0,0 +1,428 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use reqwest::header::HeaderMap;
+use rust_decimal::Decimal;
+
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_client::RpcClientConfig, rpc_sender::RpcSender};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tokio::sync::Semaphore;
+
+use crate::{
+    crossbar::DEFAULT_CROSSBAR_URL,
+    utils::{
+        Cluster, DEFAULT_GATEWAY_API_PATH, MissingValuePolicy, NumSignaturesPolicy, PriorityFeeEscalation, RecentSlotStrategy,
+        SecpMessageFormat, normalize_gateway_uri,
+    },
+};
+
+use super::{AppClient, AppError, AppResult, TransactionSigner};
+
+// one rate limiter token per nanosecond, see build
+const MAX_REQUESTS_PER_SECOND: usize = 1_000_000_000;
+
+pub struct AppClientBuilder {
+    // keypair file, only read when no signer is set
+    pub(super) private_key: String,
+    pub(super) signer: Option<Arc<dyn TransactionSigner>>,
+    pub(super) rpc_url: String,
+    // builds the rpc client over a custom transport instead of http to rpc_url, see rpc_sender
+    pub(super) rpc_sender: Option<Box<dyn FnOnce(RpcClientConfig) -> RpcClient + Send>>,
+    // None derives it from rpc_url, see derive_ws_url
+    pub(super) ws_url: Option<String>,
+    pub(super) commitment_config: CommitmentConfig,
+    // max number of rpc requests in flight at any given time
+    pub(super) max_concurrent_requests: usize,
+    // max number of rpc requests started per second
+    pub(super) requests_per_second: usize,
+    pub(super) timeout: Duration,
+    // fraction of the compute unit limit a simulation may consume before warning
+    pub(super) compute_unit_warn_ratio: f64,
+    // error instead of warning when the compute_unit_warn_ratio is exceeded
+    pub(super) strict_compute_units: bool,
+    // exponential backoff applied between retries
+    pub(super) retry_base_delay: Duration,
+    pub(super) retry_max_delay: Duration,
+    // 0.0 keeps the plain backoff, 1.0 picks uniformly between zero and the backoff (full jitter)
+    pub(super) retry_jitter: f64,
+    // price each submission from getRecentPrioritizationFees over its writable accounts
+    pub(super) dynamic_priority_fee: bool,
+    pub(super) priority_fee_cache_ttl: Duration,
+    // cached queue/oracle accounts are refetched once the observed slot moves this far past them
+    pub(super) account_cache_slot_threshold: u64,
+    pub(super) missing_value_policy: MissingValuePolicy,
+    // signatures requested from the consensus endpoint
+    pub(super) num_signatures_policy: NumSignaturesPolicy,
+    // verify the consensus checksum the oracles signed before building the update
+    pub(super) strict_checksums: bool,
+    // verify the jobs crossbar serves hash to the feed's feed_hash before requesting signatures
+    pub(super) strict_jobs_hash: bool,
+    // message the oracles sign, see SecpMessageFormat
+    pub(super) secp_message_format: SecpMessageFormat,
+    // cluster whose on-demand program instructions are built for and accounts are checked against
+    pub(super) cluster: Cluster,
+    // skip consensus submissions whose value moved less than this (relative, 0.001 = 0.1%) from
+    // the feed's current value, None always submits
+    pub(super) value_change_threshold: Option<Decimal>,
+    // max number of transactions simulate_batch simulates at once, still bound by the rate limiter
+    pub(super) simulation_parallelism: usize,
+    // gateway uri => headers attached to every request sent to that gateway
+    pub(super) gateway_headers: HashMap<String, HeaderMap>,
+    // try the gateways of higher staked oracles first
+    pub(super) weight_gateways_by_stake: bool,
+    // try the gateways with the best success rate in this process first, ahead of stake weighting
+    pub(super) weight_gateways_by_success_rate: bool,
+    // appended to advertised gateway uris that lack it
+    pub(super) gateway_api_path: String,
+    // shared by every gateway and crossbar request
+    pub(super) http_timeout: Duration,
+    pub(super) http_pool_max_idle_per_host: usize,
+    pub(super) http_pool_idle_timeout: Duration,
+    // tried in order on every crossbar fetch
+    pub(super) crossbar_urls: Vec<String>,
+    pub(super) crossbar_timeout: Duration,
+    pub(super) crossbar_max_retries: u32,
+    // retries of a failed getMultipleAccounts chunk before its accounts are reported as None
+    pub(super) multiple_accounts_max_retries: u32,
+    // accounts per getMultipleAccounts call, most providers cap it at 100
+    pub(super) multiple_accounts_chunk_size: usize,
+    // max number of slots the processed slot of a submission may run ahead of its blockhash
+    pub(super) max_slot_blockhash_gap: u64,
+    // re-simulations on a fresh blockhash after a simulation fails with BlockhashNotFound
+    pub(super) blockhash_max_retries: u32,
+    pub(super) recent_slot_strategy: RecentSlotStrategy,
+    // queue every submitted feed must be on, None accepts each feed's own queue
+    pub(super) expected_queue: Option<Pubkey>,
+    // omit raw account data and truncate large structures in the info logs, for shared environments
+    pub(super) redact_logs: bool,
+    // retries of a transient send failure, the blockhash is only refetched once it expired or the
+    // rpc node fell behind
+    pub(super) send_max_retries: u32,
+    // raise the compute unit price on every send retry, None resends at the same price
+    pub(super) priority_fee_escalation: Option<PriorityFeeEscalation>,
+    // compile submissions into v0 messages against these lookup tables, legacy messages when empty
+    pub(super) lookup_tables: Vec<Pubkey>,
+}
+
+impl AppClientBuilder {
+    pub fn new(private_key: &str, rpc_url: String) -> Self {
+        Self {
+            private_key: private_key.to_string(),
+            signer: None,
+            rpc_url,
+            rpc_sender: None,
+            ws_url: None,
+            commitment_config: CommitmentConfig::confirmed(),
+            max_concurrent_requests: 15,
+            requests_per_second: 15,
+            // timeout after 3mins
+            timeout: Duration::from_secs(180),
+            compute_unit_warn_ratio: 0.9,
+            strict_compute_units: false,
+            retry_base_delay: Duration::from_millis(250),
+            retry_max_delay: Duration::from_secs(5),
+            retry_jitter: 1.0,
+            dynamic_priority_fee: false,
+            priority_fee_cache_ttl: Duration::from_secs(10),
+            // ~1min worth of slots
+            account_cache_slot_threshold: 150,
+            missing_value_policy: MissingValuePolicy::default(),
+            num_signatures_policy: NumSignaturesPolicy::default(),
+            strict_checksums: false,
+            strict_jobs_hash: false,
+            secp_message_format: SecpMessageFormat::default(),
+            cluster: Cluster::default(),
+            value_change_threshold: None,
+            simulation_parallelism: 4,
+            gateway_headers: HashMap::new(),
+            gateway_api_path: DEFAULT_GATEWAY_API_PATH.to_string(),
+            weight_gateways_by_stake: false,
+            weight_gateways_by_success_rate: false,
+            http_timeout: Duration::from_secs(10),
+            http_pool_max_idle_per_host: 32,
+            http_pool_idle_timeout: Duration::from_secs(90),
+            crossbar_urls: vec![DEFAULT_CROSSBAR_URL.to_string()],
+            crossbar_timeout: Duration::from_secs(5),
+            crossbar_max_retries: 2,
+            multiple_accounts_max_retries: 2,
+            multiple_accounts_chunk_size: 100,
+            max_slot_blockhash_gap: 32,
+            blockhash_max_retries: 2,
+            recent_slot_strategy: RecentSlotStrategy::default(),
+            expected_queue: None,
+            redact_logs: false,
+            send_max_retries: 3,
+            priority_fee_escalation: None,
+            lookup_tables: vec![],
+        }
+    }
+
+    // Signs (and pays for) the client's transactions instead of the private_key keypair.
+    pub fn signer(mut self, signer: Arc<dyn TransactionSigner>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    // Sends the client's rpc requests through rpc_sender (e.g. a proxy or a mock) instead of http
+    // to rpc_url. rpc_url is still used to derive the default ws_url.
+    pub fn rpc_sender<T: RpcSender + Send + Sync + 'static>(mut self, rpc_sender: T) -> Self {
+        self.rpc_sender = Some(Box::new(move |config| RpcClient::new_sender(rpc_sender, config)));
+        self
+    }
+
+    pub fn ws_url(mut self, ws_url: String) -> Self {
+        self.ws_url = Some(ws_url);
+        self
+    }
+
+    pub fn commitment_config(mut self, commitment_config: CommitmentConfig) -> Self {
+        self.commitment_config = commitment_config;
+        self
+    }
+
+    pub fn max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = max_concurrent_requests;
+        self
+    }
+
+    pub fn requests_per_second(mut self, requests_per_second: usize) -> Self {
+        self.requests_per_second = requests_per_second;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn compute_unit_warn_ratio(mut self, compute_unit_warn_ratio: f64) -> Self {
+        self.compute_unit_warn_ratio = compute_unit_warn_ratio;
+        self
+    }
+
+    pub fn strict_compute_units(mut self, strict_compute_units: bool) -> Self {
+        self.strict_compute_units = strict_compute_units;
+        self
+    }
+
+    pub fn retry_base_delay(mut self, retry_base_delay: Duration) -> Self {
+        self.retry_base_delay = retry_base_delay;
+        self
+    }
+
+    pub fn retry_max_delay(mut self, retry_max_delay: Duration) -> Self {
+        self.retry_max_delay = retry_max_delay;
+        self
+    }
+
+    pub fn retry_jitter(mut self, retry_jitter: f64) -> Self {
+        self.retry_jitter = retry_jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn dynamic_priority_fee(mut self, dynamic_priority_fee: bool) -> Self {
+        self.dynamic_priority_fee = dynamic_priority_fee;
+        self
+    }
+
+    pub fn priority_fee_cache_ttl(mut self, priority_fee_cache_ttl: Duration) -> Self {
+        self.priority_fee_cache_ttl = priority_fee_cache_ttl;
+        self
+    }
+
+    pub fn account_cache_slot_threshold(mut self, account_cache_slot_threshold: u64) -> Self {
+        self.account_cache_slot_threshold = account_cache_slot_threshold;
+        self
+    }
+
+    pub fn missing_value_policy(mut self, missing_value_policy: MissingValuePolicy) -> Self {
+        self.missing_value_policy = missing_value_policy;
+        self
+    }
+
+    pub fn num_signatures_policy(mut self, num_signatures_policy: NumSignaturesPolicy) -> Self {
+        self.num_signatures_policy = num_signatures_policy;
+        self
+    }
+
+    pub fn strict_checksums(mut self, strict_checksums: bool) -> Self {
+        self.strict_checksums = strict_checksums;
+        self
+    }
+
+    pub fn strict_jobs_hash(mut self, strict_jobs_hash: bool) -> Self {
+        self.strict_jobs_hash = strict_jobs_hash;
+        self
+    }
+
+    pub fn secp_message_format(mut self, secp_message_format: SecpMessageFormat) -> Self {
+        self.secp_message_format = secp_message_format;
+        self
+    }
+
+    pub fn cluster(mut self, cluster: Cluster) -> Self {
+        self.cluster = cluster;
+        self
+    }
+
+    pub fn value_change_threshold(mut self, value_change_threshold: Option<Decimal>) -> Self {
+        self.value_change_threshold = value_change_threshold;
+        self
+    }
+
+    pub fn simulation_parallelism(mut self, simulation_parallelism: usize) -> Self {
+        self.simulation_parallelism = simulation_parallelism.max(1);
+        self
+    }
+
+    pub fn gateway_headers(mut self, gateway_uri: &str, headers: HeaderMap) -> Self {
+        self.gateway_headers
+            .insert(normalize_gateway_uri(gateway_uri).to_string(), headers);
+        self
+    }
+
+    pub fn weight_gateways_by_stake(mut self, weight_gateways_by_stake: bool) -> Self {
+        self.weight_gateways_by_stake = weight_gateways_by_stake;
+        self
+    }
+
+    pub fn weight_gateways_by_success_rate(mut self, weight_gateways_by_success_rate: bool) -> Self {
+        self.weight_gateways_by_success_rate = weight_gateways_by_success_rate;
+        self
+    }
+
+    pub fn gateway_api_path(mut self, gateway_api_path: String) -> Self {
+        self.gateway_api_path = gateway_api_path;
+        self
+    }
+
+    pub fn http_timeout(mut self, http_timeout: Duration) -> Self {
+        self.http_timeout = http_timeout;
+        self
+    }
+
+    pub fn http_pool_max_idle_per_host(mut self, http_pool_max_idle_per_host: usize) -> Self {
+        self.http_pool_max_idle_per_host = http_pool_max_idle_per_host;
+        self
+    }
+
+    pub fn http_pool_idle_timeout(mut self, http_pool_idle_timeout: Duration) -> Self {
+        self.http_pool_idle_timeout = http_pool_idle_timeout;
+        self
+    }
+
+    pub fn crossbar_urls(mut self, crossbar_urls: Vec<String>) -> Self {
+        self.crossbar_urls = crossbar_urls;
+        self
+    }
+
+    pub fn crossbar_timeout(mut self, crossbar_timeout: Duration) -> Self {
+        self.crossbar_timeout = crossbar_timeout;
+        self
+    }
+
+    pub fn crossbar_max_retries(mut self, crossbar_max_retries: u32) -> Self {
+        self.crossbar_max_retries = crossbar_max_retries;
+        self
+    }
+
+    pub fn multiple_accounts_max_retries(mut self, multiple_accounts_max_retries: u32) -> Self {
+        self.multiple_accounts_max_retries = multiple_accounts_max_retries;
+        self
+    }
+
+    pub fn multiple_accounts_chunk_size(mut self, multiple_accounts_chunk_size: usize) -> Self {
+        self.multiple_accounts_chunk_size = multiple_accounts_chunk_size;
+        self
+    }
+
+    pub fn max_slot_blockhash_gap(mut self, max_slot_blockhash_gap: u64) -> Self {
+        self.max_slot_blockhash_gap = max_slot_blockhash_gap;
+        self
+    }
+
+    pub fn blockhash_max_retries(mut self, blockhash_max_retries: u32) -> Self {
+        self.blockhash_max_retries = blockhash_max_retries;
+        self
+    }
+
+    pub fn recent_slot_strategy(mut self, recent_slot_strategy: RecentSlotStrategy) -> Self {
+        self.recent_slot_strategy = recent_slot_strategy;
+        self
+    }
+
+    pub fn expected_queue(mut self, expected_queue: Pubkey) -> Self {
+        self.expected_queue = Some(expected_queue);
+        self
+    }
+
+    pub fn redact_logs(mut self, redact_logs: bool) -> Self {
+        self.redact_logs = redact_logs;
+        self
+    }
+
+    pub fn send_max_retries(mut self, send_max_retries: u32) -> Self {
+        self.send_max_retries = send_max_retries;
+        self
+    }
+
+    pub fn priority_fee_escalation(
+        mut self,
+        priority_fee_escalation: Option<PriorityFeeEscalation>,
+    ) -> Self {
+        self.priority_fee_escalation = priority_fee_escalation;
+        self
+    }
+
+    pub fn lookup_tables(mut self, lookup_tables: Vec<Pubkey>) -> Self {
+        self.lookup_tables = lookup_tables;
+        self
+    }
+
+    // A zero max_concurrent_requests or requests_per_second would leave every request waiting
+    // for a permit forever, so both are rejected here. requests_per_second is refilled one token
+    // every 1/requests_per_second seconds, which must be at least a nanosecond, and both are
+    // semaphore sizes, bounded by Semaphore::MAX_PERMITS.
+    pub fn build(self) -> AppResult<AppClient> {
+        if !(1..=Semaphore::MAX_PERMITS).contains(&self.max_concurrent_requests) {
+            return Err(AppError::ParsingError(format!(
+                "max_concurrent_requests must be between 1 and {}",
+                Semaphore::MAX_PERMITS
+            )));
+        }
+        if !(1..=MAX_REQUESTS_PER_SECOND).contains(&self.requests_per_second) {
+            return Err(AppError::ParsingError(format!(
+                "requests_per_second must be between 1 and {MAX_REQUESTS_PER_SECOND}"
+            )));
+        }
+
+        AppClient::from_builder(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::Semaphore;
+
+    use super::super::{AppError, mock_rpc::MockRpc};
+
+    #[tokio::test]
+    async fn build_rejects_out_of_range_limits() {
+        let mock = MockRpc::new(|_, _| unreachable!());
+        let build_error = |builder: super::AppClientBuilder| match builder.build() {
+            Err(AppError::ParsingError(message)) => message,
+            Err(app_error) => panic!("unexpected {app_error:?}"),
+            Ok(_) => panic!("unexpected client"),
+        };
+
+        assert!(build_error(mock.builder().requests_per_second(0)).starts_with("requests_per_second"));
+        assert!(build_error(mock.builder().requests_per_second(1_000_000_001)).starts_with("requests_per_second"));
+        assert!(build_error(mock.builder().requests_per_second(1 << 32)).starts_with("requests_per_second"));
+        assert!(build_error(mock.builder().max_concurrent_requests(0)).starts_with("max_concurrent_requests"));
+        assert!(
+            build_error(mock.builder().max_concurrent_requests(Semaphore::MAX_PERMITS + 1))
+                .starts_with("max_concurrent_requests")
+        );
+
+        assert!(mock.builder().requests_per_second(1_000_000_000).build().is_ok());
+    }
+}
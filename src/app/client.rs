@@ -1,211 +1,1302 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 use anchor_lang::prelude::Pubkey;
+use base64::{Engine, engine::general_purpose::STANDARD as base64};
 use futures::{stream, StreamExt};
-use solana_account_decoder::UiAccountEncoding;
+use reqwest::header::HeaderMap;
+use rust_decimal::Decimal;
+use serde_json::json;
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
 use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
     nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
     rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
-    rpc_filter::RpcFilterType,
-    rpc_request::TokenAccountsFilter,
+    rpc_custom_error::JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+    rpc_request::{RpcError, RpcRequest},
 };
 use solana_sdk::{
-    account::Account, commitment_config::CommitmentConfig, hash::Hash,
+    account::Account, commitment_config::CommitmentConfig, hash::Hash, packet::PACKET_DATA_SIZE,
     instruction::Instruction,
     message::{v0, AddressLookupTableAccount, Message, VersionedMessage},
-    signature::{Keypair, Signature}, signer::Signer, transaction::{VersionedTransaction}
+    signature::Keypair,
+    transaction::TransactionError,
+};
+use switchboard_on_demand_client::QueueAccountData;
+use tokio::{sync::{Semaphore, SemaphorePermit}, task::JoinHandle, time::interval};
+use crate::{
+    app::AppError,
+    crossbar::Crossbar,
+    gateway::{GatewayClient, GatewayStats},
+    utils::{
+        backoff_with_jitter, build_feed_hash_filters, check_simulation_result,
+        derive_ws_url, ensure_transaction_fits, load_keypair,
+        get_compute_unit_limit, get_compute_unit_price, set_compute_unit_price,
+        get_writable_accounts, gateway_api_url, normalize_gateway_uri, parse_queue_account, Cluster, LogValue, MissingValuePolicy, NumSignaturesPolicy, PriorityFeeEscalation, RecentSlotStrategy, SecpMessageFormat,
+    },
 };
-use tokio::{sync::Semaphore, time::interval};
-use solana_sdk::signature::EncodableKey;
-use crate::app::AppError;
 
-use super::error::AppResult;
+use super::{builder::AppClientBuilder, error::AppResult, signer::TransactionSigner};
 
 pub struct AppClient {
-    keypair: Arc<Keypair>,
+    // None when the client signs through an external signer
+    keypair: Option<Arc<Keypair>>,
+    signer: Arc<dyn TransactionSigner>,
     keypair_pubkey: Pubkey,
     rpc_client: RpcClient,
-    rpc_url: String,
-    semaphore: Arc<Semaphore>,
+    // websocket endpoint of the rpc, see subscribe_feeds
+    ws_url: String,
+    // caps the number of in-flight requests, permits are returned on drop
+    concurrency: Arc<Semaphore>,
+    // token bucket, permits are consumed and only replenished by the rps handler
+    rate_limiter: Arc<Semaphore>,
+    rps_handler: JoinHandle<()>,
+    compute_unit_warn_ratio: f64,
+    strict_compute_units: bool,
+    retry_base_delay: Duration,
+    retry_max_delay: Duration,
+    retry_jitter: f64,
+    dynamic_priority_fee: bool,
+    // sorted writable accounts => (fetched at, priority fee in micro-lamports)
+    priority_fee_cache: Mutex<HashMap<Vec<Pubkey>, (Instant, u64)>>,
+    priority_fee_cache_ttl: Duration,
+    // pubkey => (slot fetched at, account)
+    account_cache: Mutex<HashMap<Pubkey, (u64, Account)>>,
+    account_cache_slot_threshold: u64,
+    // highest slot reported by the rpc so far
+    latest_slot: AtomicU64,
+    missing_value_policy: MissingValuePolicy,
+    num_signatures_policy: NumSignaturesPolicy,
+    strict_checksums: bool,
+    secp_message_format: SecpMessageFormat,
+    cluster: Cluster,
+    value_change_threshold: Option<Decimal>,
+    simulation_parallelism: usize,
+    // crossbar feed_hash => on-chain feed pubkey
+    feed_hash_cache: Mutex<HashMap<[u8; 32], Pubkey>>,
+    gateway_headers: HashMap<String, HeaderMap>,
+    gateway_api_path: String,
+    weight_gateways_by_stake: bool,
+    weight_gateways_by_success_rate: bool,
+    // gateway url => results of the requests sent to it
+    gateway_stats: Mutex<HashMap<String, GatewayStats>>,
+    // one connection pool for all gateways, see GatewayClient::new
+    gateway_http_client: reqwest::Client,
+    crossbar: Crossbar,
+    multiple_accounts_max_retries: u32,
+    multiple_accounts_chunk_size: usize,
+    max_slot_blockhash_gap: u64,
+    // re-simulations on a fresh blockhash after BlockhashNotFound, see call_instructions
+    blockhash_max_retries: u32,
+    recent_slot_strategy: RecentSlotStrategy,
+    // submissions of a feed on another queue fail with QueueMismatch, see load_submission_accounts
+    expected_queue: Option<Pubkey>,
+    redact_logs: bool,
+    send_max_retries: u32,
+    priority_fee_escalation: Option<PriorityFeeEscalation>,
+    lookup_tables: Vec<Pubkey>,
 }
-use solana_client::rpc_response::RpcSimulateTransactionResult;
+// times get_submission_blockhash_and_slot re-fetches an inconsistent blockhash/slot pair
+const SUBMISSION_CONTEXT_MAX_REFETCHES: u32 = 2;
+
+// mirrors the error RpcClient::get_account returns for a missing account
+fn account_not_found(account_pubkey: &Pubkey) -> AppError {
+    ClientError::from(RpcError::ForUser(format!("AccountNotFound: pubkey={account_pubkey}"))).into()
+}
+
+use solana_client::rpc_response::{RpcBlockhash, RpcSimulateTransactionResult};
 use solana_client::rpc_response::Response;
+
+#[derive(Debug)]
+pub struct SimulationOutcome {
+    pub response: Response<RpcSimulateTransactionResult>,
+    pub compute_unit_limit: u32,
+    // compute units left before hitting the limit, None when the rpc doesn't report units_consumed
+    pub compute_unit_headroom: Option<u64>,
+    // decoded return data of the last instruction that set one, None when no program returned data
+    pub return_data: Option<SimulationReturnData>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SimulationReturnData {
+    pub program_id: Pubkey,
+    pub data: Vec<u8>,
+}
+
 impl AppClient {
+    // Signs and simulates the instructions. A simulation failing with BlockhashNotFound (e.g. the
+    // gateways took long enough for recent_blockhash to expire) is rebuilt on a freshly fetched
+    // blockhash and re-signed with the same signers, up to blockhash_max_retries times.
     pub async fn call_instructions(
         &self,
         alts: Option<&[AddressLookupTableAccount]>,
         instructions: &[Instruction],
         recent_blockhash: Hash,
         signing_keypairs: Option<&[&Keypair]>,
-    ) -> AppResult<Response<RpcSimulateTransactionResult>> {
-        tracing::info!("call_instructions: {instructions:#?}");
+    ) -> AppResult<SimulationOutcome> {
+        tracing::info!("call_instructions: {}", self.log_value(instructions));
 
-        let default_signing_keypairs: &[&Keypair] = &[&self.keypair];
-        let signing_keypairs = signing_keypairs.unwrap_or(default_signing_keypairs);
+        let mut recent_blockhash = recent_blockhash;
+        let mut attempt = 0;
+        let sim = loop {
+            let message = self.build_message(alts, instructions, recent_blockhash)?;
+            let transaction = self.sign_message(message, signing_keypairs).await?;
 
-        let transaction = if alts.is_none() {
-            let message = Message::new_with_blockhash(
+            let serialized_size = ensure_transaction_fits(&transaction)?;
+
+            tracing::info!(
+                "VersionedTransaction: {}\nserialized_size: {serialized_size}/{PACKET_DATA_SIZE}",
+                self.log_value(&transaction)
+            );
+
+            let sim = {
+                let _permit = self.acquire_permit().await?;
+                self.rpc_client.simulate_transaction(&transaction).await?
+            };
+
+            if sim.value.err != Some(TransactionError::BlockhashNotFound)
+                || attempt >= self.blockhash_max_retries
+            {
+                break sim;
+            }
+
+            attempt += 1;
+            tracing::warn!(
+                "Blockhash {recent_blockhash} expired, simulating again on a fresh one ({attempt}/{})",
+                self.blockhash_max_retries
+            );
+            recent_blockhash = self.get_latest_blockhash().await?;
+        };
+
+        if let Some(err) = &sim.value.err {
+            tracing::error!("Simulation failed - {err:#?}\nlogs: {:#?}", sim.value.logs);
+        }
+        check_simulation_result(&sim.value)?;
+
+        let compute_unit_limit = get_compute_unit_limit(instructions);
+        let compute_unit_headroom = sim
+            .value
+            .units_consumed
+            .map(|units_consumed| u64::from(compute_unit_limit).saturating_sub(units_consumed));
+
+        if let Some(units_consumed) = sim.value.units_consumed {
+            let warn_threshold = f64::from(compute_unit_limit) * self.compute_unit_warn_ratio;
+
+            if units_consumed as f64 >= warn_threshold {
+                if self.strict_compute_units {
+                    return Err(AppError::ComputeUnitsNearLimit {
+                        units_consumed,
+                        compute_unit_limit,
+                    });
+                }
+
+                tracing::warn!(
+                    "Simulation consumed {units_consumed}/{compute_unit_limit} compute units, consider raising the compute unit limit"
+                );
+            }
+        }
+
+        let return_data = sim
+            .value
+            .return_data
+            .as_ref()
+            .map(|return_data| {
+                // UiReturnDataEncoding only has a base64 variant
+                let (data, _) = &return_data.data;
+
+                Ok::<_, AppError>(SimulationReturnData {
+                    program_id: Pubkey::from_str(&return_data.program_id).map_err(|error| {
+                        AppError::ParsingError(format!(
+                            "Invalid return data program id {}: {error}",
+                            return_data.program_id
+                        ))
+                    })?,
+                    data: base64.decode(data)?,
+                })
+            })
+            .transpose()?;
+
+        Ok(SimulationOutcome {
+            response: sim,
+            compute_unit_limit,
+            compute_unit_headroom,
+            return_data,
+        })
+    }
+
+    // Simulates every transaction of a batch, up to simulation_parallelism at once. Results are in
+    // the batch's order and a failing transaction doesn't affect the others.
+    pub async fn simulate_batch(
+        &self,
+        alts: Option<&[AddressLookupTableAccount]>,
+        batch: &[Vec<Instruction>],
+        recent_blockhash: Hash,
+    ) -> Vec<AppResult<SimulationOutcome>> {
+        stream::iter(batch)
+            .map(|instructions| self.call_instructions(alts, instructions, recent_blockhash, None))
+            .buffered(self.simulation_parallelism)
+            .collect()
+            .await
+    }
+
+    // Compiles a legacy message, or a v0 message when lookup tables are given, paid by the client signer.
+    pub fn build_message(
+        &self,
+        alts: Option<&[AddressLookupTableAccount]>,
+        instructions: &[Instruction],
+        recent_blockhash: Hash,
+    ) -> AppResult<VersionedMessage> {
+        let message = match alts {
+            None => VersionedMessage::Legacy(Message::new_with_blockhash(
                 instructions,
                 Some(&self.keypair_pubkey),
                 &recent_blockhash,
-            );
-            let v0_message = VersionedMessage::Legacy(message);
-
-            VersionedTransaction::try_new(v0_message, signing_keypairs)?
-        } else {
-            let alts = alts.unwrap();
-            let message = v0::Message::try_compile(
+            )),
+            Some(alts) => VersionedMessage::V0(v0::Message::try_compile(
                 &self.keypair_pubkey,
                 instructions,
                 alts,
                 recent_blockhash,
-            )?;
-            let v0_message = VersionedMessage::V0(message);
-
-            VersionedTransaction::try_new(v0_message, signing_keypairs)?
+            )?),
         };
 
-        let serialized_size = serde_json::to_vec(&transaction)?.len();
-        let size_of_val = size_of_val(&transaction);
+        Ok(message)
+    }
 
-        tracing::info!("VersionedTransaction: {transaction:#?}\nserialized_size: {serialized_size} size_of_val: {size_of_val}");
+    // ~~~~ keypair related functions ~~~~
 
-        // if serialized_size > 1232 {
-        //     return Err(AppError::TransactionTooLarge(serialized_size));
-        // }
+    pub fn keypair_pubkey(&self) -> Pubkey {
+        self.keypair_pubkey
+    }
 
-        let sim = self
-            .rpc_client
-            .simulate_transaction(&transaction)
-            .await?;
-        Ok(sim)
+    // None when the client signs through an external signer
+    pub fn signing_keypair(&self) -> Option<&Keypair> {
+        self.keypair.as_deref()
     }
 
-    // ~~~~ keypair related functions ~~~~
+    pub fn signer(&self) -> &dyn TransactionSigner {
+        self.signer.as_ref()
+    }
 
-    pub fn keypair_pubkey(&self) -> Pubkey {
-        self.keypair_pubkey.clone()
+    pub fn new(private_key: &str, url: String) -> AppResult<Self> {
+        AppClientBuilder::new(private_key, url).build()
     }
 
-    pub fn signing_keypair(&self) -> &Keypair {
-        &self.keypair
+    pub fn builder(private_key: &str, url: String) -> AppClientBuilder {
+        AppClientBuilder::new(private_key, url)
     }
 
-    pub fn new(private_key: &str, url: String) -> Self {
-        let commitment_config = CommitmentConfig::confirmed();
+    // Fails when private_key can't be loaded, see load_keypair.
+    pub(super) fn from_builder(builder: AppClientBuilder) -> AppResult<Self> {
+        let AppClientBuilder {
+            private_key,
+            signer,
+            rpc_url: url,
+            rpc_sender,
+            ws_url,
+            commitment_config,
+            max_concurrent_requests,
+            requests_per_second,
+            timeout,
+            compute_unit_warn_ratio,
+            strict_compute_units,
+            retry_base_delay,
+            retry_max_delay,
+            retry_jitter,
+            dynamic_priority_fee,
+            priority_fee_cache_ttl,
+            account_cache_slot_threshold,
+            missing_value_policy,
+            num_signatures_policy,
+            strict_checksums,
+            strict_jobs_hash,
+            secp_message_format,
+            cluster,
+            value_change_threshold,
+            simulation_parallelism,
+            gateway_headers,
+            gateway_api_path,
+            weight_gateways_by_stake,
+            weight_gateways_by_success_rate,
+            http_timeout,
+            http_pool_max_idle_per_host,
+            http_pool_idle_timeout,
+            crossbar_urls,
+            crossbar_timeout,
+            crossbar_max_retries,
+            multiple_accounts_max_retries,
+            multiple_accounts_chunk_size,
+            max_slot_blockhash_gap,
+            blockhash_max_retries,
+            recent_slot_strategy,
+            expected_queue,
+            redact_logs,
+            send_max_retries,
+            priority_fee_escalation,
+            lookup_tables,
+        } = builder;
 
-        let keypair = Arc::new(Keypair::read_from_file(private_key).expect("Failed to read keypair file"));
+        let (keypair, signer) = match signer {
+            Some(signer) => (None, signer),
+            None => {
+                let keypair = Arc::new(load_keypair(&private_key)?);
+                (Some(keypair.clone()), keypair as Arc<dyn TransactionSigner>)
+            }
+        };
 
-        let keypair_pubkey = keypair.pubkey();
+        let keypair_pubkey = signer.pubkey();
         tracing::info!("Connected wallet - {keypair_pubkey}");
 
-        // request per second rate
-        let rqs_rate = 15;
-        let semaphore = Arc::new(Semaphore::new(rqs_rate));
-        // timeout after 3mins
-        let timeout = Duration::from_secs(180);
+        let http_client_builder = || {
+            reqwest::Client::builder()
+                .timeout(http_timeout)
+                .pool_max_idle_per_host(http_pool_max_idle_per_host)
+                .pool_idle_timeout(http_pool_idle_timeout)
+        };
+        let gateway_http_client = http_client_builder()
+            .danger_accept_invalid_certs(true)
+            .build()?;
+        let crossbar_http_client = http_client_builder().build()?;
+
+        let concurrency = Arc::new(Semaphore::new(max_concurrent_requests));
+        let rate_limiter = Arc::new(Semaphore::new(requests_per_second));
 
-        let mut interval = interval(Duration::from_secs(15));
+        // one token every 1/rate seconds rather than the whole second's worth at once, so a burst
+        // right before a refill can't be followed by another full burst right after it. build keeps
+        // the rate within 1..=1e9, a period of at least a nanosecond
+        let mut interval = interval(Duration::from_secs(1) / requests_per_second as u32);
 
-        // request per minute handler
-        let rps_handler_semaphore = semaphore.clone();
-        let _rps_handler = tokio::spawn(async move {
+        // request per second handler, refills the token bucket up to rate
+        let rps_handler_semaphore = rate_limiter.clone();
+        let rps_handler = tokio::spawn(async move {
             loop {
                 interval.tick().await;
 
-                let available_permits = rps_handler_semaphore.available_permits();
-
-                let to_add = if available_permits < rqs_rate {
-                    rqs_rate - available_permits
-                } else {
-                    0
-                };
-
                 // Replenish up to rate.
-                if to_add > 0 {
-                    rps_handler_semaphore.add_permits(to_add);
+                if rps_handler_semaphore.available_permits() < requests_per_second {
+                    rps_handler_semaphore.add_permits(1);
                 }
             }
         });
 
-        Self {
+        Ok(Self {
             keypair,
+            signer,
             keypair_pubkey,
-            rpc_client: RpcClient::new_with_timeout_and_commitment(
-                url.clone(),
-                timeout,
-                commitment_config,
-            ),
-            rpc_url: url,
-            semaphore,
+            rpc_client: match rpc_sender {
+                Some(new_rpc_client) => new_rpc_client(RpcClientConfig::with_commitment(commitment_config)),
+                None => RpcClient::new_with_timeout_and_commitment(url.clone(), timeout, commitment_config),
+            },
+            ws_url: ws_url.unwrap_or_else(|| derive_ws_url(&url)),
+            concurrency,
+            rate_limiter,
+            rps_handler,
+            compute_unit_warn_ratio,
+            strict_compute_units,
+            retry_base_delay,
+            retry_max_delay,
+            retry_jitter,
+            dynamic_priority_fee,
+            priority_fee_cache: Mutex::new(HashMap::new()),
+            priority_fee_cache_ttl,
+            account_cache: Mutex::new(HashMap::new()),
+            account_cache_slot_threshold,
+            latest_slot: AtomicU64::new(0),
+            missing_value_policy,
+            num_signatures_policy,
+            strict_checksums,
+            secp_message_format,
+            cluster,
+            value_change_threshold,
+            simulation_parallelism,
+            feed_hash_cache: Mutex::new(HashMap::new()),
+            gateway_headers,
+            gateway_api_path,
+            weight_gateways_by_stake,
+            weight_gateways_by_success_rate,
+            gateway_stats: Mutex::new(HashMap::new()),
+            gateway_http_client,
+            crossbar: Crossbar::new(crossbar_urls, crossbar_http_client)
+                .timeout(crossbar_timeout)
+                .max_retries(crossbar_max_retries)
+                .retry_backoff(retry_base_delay, retry_max_delay, retry_jitter)
+                .verify_jobs_hash(strict_jobs_hash),
+            multiple_accounts_max_retries,
+            multiple_accounts_chunk_size,
+            max_slot_blockhash_gap,
+            blockhash_max_retries,
+            recent_slot_strategy,
+            expected_queue,
+            redact_logs,
+            send_max_retries,
+            priority_fee_escalation,
+            lookup_tables,
+        })
+    }
+
+    // Client for an oracle's gateway, carrying the headers configured for its uri (none by default).
+    pub fn gateway(&self, gateway_uri: String) -> GatewayClient {
+        let headers = self
+            .gateway_headers
+            .get(normalize_gateway_uri(&gateway_uri))
+            .cloned()
+            .unwrap_or_default();
+
+        // reqwest clients are reference counted, clones share the connection pool
+        GatewayClient::new(
+            gateway_api_url(&gateway_uri, &self.gateway_api_path),
+            self.gateway_http_client.clone(),
+            headers,
+        )
+    }
+
+    pub fn record_gateway_result(&self, gateway: &GatewayClient, success: bool) {
+        self.gateway_stats
+            .lock()
+            .unwrap()
+            .entry(gateway.gateway_url().to_string())
+            .or_default()
+            .record(success);
+    }
+
+    // Snapshot of every gateway's stats, keyed by gateway url.
+    pub fn gateway_stats(&self) -> HashMap<String, GatewayStats> {
+        self.gateway_stats.lock().unwrap().clone()
+    }
+
+    pub fn gateway_success_rate(&self, gateway: &GatewayClient) -> f64 {
+        self.gateway_stats
+            .lock()
+            .unwrap()
+            .get(gateway.gateway_url())
+            .copied()
+            .unwrap_or_default()
+            .success_rate
+    }
+
+    // Large values (responses, transactions, simulations) for the logs, truncated when redact_logs.
+    pub fn log_value<'a, T: ?Sized>(&self, value: &'a T) -> LogValue<'a, T> {
+        if self.redact_logs {
+            LogValue::Truncated(value)
+        } else {
+            LogValue::Full(value)
         }
     }
 
-    pub async fn get_account(&self, account_pubkey: &Pubkey) -> AppResult<Account> {
-        let _permit = self.semaphore.acquire().await?;
-        let account = self.rpc_client.get_account(account_pubkey).await?;
+    // Deserialized account data for the logs, omitted entirely when redact_logs.
+    pub fn log_account_data<'a, T: ?Sized>(&self, account_data: &'a T) -> LogValue<'a, T> {
+        if self.redact_logs {
+            LogValue::Substitute("<redacted>".to_string())
+        } else {
+            LogValue::Full(account_data)
+        }
+    }
+
+    // Gateways for the logs, only their urls when redact_logs as their headers may carry credentials.
+    pub fn log_gateways<'a>(&self, gateways: &'a [GatewayClient]) -> LogValue<'a, [GatewayClient]> {
+        if self.redact_logs {
+            LogValue::Substitute(format!(
+                "{:?}",
+                gateways.iter().map(GatewayClient::gateway_url).collect::<Vec<_>>()
+            ))
+        } else {
+            LogValue::Full(gateways)
+        }
+    }
+
+    pub fn crossbar(&self) -> &Crossbar {
+        &self.crossbar
+    }
+
+    // (base delay, max delay, jitter) of retry_delay, for tasks that outlive a borrow of the client
+    pub(super) fn retry_backoff(&self) -> (Duration, Duration, f64) {
+        (self.retry_base_delay, self.retry_max_delay, self.retry_jitter)
+    }
+
+    // Jittered backoff to wait before the given retry, desynchronizes keepers sharing gateways/rpcs.
+    pub fn retry_delay(&self, attempt: u32) -> Duration {
+        backoff_with_jitter(
+            self.retry_base_delay,
+            self.retry_max_delay,
+            attempt,
+            self.retry_jitter,
+        )
+    }
+
+    // Stops the rps handler and fails any pending or future request with SemaphoreAcquireError.
+    pub fn close(&self) {
+        self.rps_handler.abort();
+        self.rate_limiter.close();
+        self.concurrency.close();
+    }
+
+    // True as soon as close returns, the aborted rps handler only stops asynchronously.
+    pub fn is_closed(&self) -> bool {
+        self.rate_limiter.is_closed()
+    }
+
+    // Waits for a rate limiter token and then a concurrency slot. The token is consumed,
+    // the returned concurrency permit is released when dropped.
+    pub(super) async fn acquire_permit(&self) -> AppResult<SemaphorePermit<'_>> {
+        self.rate_limiter.acquire().await?.forget();
+        let permit = self.concurrency.acquire().await?;
+
+        Ok(permit)
+    }
+
+    // With a min_context_slot the rpc must serve the account at or beyond that slot, a lagging
+    // node answers MinContextSlotNotReached instead of the stale state.
+    pub async fn get_account(
+        &self,
+        account_pubkey: &Pubkey,
+        min_context_slot: Option<u64>,
+    ) -> AppResult<Account> {
+        let config = self.account_info_config(min_context_slot);
+
+        let _permit = self.acquire_permit().await?;
+        let response = self
+            .rpc_client
+            .get_account_with_config(account_pubkey, config)
+            .await
+            .map_err(|rpc_error| map_min_context_slot_error(rpc_error, min_context_slot))?;
+        self.observe_slot(response.context.slot);
+
+        let account = response
+            .value
+            .ok_or_else(|| account_not_found(account_pubkey))?;
 
         Ok(account)
     }
 
+    // Same as get_multiple_accounts but serves accounts fetched within account_cache_slot_threshold
+    // slots of the latest observed slot from the cache. Meant for rarely changing accounts (queue,
+    // oracles), feeds should always be fetched directly.
+    pub async fn get_cached_multiple_accounts(
+        &self,
+        accounts_pubkey: &[Pubkey],
+    ) -> AppResult<Vec<Option<Account>>> {
+        let latest_slot = self.latest_slot.load(Ordering::Relaxed);
+
+        let missing_pubkeys = {
+            let account_cache = self.account_cache.lock().unwrap();
+            accounts_pubkey
+                .iter()
+                .filter(|pubkey| {
+                    !account_cache.get(pubkey).is_some_and(|(fetched_slot, _)| {
+                        fetched_slot + self.account_cache_slot_threshold >= latest_slot
+                    })
+                })
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        if !missing_pubkeys.is_empty() {
+            tracing::info!("Account cache miss for {} accounts", missing_pubkeys.len());
+            let fetched_accounts = self
+                .get_multiple_accounts(&missing_pubkeys, None, None)
+                .await?;
+            // the fetch observed the slot it was served at, latest_slot may still be 0 before it
+            let fetched_slot = self.latest_slot.load(Ordering::Relaxed);
+
+            let mut account_cache = self.account_cache.lock().unwrap();
+            for (pubkey, account) in missing_pubkeys.into_iter().zip(fetched_accounts) {
+                match account {
+                    Some(account) => account_cache.insert(pubkey, (fetched_slot, account)),
+                    None => account_cache.remove(&pubkey),
+                };
+            }
+        }
+
+        let account_cache = self.account_cache.lock().unwrap();
+        let accounts = accounts_pubkey
+            .iter()
+            .map(|pubkey| account_cache.get(pubkey).map(|(_, account)| account.clone()))
+            .collect();
+
+        Ok(accounts)
+    }
+
+    pub async fn load_queue(&self, queue_pubkey: &Pubkey) -> AppResult<QueueAccountData> {
+        let queue_account = self
+            .get_cached_multiple_accounts(&[*queue_pubkey])
+            .await?
+            .pop()
+            .flatten()
+            .ok_or_else(|| account_not_found(queue_pubkey))?;
+
+        parse_queue_account(&queue_account.data)
+    }
+
+    // Resolves a crossbar feed_hash to its on-chain PullFeed account through getProgramAccounts,
+    // the mapping never changes so it is cached for the lifetime of the client.
+    pub async fn find_feed_by_hash(&self, feed_hash: &[u8; 32]) -> AppResult<Pubkey> {
+        if let Some(feed_pubkey) = self.feed_hash_cache.lock().unwrap().get(feed_hash) {
+            return Ok(*feed_pubkey);
+        }
+
+        let config = RpcProgramAccountsConfig {
+            filters: Some(build_feed_hash_filters(feed_hash)),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                // only the pubkeys are needed
+                data_slice: Some(UiDataSliceConfig {
+                    offset: 0,
+                    length: 0,
+                }),
+                commitment: Some(self.rpc_client.commitment()),
+                min_context_slot: None,
+            },
+            with_context: None,
+            sort_results: Some(true),
+        };
+
+        let matching_feeds = {
+            let _permit = self.acquire_permit().await?;
+            self.rpc_client
+                .get_program_accounts_with_config(&self.cluster.program_id(), config)
+                .await?
+        };
+
+        let feed_pubkey = match matching_feeds.as_slice() {
+            [] => return Err(AppError::FeedHashNotFound(hex::encode(feed_hash))),
+            [(feed_pubkey, _)] => *feed_pubkey,
+            [(feed_pubkey, _), ..] => {
+                tracing::warn!(
+                    "{} feeds share feed_hash {}, using - {feed_pubkey}",
+                    matching_feeds.len(),
+                    hex::encode(feed_hash)
+                );
+                *feed_pubkey
+            }
+        };
+
+        self.feed_hash_cache
+            .lock()
+            .unwrap()
+            .insert(*feed_hash, feed_pubkey);
+
+        Ok(feed_pubkey)
+    }
+
+    // Loads the queue and its oracle accounts into the account cache, so the submissions of a group
+    // of feeds sharing the queue don't each fetch them.
+    pub async fn preload_queue(&self, queue_pubkey: &Pubkey) -> AppResult<()> {
+        let queue_account_data = self.load_queue(queue_pubkey).await?;
+        self.get_cached_multiple_accounts(&queue_account_data.oracle_keys())
+            .await?;
+
+        Ok(())
+    }
+
+    pub fn invalidate_account_cache(&self) {
+        self.account_cache.lock().unwrap().clear();
+    }
+
+    fn account_info_config(&self, min_context_slot: Option<u64>) -> RpcAccountInfoConfig {
+        RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: None,
+            commitment: Some(self.rpc_client.commitment()),
+            min_context_slot,
+        }
+    }
+
+    fn observe_slot(&self, slot: u64) {
+        self.latest_slot.fetch_max(slot, Ordering::Relaxed);
+    }
+
     pub async fn get_latest_blockhash(&self) -> AppResult<Hash> {
-        let _permit = self.semaphore.acquire().await?;
+        let _permit = self.acquire_permit().await?;
         let latest_hash = self.rpc_client.get_latest_blockhash().await?;
 
         Ok(latest_hash)
     }
 
+    // Latest blockhash at the client's commitment with the slot the rpc served it at.
+    pub async fn get_latest_blockhash_with_slot(&self) -> AppResult<(Hash, u64)> {
+        let _permit = self.acquire_permit().await?;
+        let response = self
+            .rpc_client
+            .send::<Response<RpcBlockhash>>(
+                RpcRequest::GetLatestBlockhash,
+                json!([self.rpc_client.commitment()]),
+            )
+            .await?;
+        self.observe_slot(response.context.slot);
+
+        let blockhash = Hash::from_str(&response.value.blockhash)
+            .map_err(|error| AppError::ParsingError(format!("{error}")))?;
+
+        Ok((blockhash, response.context.slot))
+    }
+
+    // Blockhash and recent slot for a submission. The blockhash is read at the client's commitment
+    // (confirmed) and the slot at processed, so the slot is expected to be at most
+    // max_slot_blockhash_gap slots ahead of the blockhash. Load balanced rpcs may answer from nodes
+    // that disagree, in which case both are re-fetched and the blockhash's own slot is used if
+    // they still don't line up.
+    pub async fn get_submission_blockhash_and_slot(&self) -> AppResult<(Hash, u64)> {
+        let mut attempt = 0;
+        loop {
+            let (blockhash_result, slot_result) = tokio::join!(
+                self.get_latest_blockhash_with_slot(),
+                self.get_slot(Some(CommitmentConfig::processed()))
+            );
+            let (blockhash, blockhash_slot) = blockhash_result?;
+            let recent_slot = slot_result?;
+
+            if recent_slot >= blockhash_slot
+                && recent_slot - blockhash_slot <= self.max_slot_blockhash_gap
+            {
+                return Ok((blockhash, recent_slot));
+            }
+
+            tracing::warn!(
+                "Processed slot {recent_slot} is inconsistent with blockhash {blockhash} served at slot {blockhash_slot}, max gap - {}",
+                self.max_slot_blockhash_gap
+            );
+
+            if attempt >= SUBMISSION_CONTEXT_MAX_REFETCHES {
+                return Ok((blockhash, blockhash_slot));
+            }
+
+            tokio::time::sleep(self.retry_delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    // get_submission_blockhash_and_slot for a feed last updated at last_update_slot, refetching
+    // until the recent slot satisfies the client's recent_slot_strategy.
+    pub async fn get_submission_blockhash_and_slot_after(
+        &self,
+        last_update_slot: Option<u64>,
+    ) -> AppResult<(Hash, u64)> {
+        let max_refetches = match self.recent_slot_strategy {
+            RecentSlotStrategy::Latest => 0,
+            RecentSlotStrategy::AfterLastUpdate { max_refetches } => max_refetches,
+        };
+
+        let mut attempt = 0;
+        loop {
+            let (blockhash, recent_slot) = self.get_submission_blockhash_and_slot().await?;
+            if self.recent_slot_strategy.accepts(recent_slot, last_update_slot) {
+                return Ok((blockhash, recent_slot));
+            }
+
+            let last_update_slot = last_update_slot.unwrap_or_default();
+            if attempt >= max_refetches {
+                return Err(AppError::SlotNotAdvanced {
+                    last_update_slot,
+                    recent_slot,
+                });
+            }
+
+            tracing::info!(
+                "Recent slot {recent_slot} isn't past the feed's last update at {last_update_slot}, refetching ({}/{max_refetches})",
+                attempt + 1
+            );
+            tokio::time::sleep(self.retry_delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    // Returns one entry per pubkey, in the order of accounts_pubkey. A chunk that keeps failing
+    // (or answers with the wrong number of accounts) is reported as None accounts, except when the
+    // rpc is behind min_context_slot which fails the whole call rather than passing stale state off
+    // as missing.
     pub async fn get_multiple_accounts(
         &self,
         accounts_pubkey: &[Pubkey],
         limit: Option<usize>,
+        min_context_slot: Option<u64>,
     ) -> AppResult<Vec<Option<Account>>> {
-        if accounts_pubkey.len() == 0 {
-            return Ok(vec![]);
+        let chunks = self
+            .get_multiple_accounts_chunks(accounts_pubkey, limit, min_context_slot)
+            .await
+            .into_iter()
+            .zip(accounts_pubkey.chunks(self.multiple_accounts_chunk_size.max(1)))
+            .map(|(chunk, accounts_pubkey)| match chunk {
+                Err(AppError::MinContextSlotNotReached(min_context_slot)) => {
+                    Err(AppError::MinContextSlotNotReached(min_context_slot))
+                }
+                Err(app_error) => {
+                    tracing::error!("Failed to get multiple accounts, reporting them as missing\n{app_error:#?}");
+                    Ok(vec![None; accounts_pubkey.len()])
+                }
+                Ok(accounts) => Ok(accounts),
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+
+        Ok(chunks.into_iter().flatten().collect())
+    }
+
+    // Same as get_multiple_accounts, but a chunk that keeps failing fails the whole call instead of
+    // being reported as missing accounts.
+    pub(super) async fn try_get_multiple_accounts(
+        &self,
+        accounts_pubkey: &[Pubkey],
+        limit: Option<usize>,
+        min_context_slot: Option<u64>,
+    ) -> AppResult<Vec<Option<Account>>> {
+        let chunks = self
+            .get_multiple_accounts_chunks(accounts_pubkey, limit, min_context_slot)
+            .await
+            .into_iter()
+            .collect::<AppResult<Vec<_>>>()?;
+
+        Ok(chunks.into_iter().flatten().collect())
+    }
+
+    // One result per chunk of multiple_accounts_chunk_size pubkeys, in order, each retried up to
+    // multiple_accounts_max_retries times. Fetches up to limit (default 5) chunks at once, each
+    // request counting against the rate limit and max_concurrent_requests.
+    async fn get_multiple_accounts_chunks(
+        &self,
+        accounts_pubkey: &[Pubkey],
+        limit: Option<usize>,
+        min_context_slot: Option<u64>,
+    ) -> Vec<AppResult<Vec<Option<Account>>>> {
+        if accounts_pubkey.is_empty() {
+            return vec![];
         }
 
-        let _permit = self.semaphore.acquire().await?;
+        // the last chunk holds the remainder and is never empty
+        let chunk_size = self.multiple_accounts_chunk_size.max(1);
+        let chunked_accounts_pubkey = accounts_pubkey.chunks(chunk_size).map(<[Pubkey]>::to_vec);
+
+        // buffered keeps the chunks in order, callers zip the result with accounts_pubkey
+        stream::iter(chunked_accounts_pubkey)
+            .map(async |accounts_pubkey| {
+                let mut attempt = 0;
+                loop {
+                    // every request takes its own permit, released before a retry's backoff
+                    let response = {
+                        let _permit = match self.acquire_permit().await {
+                            Err(app_error) => break Err(app_error),
+                            Ok(permit) => permit,
+                        };
+                        self.rpc_client
+                            .get_multiple_accounts_with_config(
+                                accounts_pubkey.as_slice(),
+                                self.account_info_config(min_context_slot),
+                            )
+                            .await
+                    }
+                    .map_err(|rpc_error| map_min_context_slot_error(rpc_error, min_context_slot))
+                        // a short or long chunk would shift every later account onto the wrong pubkey
+                        .and_then(|response| {
+                            if response.value.len() != accounts_pubkey.len() {
+                                return Err(AppError::ParsingError(format!(
+                                    "getMultipleAccounts returned {} accounts for {} pubkeys",
+                                    response.value.len(),
+                                    accounts_pubkey.len()
+                                )));
+                            }
+
+                            Ok(response)
+                        });
+
+                    match response {
+                        Err(AppError::MinContextSlotNotReached(min_context_slot)) => {
+                            break Err(AppError::MinContextSlotNotReached(min_context_slot));
+                        }
+                        Err(app_error) if attempt < self.multiple_accounts_max_retries => {
+                            tracing::warn!(
+                                "Failed to get multiple accounts with chunk size - {chunk_size}, retrying ({}/{})\n{app_error:#?}",
+                                attempt + 1,
+                                self.multiple_accounts_max_retries
+                            );
 
-        const CHUNK_SIZE: usize = 5;
+                            tokio::time::sleep(self.retry_delay(attempt)).await;
+                            attempt += 1;
+                        }
+                        Err(app_error) => break Err(app_error),
+                        Ok(response) => {
+                            self.observe_slot(response.context.slot);
+                            break Ok(response.value);
+                        }
+                    }
+                }
+            })
+            .buffered(limit.unwrap_or(5))
+            .collect()
+            .await
+    }
+
+    // Median of the recent prioritization fees paid for the given writable accounts, cached
+    // per account set for priority_fee_cache_ttl.
+    pub async fn get_priority_fee(&self, writable_accounts: &[Pubkey]) -> AppResult<u64> {
+        let mut cache_key = writable_accounts.to_vec();
+        cache_key.sort();
+        cache_key.dedup();
+
+        if let Some((fetched_at, priority_fee)) =
+            self.priority_fee_cache.lock().unwrap().get(&cache_key)
+            && fetched_at.elapsed() < self.priority_fee_cache_ttl
+        {
+            return Ok(*priority_fee);
+        }
 
-        let (chunked_accounts_pubkey, remainder) =
-            accounts_pubkey.as_chunks::<CHUNK_SIZE>();
-        let mut chunked_accounts_pubkey: Vec<Vec<Pubkey>> = chunked_accounts_pubkey
+        let recent_fees = {
+            let _permit = self.acquire_permit().await?;
+            self.rpc_client
+                .get_recent_prioritization_fees(&cache_key)
+                .await?
+        };
+
+        let mut fees = recent_fees
             .iter()
-            .map(|pubkeys| pubkeys.to_vec())
-            .collect();
+            .map(|recent_fee| recent_fee.prioritization_fee)
+            .collect::<Vec<_>>();
+        fees.sort_unstable();
+        let priority_fee = fees.get(fees.len() / 2).copied().unwrap_or(0);
 
-        chunked_accounts_pubkey.push(remainder.to_vec());
+        self.priority_fee_cache
+            .lock()
+            .unwrap()
+            .insert(cache_key, (Instant::now(), priority_fee));
 
-        let multiple_accounts = stream::iter(chunked_accounts_pubkey).map(async |accounts_pubkey| {
-            match self.rpc_client.get_multiple_accounts(accounts_pubkey.as_slice()).await {
-                Err(app_error) => {
-                    tracing::error!(
-                        "Failed to get multiple accounts with chunk size - {CHUNK_SIZE}\n{app_error:#?}"
-                    );
+        Ok(priority_fee)
+    }
 
-                    let length = accounts_pubkey.len();
-                    let default = (0..length).into_iter().map(|_| None).collect::<Vec<Option<Account>>>();
+    // Appends a SetComputeUnitPrice ix priced for the instructions' writable accounts when
+    // dynamic priority fees are enabled. It is appended rather than prepended so the secp256k1
+    // instruction keeps its index. Instructions that already set a price are left as is, the
+    // runtime rejects a second SetComputeUnitPrice.
+    pub async fn with_priority_fee(
+        &self,
+        mut instructions: Vec<Instruction>,
+    ) -> AppResult<Vec<Instruction>> {
+        if !self.dynamic_priority_fee || get_compute_unit_price(&instructions).is_some() {
+            return Ok(instructions);
+        }
 
-                    default.to_vec()
-                }
-                Ok(accounts) => accounts
-            }
-        }).buffer_unordered(limit.unwrap_or(5)).collect::<Vec<_>>().await;
+        let writable_accounts = get_writable_accounts(&instructions);
+        let priority_fee = self.get_priority_fee(&writable_accounts).await?;
+        tracing::info!("Using priority fee of {priority_fee} micro-lamports for - {writable_accounts:#?}");
 
-        let accounts = multiple_accounts.into_iter().flatten().collect::<Vec<_>>();
+        set_compute_unit_price(&mut instructions, priority_fee);
 
-        Ok(accounts)
+        Ok(instructions)
     }
 
-    pub async fn get_slot(&self) -> AppResult<u64> {
-        let _permit = self.semaphore.acquire().await?;
-        let slot = self.rpc_client.get_slot().await?;
+    // commitment overrides the client's default (confirmed) commitment for this call
+    pub async fn get_slot(&self, commitment: Option<CommitmentConfig>) -> AppResult<u64> {
+        let _permit = self.acquire_permit().await?;
+        let slot = self
+            .rpc_client
+            .get_slot_with_commitment(commitment.unwrap_or(self.rpc_client.commitment()))
+            .await?;
+        self.observe_slot(slot);
 
         Ok(slot)
     }
 
+    pub fn missing_value_policy(&self) -> MissingValuePolicy {
+        self.missing_value_policy
+    }
+
+    pub fn num_signatures_policy(&self) -> NumSignaturesPolicy {
+        self.num_signatures_policy
+    }
+
+    pub fn strict_checksums(&self) -> bool {
+        self.strict_checksums
+    }
+
+    pub fn secp_message_format(&self) -> SecpMessageFormat {
+        self.secp_message_format
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        self.cluster
+    }
+
+    pub fn value_change_threshold(&self) -> Option<Decimal> {
+        self.value_change_threshold
+    }
+
+    pub fn weight_gateways_by_stake(&self) -> bool {
+        self.weight_gateways_by_stake
+    }
+
+    pub fn send_max_retries(&self) -> u32 {
+        self.send_max_retries
+    }
+
+    pub fn lookup_tables(&self) -> &[Pubkey] {
+        &self.lookup_tables
+    }
+
+    pub fn recent_slot_strategy(&self) -> RecentSlotStrategy {
+        self.recent_slot_strategy
+    }
+
+    pub fn expected_queue(&self) -> Option<Pubkey> {
+        self.expected_queue
+    }
+
+    pub fn ws_url(&self) -> &str {
+        &self.ws_url
+    }
+
+    pub fn commitment_config(&self) -> CommitmentConfig {
+        self.rpc_client.commitment()
+    }
+
+    pub fn priority_fee_escalation(&self) -> Option<PriorityFeeEscalation> {
+        self.priority_fee_escalation
+    }
+
+    pub fn weight_gateways_by_success_rate(&self) -> bool {
+        self.weight_gateways_by_success_rate
+    }
+
     pub fn rpc_client(&self) -> &RpcClient {
         &self.rpc_client
     }
 }
+
+// The rps handler would otherwise keep refilling the bucket of a dropped client for as long as
+// the runtime lives.
+impl Drop for AppClient {
+    fn drop(&mut self) {
+        self.rps_handler.abort();
+    }
+}
+
+// The rpc answers -32016 when it is behind the requested min_context_slot.
+fn map_min_context_slot_error(rpc_error: ClientError, min_context_slot: Option<u64>) -> AppError {
+    match (rpc_error.kind(), min_context_slot) {
+        (
+            ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }),
+            Some(min_context_slot),
+        ) if *code == JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED => {
+            AppError::MinContextSlotNotReached(min_context_slot)
+        }
+        _ => rpc_error.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{
+            Arc,
+            atomic::{AtomicU64, Ordering},
+        },
+        time::{Duration, Instant},
+    };
+
+    use futures::future::join_all;
+    use serde_json::json;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::{
+        account::Account, hash::Hash, instruction::Instruction, pubkey::Pubkey,
+        transaction::TransactionError,
+    };
+
+    use super::super::mock_rpc::{
+        MockRpc, multiple_accounts_response, requested_pubkeys, rpc_error, rpc_response,
+    };
+    use crate::app::AppError;
+
+    fn slot_rpc() -> MockRpc {
+        MockRpc::new(|request, _| match request {
+            RpcRequest::GetSlot => Ok(json!(1)),
+            _ => Err(rpc_error(&format!("unexpected {request}"))),
+        })
+    }
+
+    #[tokio::test]
+    async fn requests_are_rate_limited() {
+        let client = slot_rpc().builder().requests_per_second(20).build().unwrap();
+
+        let started = Instant::now();
+        for _ in 0..30 {
+            client.get_slot(None).await.unwrap();
+        }
+
+        // the first 20 go out right away, the other 10 wait for a token every 50ms
+        assert!(started.elapsed() >= Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn concurrency_limit_is_separate_from_the_rate() {
+        let client = slot_rpc()
+            .builder()
+            .max_concurrent_requests(1)
+            .requests_per_second(20)
+            .build()
+            .unwrap();
+
+        // one request at a time, but each frees its slot as soon as it completes
+        let started = Instant::now();
+        let slots = join_all((0..20).map(|_| client.get_slot(None))).await;
+
+        assert!(slots.into_iter().all(|slot| slot.is_ok()));
+        assert!(started.elapsed() < Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn close_stops_the_rps_handler() {
+        let client = slot_rpc().client();
+        client.get_slot(None).await.unwrap();
+        assert!(!client.rps_handler.is_finished());
+
+        client.close();
+        assert!(client.is_closed());
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !client.rps_handler.is_finished() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert!(client.get_slot(None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn cached_queue_is_refetched_only_past_the_threshold() {
+        let queue = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let accounts = HashMap::from([(queue, Account::default()), (other, Account::default())]);
+        let slot = Arc::new(AtomicU64::new(1_000));
+        let mock = MockRpc::new({
+            let slot = slot.clone();
+            move |_, params| Ok(multiple_accounts_response(slot.load(Ordering::Relaxed), params, &accounts))
+        });
+        let client = mock.builder().account_cache_slot_threshold(10).build().unwrap();
+        let queue_fetches = || {
+            mock.requests().iter().filter(|(_, params)| requested_pubkeys(params).contains(&queue)).count()
+        };
+
+        client.get_cached_multiple_accounts(&[queue]).await.unwrap();
+        assert_eq!(queue_fetches(), 1);
+
+        // another fetch observes a slot still within the window
+        slot.store(1_010, Ordering::Relaxed);
+        client.get_multiple_accounts(&[other], None, None).await.unwrap();
+        assert!(client.get_cached_multiple_accounts(&[queue]).await.unwrap()[0].is_some());
+        assert_eq!(queue_fetches(), 1);
+
+        slot.store(1_011, Ordering::Relaxed);
+        client.get_multiple_accounts(&[other], None, None).await.unwrap();
+        client.get_cached_multiple_accounts(&[queue]).await.unwrap();
+        assert_eq!(queue_fetches(), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_simulation_without_logs_is_an_error() {
+        let mock = MockRpc::new(|request, _| match request {
+            RpcRequest::SimulateTransaction => Ok(rpc_response(1, json!({ "err": "AccountNotFound", "logs": null }))),
+            _ => Err(rpc_error(&format!("unexpected {request}"))),
+        });
+        let instruction = Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![]);
+
+        let error = mock
+            .client()
+            .call_instructions(None, &[instruction], Hash::new_unique(), None)
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(
+                &error,
+                AppError::SimulationFailed { err: TransactionError::AccountNotFound, logs } if logs.is_empty()
+            ),
+            "{error}"
+        );
+    }
+
+    // 250 pubkeys, the even ones exist with their index as lamports
+    fn indexed_accounts() -> (Vec<Pubkey>, HashMap<Pubkey, Account>) {
+        let pubkeys = (0..250).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        let accounts = pubkeys
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| index % 2 == 0)
+            .map(|(index, pubkey)| (*pubkey, Account { lamports: index as u64, ..Account::default() }))
+            .collect();
+
+        (pubkeys, accounts)
+    }
+
+    fn lamports(accounts: &[Option<Account>]) -> Vec<Option<u64>> {
+        accounts.iter().map(|account| account.as_ref().map(|account| account.lamports)).collect()
+    }
+
+    #[tokio::test]
+    async fn multiple_accounts_are_chunked_in_order() {
+        let (pubkeys, accounts) = indexed_accounts();
+        let mock = MockRpc::with_accounts(1, accounts);
+
+        let fetched = mock.client().get_multiple_accounts(&pubkeys, None, None).await.unwrap();
+
+        let mut chunk_sizes = mock
+            .requests()
+            .iter()
+            .map(|(_, params)| requested_pubkeys(params).len())
+            .collect::<Vec<_>>();
+        chunk_sizes.sort_unstable_by(|size, other_size| other_size.cmp(size));
+        assert_eq!(chunk_sizes, [100, 100, 50]);
+
+        let expected = (0..250u64).map(|index| (index % 2 == 0).then_some(index)).collect::<Vec<_>>();
+        assert_eq!(lamports(&fetched), expected);
+    }
+
+    #[tokio::test]
+    async fn failing_chunk_is_missing_or_an_error() {
+        let (pubkeys, accounts) = indexed_accounts();
+        let failing_pubkey = pubkeys[150];
+        let mock = MockRpc::new(move |_, params| {
+            if requested_pubkeys(params).contains(&failing_pubkey) {
+                return Err(rpc_error("chunk unavailable"));
+            }
+
+            Ok(multiple_accounts_response(1, params, &accounts))
+        });
+        let client = mock.client();
+
+        // the second chunk is reported missing, the others are unaffected
+        let fetched = client.get_multiple_accounts(&pubkeys, None, None).await.unwrap();
+        let expected = (0..250u64)
+            .map(|index| (index % 2 == 0 && !(100..200).contains(&index)).then_some(index))
+            .collect::<Vec<_>>();
+        assert_eq!(lamports(&fetched), expected);
+
+        assert!(client.try_get_multiple_accounts(&pubkeys, None, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn short_chunk_does_not_shift_later_accounts() {
+        let (pubkeys, accounts) = indexed_accounts();
+        let short_pubkey = pubkeys[150];
+        let mock = MockRpc::new(move |_, params| {
+            let mut response = multiple_accounts_response(1, params, &accounts);
+            // the middle chunk misses its last account
+            if requested_pubkeys(params).contains(&short_pubkey) {
+                response["value"].as_array_mut().unwrap().pop();
+            }
+
+            Ok(response)
+        });
+
+        let fetched = mock.client().get_multiple_accounts(&pubkeys, None, None).await.unwrap();
+
+        let expected = (0..250u64)
+            .map(|index| (index % 2 == 0 && !(100..200).contains(&index)).then_some(index))
+            .collect::<Vec<_>>();
+        assert_eq!(lamports(&fetched), expected);
+    }
+}
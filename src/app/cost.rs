@@ -0,0 +1,61 @@
+use solana_sdk::{
+    ed25519_program, hash::Hash, instruction::Instruction, message::AddressLookupTableAccount,
+    secp256k1_program,
+};
+
+use crate::utils::get_compute_unit_limit;
+
+use super::{client::AppClient, error::AppResult};
+
+// charged for every transaction signature and every signature verified by a precompile
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SubmissionCost {
+    // transaction signatures plus secp256k1/ed25519 precompile signatures
+    pub signatures: u64,
+    pub base_fee: u64,
+    pub priority_fee: u64,
+}
+
+impl SubmissionCost {
+    pub fn total(&self) -> u64 {
+        self.base_fee + self.priority_fee
+    }
+}
+
+impl AppClient {
+    // Lamports charged for the transaction built from `instructions`, with cu_price in
+    // micro-lamports per compute unit. The runtime charges the priority fee on the requested
+    // compute unit limit rather than the units actually consumed, so the cost matches what the
+    // simulation used only when the limit is set from it (see get_compute_unit_limit).
+    pub fn estimate_submission_cost(
+        &self,
+        instructions: &[Instruction],
+        alts: Option<&[AddressLookupTableAccount]>,
+        cu_price: u64,
+    ) -> AppResult<SubmissionCost> {
+        // the blockhash doesn't change the signer count
+        let message = self.build_message(alts, instructions, Hash::default())?;
+
+        let precompile_signatures = instructions
+            .iter()
+            .filter(|instruction| {
+                instruction.program_id == secp256k1_program::ID
+                    || instruction.program_id == ed25519_program::ID
+            })
+            // both precompiles start their data with the number of signatures
+            .map(|instruction| instruction.data.first().copied().unwrap_or(0) as u64)
+            .sum::<u64>();
+        let signatures = message.header().num_required_signatures as u64 + precompile_signatures;
+
+        let compute_unit_limit = get_compute_unit_limit(instructions) as u128;
+        let priority_fee = (cu_price as u128 * compute_unit_limit).div_ceil(1_000_000) as u64;
+
+        Ok(SubmissionCost {
+            signatures,
+            base_fee: signatures * LAMPORTS_PER_SIGNATURE,
+            priority_fee,
+        })
+    }
+}
@@ -1,30 +1,166 @@
 use base64::DecodeError;
 use reqwest::Error as ReqwestError;
 use serde_json::Error as SerdeJsonError;
-use solana_client::client_error::ClientError as RpcClientError;
-use solana_sdk::{message::CompileError, signer::SignerError};
-use std::io::Error as IoError;
+use solana_client::{
+    client_error::{ClientError as RpcClientError, ClientErrorKind},
+    nonblocking::pubsub_client::PubsubClientError,
+    rpc_custom_error::JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
+use solana_sdk::{
+    message::CompileError, pubkey::Pubkey, signature::Signature, signer::SignerError,
+    transaction::TransactionError,
+};
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    io::Error as IoError,
+};
 use tokio::sync::AcquireError as SemaphoreAcquireError;
 
 #[derive(Debug)]
 pub enum AppError {
+    ChecksumMismatch(String),
     CompileError(CompileError),
+    CrossbarError(String),
+    CrossCheckFailed(String),
+    // the same account appears twice in an instruction's positional accounts
+    DuplicateAccount(Pubkey),
+    ComputeUnitsNearLimit {
+        units_consumed: u64,
+        compute_unit_limit: u32,
+    },
+    FeedHashNotFound(String),
+    // crossbar has no jobs registered for this feed_hash (404)
+    FeedJobsNotFound(String),
+    // every oracle failed to evaluate the feed's jobs with the same error
+    FeedSourceUnavailable(String),
+    // a secp256k1 instruction the precompile would reject, see validate_secp256k1_instruction
+    InvalidSecpInstruction(String),
+    // fewer successful oracle responses than the feed's min_responses
+    InsufficientResponses {
+        received: usize,
+        min_responses: u32,
+    },
     IoError(IoError),
+    // the jobs crossbar served don't hash to the feed's on-chain feed_hash
+    JobsHashMismatch(String),
     LiquidateIxBuilderError(String),
+    // a configured lookup table is missing, deactivated or not a lookup table
+    LookupTableUnresolved(String),
     LiquidateMarginfiAccountMode,
+    // accounts an instruction references that don't exist on-chain
+    MissingAccounts(Vec<Pubkey>),
     MissingCacheData,
     MissingMarginfiAccount,
+    // none of the queue's oracles advertises a gateway
+    NoGatewaysAvailable {
+        oracles_inspected: usize,
+    },
+    // the rpc hasn't caught up with the requested min_context_slot yet
+    MinContextSlotNotReached(u64),
+    // anyhow error surfaced by switchboard_on_demand_client helpers
+    OnDemandClientError(String),
     ParsingError(String),
+    // boxed with RpcClientError, inline they'd make every AppResult large
+    PubsubClientError(Box<PubsubClientError>),
+    // the feed is on another queue than the one configured
+    QueueMismatch {
+        feed: Pubkey,
+        expected: Pubkey,
+        actual: Pubkey,
+    },
     ReqwestError(ReqwestError),
-    RpcClientError(RpcClientError),
+    RpcClientError(Box<RpcClientError>),
     SemaphoreAcquireError(SemaphoreAcquireError),
+    // the processed slot didn't move past the feed's last update within the RecentSlotStrategy bound
+    SlotNotAdvanced {
+        last_update_slot: u64,
+        recent_slot: u64,
+    },
     SerdeJsonError(SerdeJsonError),
+    SimulationFailed {
+        err: TransactionError,
+        // the log lines explaining the failure, see check_simulation_result, empty when the rpc
+        // node omits simulation logs
+        logs: Vec<String>,
+    },
     SwitchboardInvalidAccount,
     SignerError(SignerError),
+    // an oracle's secp256k1 signature doesn't recover to its eth_address
+    SignatureVerificationFailed(String),
     // SolanaClientReqwestError(SolanaClientReqwestError),
+    TransactionNotFound(Signature),
+    // the program (or runtime) rejected a sent transaction, retrying won't help
+    TransactionRejected(TransactionError),
+    // wire size of a transaction over the packet limit, see ensure_transaction_fits
     TransactionTooLarge(usize),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryAction {
+    // misconfigurations, invalid data or a rejected transaction, retrying won't help
+    Fail,
+    // transient network/rpc failure, retry the same call
+    Retry,
+    // the blockhash expired or the rpc node lags behind the cluster, refetch the blockhash (and
+    // slot) the call was built with before retrying
+    RefreshAndRetry,
+}
+
+impl AppError {
+    // Whether the same call may succeed when retried, see retry_action.
+    pub fn is_retryable(&self) -> bool {
+        self.retry_action() != RetryAction::Fail
+    }
+
+    pub fn retry_action(&self) -> RetryAction {
+        match self {
+            AppError::ReqwestError(error) if error.status().is_some_and(|status| status.is_client_error()) => {
+                RetryAction::Fail
+            }
+            AppError::ReqwestError(_)
+            | AppError::CrossbarError(_)
+            | AppError::InsufficientResponses { .. }
+            | AppError::MinContextSlotNotReached(_)
+            | AppError::SlotNotAdvanced { .. } => RetryAction::Retry,
+            AppError::SimulationFailed {
+                err: TransactionError::BlockhashNotFound,
+                ..
+            } => RetryAction::RefreshAndRetry,
+            AppError::RpcClientError(rpc_error) => rpc_client_error_retry_action(rpc_error),
+            _ => RetryAction::Fail,
+        }
+    }
+}
+
+fn rpc_client_error_retry_action(rpc_error: &RpcClientError) -> RetryAction {
+    match rpc_error.get_transaction_error() {
+        Some(TransactionError::BlockhashNotFound) => return RetryAction::RefreshAndRetry,
+        Some(_) => return RetryAction::Fail,
+        None => {}
+    }
+
+    if let ClientErrorKind::RpcError(RpcError::RpcResponseError { code, data, .. }) = rpc_error.kind()
+        && (*code == JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+            || matches!(data, RpcResponseErrorData::NodeUnhealthy { .. }))
+    {
+        return RetryAction::RefreshAndRetry;
+    }
+
+    // some rpc providers only carry these in the message, and send_and_confirm_transaction gives
+    // up with "unable to confirm transaction" once the blockhash expired
+    let message = rpc_error.to_string();
+    if ["Node is behind", "Blockhash not found", "unable to confirm transaction"]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+    {
+        return RetryAction::RefreshAndRetry;
+    }
+
+    RetryAction::Retry
+}
+
 impl From<CompileError> for AppError {
     fn from(value: CompileError) -> Self {
         AppError::CompileError(value)
@@ -43,6 +179,12 @@ impl From<IoError> for AppError {
     }
 }
 
+impl From<PubsubClientError> for AppError {
+    fn from(value: PubsubClientError) -> Self {
+        AppError::PubsubClientError(Box::new(value))
+    }
+}
+
 impl From<ReqwestError> for AppError {
     fn from(value: ReqwestError) -> Self {
         AppError::ReqwestError(value)
@@ -51,7 +193,7 @@ impl From<ReqwestError> for AppError {
 
 impl From<RpcClientError> for AppError {
     fn from(value: RpcClientError) -> Self {
-        AppError::RpcClientError(value)
+        AppError::RpcClientError(Box::new(value))
     }
 }
 
@@ -73,4 +215,114 @@ impl From<SignerError> for AppError {
     }
 }
 
+impl Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::ChecksumMismatch(error) => write!(f, "consensus checksum mismatch: {error}"),
+            AppError::CompileError(error) => write!(f, "failed to compile message: {error}"),
+            AppError::CrossbarError(error) => write!(f, "crossbar request failed: {error}"),
+            AppError::CrossCheckFailed(error) => write!(f, "gateway cross check failed: {error}"),
+            AppError::DuplicateAccount(pubkey) => write!(f, "duplicate account in instruction: {pubkey}"),
+            AppError::ComputeUnitsNearLimit {
+                units_consumed,
+                compute_unit_limit,
+            } => write!(f, "simulation consumed {units_consumed}/{compute_unit_limit} compute units"),
+            AppError::FeedHashNotFound(feed_hash) => write!(f, "no feed found for feed_hash {feed_hash}"),
+            AppError::FeedJobsNotFound(feed_hash) => write!(f, "crossbar has no jobs for feed_hash {feed_hash}"),
+            AppError::FeedSourceUnavailable(error) => write!(f, "feed data source unavailable: {error}"),
+            AppError::InvalidSecpInstruction(error) => write!(f, "invalid secp256k1 instruction: {error}"),
+            AppError::InsufficientResponses { received, min_responses } => {
+                write!(f, "{received} successful oracle responses, the feed requires {min_responses}")
+            }
+            AppError::IoError(error) => write!(f, "io error: {error}"),
+            AppError::JobsHashMismatch(error) => write!(f, "jobs don't match the feed's feed_hash: {error}"),
+            AppError::LiquidateIxBuilderError(error) => write!(f, "failed to build liquidate instruction: {error}"),
+            AppError::LookupTableUnresolved(error) => write!(f, "lookup table unresolved: {error}"),
+            AppError::LiquidateMarginfiAccountMode => write!(f, "invalid marginfi account mode for liquidation"),
+            AppError::MissingAccounts(pubkeys) => write!(f, "missing accounts: {pubkeys:?}"),
+            AppError::MissingCacheData => write!(f, "missing cache data"),
+            AppError::MissingMarginfiAccount => write!(f, "missing marginfi account"),
+            AppError::NoGatewaysAvailable { oracles_inspected } => {
+                write!(f, "none of the {oracles_inspected} oracles inspected advertises a gateway")
+            }
+            AppError::MinContextSlotNotReached(slot) => write!(f, "rpc hasn't reached min context slot {slot}"),
+            AppError::OnDemandClientError(error) => write!(f, "switchboard on-demand client error: {error}"),
+            AppError::ParsingError(error) => write!(f, "parsing error: {error}"),
+            AppError::PubsubClientError(error) => write!(f, "pubsub error: {error}"),
+            AppError::QueueMismatch { feed, expected, actual } => {
+                write!(f, "feed {feed} is on queue {actual}, expected {expected}")
+            }
+            AppError::ReqwestError(error) => write!(f, "http request failed: {error}"),
+            AppError::RpcClientError(error) => write!(f, "rpc request failed: {error}"),
+            AppError::SemaphoreAcquireError(error) => write!(f, "client closed: {error}"),
+            AppError::SlotNotAdvanced {
+                last_update_slot,
+                recent_slot,
+            } => write!(
+                f,
+                "recent slot {recent_slot} didn't advance past the feed's last update at {last_update_slot}"
+            ),
+            AppError::SerdeJsonError(error) => write!(f, "json error: {error}"),
+            AppError::SimulationFailed { err, .. } => write!(f, "simulation failed: {err}"),
+            AppError::SwitchboardInvalidAccount => write!(f, "invalid switchboard account"),
+            AppError::SignerError(error) => write!(f, "signer error: {error}"),
+            AppError::SignatureVerificationFailed(error) => write!(f, "signature verification failed: {error}"),
+            AppError::TransactionNotFound(signature) => write!(f, "transaction not found: {signature}"),
+            AppError::TransactionRejected(error) => write!(f, "transaction rejected: {error}"),
+            AppError::TransactionTooLarge(size) => write!(f, "transaction too large: {size} bytes"),
+        }
+    }
+}
+
+impl Error for AppError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AppError::CompileError(error) => Some(error),
+            AppError::IoError(error) => Some(error),
+            AppError::PubsubClientError(error) => Some(error.as_ref()),
+            AppError::ReqwestError(error) => Some(error),
+            AppError::RpcClientError(error) => Some(error.as_ref()),
+            AppError::SemaphoreAcquireError(error) => Some(error),
+            AppError::SerdeJsonError(error) => Some(error),
+            AppError::SignerError(error) => Some(error),
+            AppError::SimulationFailed { err, .. } => Some(err),
+            AppError::TransactionRejected(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
 pub type AppResult<T> = Result<T, AppError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rpc_response_error(code: i64, message: &str) -> AppError {
+        RpcClientError::from(RpcError::RpcResponseError {
+            code,
+            message: message.to_string(),
+            data: RpcResponseErrorData::Empty,
+        })
+        .into()
+    }
+
+    #[test]
+    fn node_behind_and_expired_blockhash_refresh_before_retrying() {
+        let refreshed_errors = [
+            rpc_response_error(JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY, "Node is unhealthy"),
+            rpc_response_error(-32000, "Node is behind by 150 slots"),
+            rpc_response_error(-32002, "Transaction simulation failed: Blockhash not found"),
+            RpcClientError::from(TransactionError::BlockhashNotFound).into(),
+        ];
+        for error in refreshed_errors {
+            assert_eq!(error.retry_action(), RetryAction::RefreshAndRetry, "{error}");
+        }
+
+        assert_eq!(rpc_response_error(-32000, "connection reset").retry_action(), RetryAction::Retry);
+        assert_eq!(
+            AppError::from(RpcClientError::from(TransactionError::AccountNotFound)).retry_action(),
+            RetryAction::Fail
+        );
+    }
+}
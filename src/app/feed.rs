@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use switchboard_on_demand::{OracleAccountData, PullFeedAccountData};
+use switchboard_on_demand_client::QueueAccountData;
+
+use crate::utils::{
+    VALUE_SCALE, parse_oracle_account, parse_queue_account, parse_swb_ignore_alignment,
+};
+
+use super::{
+    client::AppClient,
+    error::{AppError, AppResult},
+};
+
+// Parses a PullFeed account, rejecting accounts not owned by the on-demand program (program_id):
+// the discriminator alone can be spoofed by any program.
+pub(super) fn parse_feed_account(
+    feed_pubkey: &Pubkey,
+    feed_account: &Account,
+    program_id: &Pubkey,
+) -> AppResult<PullFeedAccountData> {
+    if feed_account.owner != *program_id {
+        tracing::warn!(
+            "Feed {feed_pubkey} is owned by {} instead of {program_id}",
+            feed_account.owner
+        );
+        return Err(AppError::SwitchboardInvalidAccount);
+    }
+
+    parse_swb_ignore_alignment(&feed_account.data)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct FeedResult {
+    // latest aggregated value, already scaled down by `scale`
+    pub value: Decimal,
+    // number of decimals the on-chain i128 value is stored with
+    pub scale: u32,
+    // slot the value was produced at
+    pub slot: u64,
+    pub timestamp: i64,
+}
+
+impl FeedResult {
+    // None when the feed was never updated
+    pub fn from_feed_data(feed_data: &PullFeedAccountData) -> Option<Self> {
+        Some(Self {
+            value: feed_data.result.value()?,
+            scale: VALUE_SCALE,
+            slot: feed_data.result.result_slot()?,
+            timestamp: feed_data.last_update_timestamp,
+        })
+    }
+
+    pub fn from_account_data(data: &[u8]) -> AppResult<Self> {
+        let feed_data = parse_swb_ignore_alignment(data)?;
+
+        Self::from_feed_data(&feed_data)
+            .ok_or_else(|| AppError::ParsingError("Feed has no result yet".to_string()))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FeedInfo {
+    // latest aggregated value, None when the feed was never updated
+    pub value: Option<Decimal>,
+    pub result_slot: Option<u64>,
+    pub last_update_timestamp: i64,
+    // slots elapsed since the latest result, None when the feed was never updated
+    pub staleness: Option<u64>,
+    // whether staleness exceeds the feed's own max_staleness
+    pub is_stale: bool,
+}
+
+// Everything a submission reads on-chain before contacting the gateways.
+#[derive(Clone)]
+pub struct SubmissionAccounts {
+    pub feed: PullFeedAccountData,
+    pub queue: QueueAccountData,
+    // every oracle of the queue in queue order, None when its account is missing or unparsable
+    pub oracles: Vec<(Pubkey, Option<OracleAccountData>)>,
+}
+
+impl AppClient {
+    // Loads the accounts of a submission in as few getMultipleAccounts calls as their dependencies
+    // allow. The feed is always read fresh, together with the queue when queue_hint is its queue
+    // (e.g. the queue of a batch), otherwise the queue is read after it through the account cache.
+    // The queue's oracles are then read in one chunked call, also through the account cache.
+    // The queue is always the feed's own, checked against expected_queue when one is configured.
+    pub async fn load_submission_accounts(
+        &self,
+        feed_pubkey: &Pubkey,
+        queue_hint: Option<Pubkey>,
+    ) -> AppResult<SubmissionAccounts> {
+        let mut pubkeys = vec![*feed_pubkey];
+        pubkeys.extend(queue_hint);
+
+        let mut accounts = self.get_multiple_accounts(&pubkeys, None, None).await?.into_iter();
+        let feed_account = accounts
+            .next()
+            .flatten()
+            .ok_or_else(|| AppError::ParsingError(format!("Feed account not found - {feed_pubkey}")))?;
+        let feed = self.parse_submission_feed(feed_pubkey, &feed_account)?;
+
+        let queue = match (queue_hint, accounts.next().flatten()) {
+            (Some(queue_pubkey), Some(queue_account)) if queue_pubkey == feed.queue => {
+                parse_queue_account(&queue_account.data)?
+            }
+            _ => self.load_queue(&feed.queue).await?,
+        };
+
+        let oracle_keys = queue.oracle_keys();
+        let oracle_accounts = self.get_cached_multiple_accounts(&oracle_keys).await?;
+
+        let oracles = oracle_keys
+            .into_iter()
+            .zip(oracle_accounts)
+            .map(|(oracle_pubkey, oracle_account)| {
+                let oracle_account_data = oracle_account.and_then(|oracle_account| {
+                    parse_oracle_account(&oracle_account.data)
+                        .inspect_err(|app_error| {
+                            tracing::warn!(
+                                "Failed to parse OracleAccountData - {oracle_pubkey}\n{app_error:#?}"
+                            );
+                        })
+                        .ok()
+                });
+
+                (oracle_pubkey, oracle_account_data)
+            })
+            .collect();
+
+        Ok(SubmissionAccounts {
+            feed,
+            queue,
+            oracles,
+        })
+    }
+
+    // Parses the feed of a submission as parse_feed_account does, also checking that it is on
+    // expected_queue when one is configured.
+    pub(crate) fn parse_submission_feed(
+        &self,
+        feed_pubkey: &Pubkey,
+        feed_account: &Account,
+    ) -> AppResult<PullFeedAccountData> {
+        let feed = parse_feed_account(feed_pubkey, feed_account, &self.cluster().program_id())?;
+
+        if let Some(expected_queue) = self.expected_queue()
+            && feed.queue != expected_queue
+        {
+            return Err(AppError::QueueMismatch {
+                feed: *feed_pubkey,
+                expected: expected_queue,
+                actual: feed.queue,
+            });
+        }
+
+        Ok(feed)
+    }
+
+    // Pass the slot of a submission as min_context_slot to read the feed after that submission,
+    // see get_account.
+    pub async fn load_feed(
+        &self,
+        feed_pubkey: &Pubkey,
+        min_context_slot: Option<u64>,
+    ) -> AppResult<PullFeedAccountData> {
+        let feed_account = self.get_account(feed_pubkey, min_context_slot).await?;
+
+        parse_feed_account(feed_pubkey, &feed_account, &self.cluster().program_id())
+    }
+
+    pub async fn get_feed_result(
+        &self,
+        feed_pubkey: &Pubkey,
+        min_context_slot: Option<u64>,
+    ) -> AppResult<FeedResult> {
+        let feed_data = self.load_feed(feed_pubkey, min_context_slot).await?;
+
+        FeedResult::from_feed_data(&feed_data).ok_or_else(|| {
+            AppError::ParsingError(format!("Feed has no result yet - {feed_pubkey}"))
+        })
+    }
+
+    // Groups feeds by the queue they submit to, feeds whose account is missing or unparsable are
+    // grouped under None.
+    pub async fn group_feeds_by_queue(
+        &self,
+        feeds: &[Pubkey],
+    ) -> AppResult<HashMap<Option<Pubkey>, Vec<Pubkey>>> {
+        let feed_accounts = self.get_multiple_accounts(feeds, None, None).await?;
+        let program_id = self.cluster().program_id();
+
+        let mut feeds_by_queue: HashMap<Option<Pubkey>, Vec<Pubkey>> = HashMap::new();
+        for (feed_pubkey, feed_account) in feeds.iter().zip(feed_accounts) {
+            let queue_pubkey = feed_account
+                .and_then(|feed_account| parse_feed_account(feed_pubkey, &feed_account, &program_id).ok())
+                .map(|pull_feed_account_data| pull_feed_account_data.queue);
+
+            feeds_by_queue.entry(queue_pubkey).or_default().push(*feed_pubkey);
+        }
+
+        Ok(feeds_by_queue)
+    }
+
+    pub async fn read_feeds(
+        &self,
+        feeds: &[Pubkey],
+        min_context_slot: Option<u64>,
+    ) -> AppResult<HashMap<Pubkey, FeedInfo>> {
+        let (accounts_result, slot_result) = tokio::join!(
+            self.get_multiple_accounts(feeds, None, min_context_slot),
+            self.get_slot(None)
+        );
+
+        let feed_accounts = accounts_result?;
+        let current_slot = slot_result?;
+        let program_id = self.cluster().program_id();
+
+        let feed_infos = feed_accounts
+            .into_iter()
+            .zip(feeds)
+            .filter_map(|(account, feed_pubkey)| {
+                let Some(feed_account) = account else {
+                    tracing::warn!("getMultipleAccounts returned None for - feed_pubkey: {feed_pubkey}");
+                    return None;
+                };
+
+                let pull_feed_account_data = match parse_feed_account(feed_pubkey, &feed_account, &program_id) {
+                    Err(app_error) => {
+                        tracing::warn!(
+                            "Skipping feed, failed to parse PullFeedAccountData - {feed_pubkey}\n{app_error:#?}"
+                        );
+                        return None;
+                    }
+                    Ok(pull_feed_account_data) => pull_feed_account_data,
+                };
+
+                let result_slot = pull_feed_account_data.result.result_slot();
+                let staleness = result_slot.map(|slot| current_slot.saturating_sub(slot));
+                let is_stale = staleness
+                    .is_none_or(|staleness| staleness > u64::from(pull_feed_account_data.max_staleness));
+
+                let feed_info = FeedInfo {
+                    value: pull_feed_account_data.result.value(),
+                    result_slot,
+                    last_update_timestamp: pull_feed_account_data.last_update_timestamp,
+                    staleness,
+                    is_stale,
+                };
+
+                Some((*feed_pubkey, feed_info))
+            })
+            .collect();
+
+        Ok(feed_infos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::{account::Account, pubkey::Pubkey};
+
+    use crate::utils::Cluster;
+
+    use super::super::mock_rpc::{MockRpc, feed_account, queue_account};
+
+    #[tokio::test]
+    async fn queue_hint_reads_the_queue_with_the_feed() {
+        let (feed, queue, oracle) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let accounts = HashMap::from([
+            (feed, feed_account(Cluster::default().program_id(), |feed_data| feed_data.queue = queue)),
+            (queue, queue_account(&[oracle])),
+            (oracle, Account::default()),
+        ]);
+
+        // feed and queue in one request, then the oracles
+        for (queue_hint, requests) in [(Some(queue), 2), (None, 3), (Some(Pubkey::new_unique()), 3)] {
+            let mock = MockRpc::with_accounts(1, accounts.clone());
+
+            let submission_accounts = mock.client().load_submission_accounts(&feed, queue_hint).await.unwrap();
+
+            assert_eq!(submission_accounts.queue.oracle_keys(), [oracle]);
+            assert_eq!(submission_accounts.oracles.len(), 1);
+            assert_eq!(mock.count(RpcRequest::GetMultipleAccounts), requests, "queue_hint {queue_hint:?}");
+            assert_eq!(mock.requests().len(), requests);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use solana_address_lookup_table_interface::{
+    program::ID as ADDRESS_LOOKUP_TABLE_PID, state::AddressLookupTable,
+};
+use solana_sdk::{message::AddressLookupTableAccount, pubkey::Pubkey};
+
+use super::{
+    client::AppClient,
+    error::{AppError, AppResult},
+};
+
+impl AppClient {
+    // Resolves lookup tables for v0 messages through the account cache. A table that is missing,
+    // not owned by the lookup table program or deactivated would make the transaction fail to load
+    // on-chain, so it is reported here with its address instead.
+    pub async fn load_lookup_tables(
+        &self,
+        lookup_table_pubkeys: &[Pubkey],
+    ) -> AppResult<Vec<AddressLookupTableAccount>> {
+        let lookup_table_accounts = self
+            .get_cached_multiple_accounts(lookup_table_pubkeys)
+            .await?;
+
+        lookup_table_pubkeys
+            .iter()
+            .zip(lookup_table_accounts)
+            .map(|(lookup_table_pubkey, lookup_table_account)| {
+                let unresolved = |reason: &str| {
+                    AppError::LookupTableUnresolved(format!("{lookup_table_pubkey} {reason}"))
+                };
+
+                let lookup_table_account =
+                    lookup_table_account.ok_or_else(|| unresolved("not found"))?;
+                if lookup_table_account.owner != ADDRESS_LOOKUP_TABLE_PID {
+                    return Err(unresolved("is not a lookup table"));
+                }
+
+                let lookup_table = AddressLookupTable::deserialize(&lookup_table_account.data)
+                    .map_err(|error| unresolved(&format!("failed to deserialize: {error}")))?;
+                if lookup_table.meta.deactivation_slot != u64::MAX {
+                    return Err(unresolved("is deactivated"));
+                }
+
+                Ok(AddressLookupTableAccount {
+                    key: *lookup_table_pubkey,
+                    addresses: lookup_table.addresses.to_vec(),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD as base64};
+use serde_json::{Value, json};
+use solana_client::{
+    client_error::Result as ClientResult,
+    rpc_request::{RpcError, RpcRequest, RpcResponseErrorData},
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_sdk::{account::Account, pubkey::Pubkey, signature::Keypair};
+use switchboard_on_demand::{
+    Discriminator, OracleAccountData, PullFeedAccountData, QUEUE_ACCOUNT_DISCRIMINATOR,
+};
+use switchboard_on_demand_client::QueueAccountData;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+use super::{AppClient, AppClientBuilder};
+
+type Handler = dyn Fn(RpcRequest, &Value) -> Result<Value, MockRpcError> + Send + Sync;
+
+// A json-rpc error response the handler answers with, kept small so the handlers don't return
+// a large ClientError.
+#[derive(Debug)]
+pub(crate) struct MockRpcError {
+    code: i64,
+    message: String,
+}
+
+// Rpc transport for tests: answers every request through the handler instead of the network and
+// records the requests it was sent, see AppClientBuilder::rpc_sender.
+#[derive(Clone)]
+pub(crate) struct MockRpc {
+    handler: Arc<Handler>,
+    requests: Arc<Mutex<Vec<(RpcRequest, Value)>>>,
+}
+
+impl MockRpc {
+    pub(crate) fn new(
+        handler: impl Fn(RpcRequest, &Value) -> Result<Value, MockRpcError> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            handler: Arc::new(handler),
+            requests: Arc::default(),
+        }
+    }
+
+    // Serves getMultipleAccounts (and getAccountInfo) from the given accounts, every other request
+    // fails.
+    pub(crate) fn with_accounts(slot: u64, accounts: HashMap<Pubkey, Account>) -> Self {
+        Self::new(move |request, params| {
+            accounts_response(slot, request, params, &accounts)
+                .ok_or_else(|| rpc_error(&format!("unexpected {request}")))
+        })
+    }
+
+    // A client over this transport with fast retries and a rate limit tests don't wait on.
+    pub(crate) fn builder(&self) -> AppClientBuilder {
+        AppClient::builder(&Keypair::new().to_base58_string(), "http://mock.rpc".to_string())
+            .rpc_sender(self.clone())
+            .requests_per_second(10_000)
+            .retry_base_delay(std::time::Duration::from_millis(1))
+            .retry_max_delay(std::time::Duration::from_millis(1))
+    }
+
+    pub(crate) fn client(&self) -> AppClient {
+        self.builder().build().unwrap()
+    }
+
+    pub(crate) fn requests(&self) -> Vec<(RpcRequest, Value)> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub(crate) fn count(&self, request: RpcRequest) -> usize {
+        self.requests().iter().filter(|(sent, _)| *sent == request).count()
+    }
+}
+
+#[async_trait]
+impl RpcSender for MockRpc {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        self.requests.lock().unwrap().push((request, params.clone()));
+        (self.handler)(request, &params).map_err(|MockRpcError { code, message }| {
+            RpcError::RpcResponseError {
+                code,
+                message,
+                data: RpcResponseErrorData::Empty,
+            }
+            .into()
+        })
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        "mock".to_string()
+    }
+}
+
+// A generic (retryable) json-rpc error.
+pub(crate) fn rpc_error(message: &str) -> MockRpcError {
+    MockRpcError {
+        code: -32000,
+        message: message.to_string(),
+    }
+}
+
+pub(crate) fn rpc_response(slot: u64, value: Value) -> Value {
+    json!({ "context": { "slot": slot }, "value": value })
+}
+
+pub(crate) fn ui_account(account: &Account) -> Value {
+    json!({
+        "lamports": account.lamports,
+        "data": [base64.encode(&account.data), "base64"],
+        "owner": account.owner.to_string(),
+        "executable": account.executable,
+        "rentEpoch": account.rent_epoch,
+        "space": account.data.len(),
+    })
+}
+
+// The pubkeys a getMultipleAccounts request asks for, in order.
+pub(crate) fn requested_pubkeys(params: &Value) -> Vec<Pubkey> {
+    params[0]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|pubkey| pubkey.as_str().unwrap().parse().unwrap())
+        .collect()
+}
+
+pub(crate) fn multiple_accounts_response(slot: u64, params: &Value, accounts: &HashMap<Pubkey, Account>) -> Value {
+    let value = requested_pubkeys(params)
+        .iter()
+        .map(|pubkey| accounts.get(pubkey).map_or(Value::Null, ui_account))
+        .collect::<Vec<_>>();
+
+    rpc_response(slot, Value::Array(value))
+}
+
+// Answers getMultipleAccounts and getAccountInfo from accounts, None for any other request.
+pub(crate) fn accounts_response(
+    slot: u64,
+    request: RpcRequest,
+    params: &Value,
+    accounts: &HashMap<Pubkey, Account>,
+) -> Option<Value> {
+    match request {
+        RpcRequest::GetMultipleAccounts => Some(multiple_accounts_response(slot, params, accounts)),
+        RpcRequest::GetAccountInfo => {
+            let pubkey = params[0].as_str().unwrap().parse::<Pubkey>().unwrap();
+            Some(rpc_response(slot, accounts.get(&pubkey).map_or(Value::Null, ui_account)))
+        }
+        _ => None,
+    }
+}
+
+// A feed account of the on-demand program, zeroed apart from what set_feed_data changes.
+pub(crate) fn feed_account(program_id: Pubkey, set_feed_data: impl FnOnce(&mut PullFeedAccountData)) -> Account {
+    let mut feed_data: PullFeedAccountData = bytemuck::Zeroable::zeroed();
+    set_feed_data(&mut feed_data);
+
+    let mut data = PullFeedAccountData::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(bytemuck::bytes_of(&feed_data));
+
+    Account {
+        lamports: 1,
+        data,
+        owner: program_id,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+// A queue account listing the given oracles, zeroed otherwise.
+pub(crate) fn queue_account(oracle_keys: &[Pubkey]) -> Account {
+    let mut queue_data: QueueAccountData = bytemuck::Zeroable::zeroed();
+    queue_data.oracle_keys[..oracle_keys.len()].copy_from_slice(oracle_keys);
+    queue_data.oracle_keys_len = oracle_keys.len() as u32;
+
+    let mut data = QUEUE_ACCOUNT_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(bytemuck::bytes_of(&queue_data));
+
+    Account {
+        lamports: 1,
+        data,
+        ..Account::default()
+    }
+}
+
+// An oracle account advertising gateway_uri, zeroed otherwise.
+pub(crate) fn oracle_account(gateway_uri: &str) -> Account {
+    let mut oracle_data: OracleAccountData = bytemuck::Zeroable::zeroed();
+    oracle_data.gateway_uri[..gateway_uri.len()].copy_from_slice(gateway_uri.as_bytes());
+
+    let mut data = OracleAccountData::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(bytemuck::bytes_of(&oracle_data));
+
+    Account {
+        lamports: 1,
+        data,
+        ..Account::default()
+    }
+}
+
+// A local http server (crossbar, gateway) answering every request with status and body. Returns
+// its url and the number of requests served.
+pub(crate) async fn serve_http(status: &'static str, body: String) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(AtomicUsize::new(0));
+
+    let served_requests = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).await;
+            served_requests.fetch_add(1, Ordering::SeqCst);
+
+            let response = format!(
+                "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    });
+
+    (url, requests)
+}
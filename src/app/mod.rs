@@ -1,5 +1,26 @@
+mod builder;
 mod client;
+mod cost;
 mod error;
+mod feed;
+mod lookup_table;
+#[cfg(test)]
+pub(crate) mod mock_rpc;
+mod offline;
+mod oracle;
+mod preflight;
+mod program;
+mod signer;
+mod subscription;
+mod transaction;
 
+pub use builder::*;
 pub use client::*;
+pub use cost::*;
 pub use error::*;
+pub use feed::*;
+pub use offline::*;
+pub use program::*;
+pub use signer::*;
+pub use subscription::*;
+pub use transaction::*;
@@ -0,0 +1,97 @@
+use std::str::FromStr;
+
+use base64::{Engine, engine::general_purpose::STANDARD as base64};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    hash::Hash,
+    instruction::Instruction,
+    message::{AddressLookupTableAccount, VersionedMessage},
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::SignerError,
+    transaction::VersionedTransaction,
+};
+
+use super::{
+    client::AppClient,
+    error::{AppError, AppResult},
+};
+
+// A blockhash is only accepted for 150 slots after it was produced.
+pub const BLOCKHASH_VALIDITY_SLOTS: u64 = 150;
+
+// Everything an air-gapped signer needs to sign a submission, the online side keeps it around
+// to assemble the final transaction once the signatures come back.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UnsignedSubmission {
+    // base64 encoded serialized VersionedMessage, the exact bytes to sign
+    pub message: String,
+    // base58 pubkeys of the required signers, in the order the signatures must be attached
+    pub required_signers: Vec<String>,
+    pub recent_blockhash: String,
+}
+
+impl AppClient {
+    pub fn build_unsigned_submission(
+        &self,
+        alts: Option<&[AddressLookupTableAccount]>,
+        instructions: &[Instruction],
+        recent_blockhash: Hash,
+    ) -> AppResult<UnsignedSubmission> {
+        let message = self.build_message(alts, instructions, recent_blockhash)?;
+
+        let num_required_signatures = usize::from(message.header().num_required_signatures);
+        let required_signers = message.static_account_keys()[..num_required_signatures]
+            .iter()
+            .map(|pubkey| pubkey.to_string())
+            .collect::<Vec<_>>();
+
+        tracing::warn!(
+            "Unsigned submission embeds blockhash {recent_blockhash}, it has to be signed and broadcast within {BLOCKHASH_VALIDITY_SLOTS} slots (~1min) of it"
+        );
+
+        Ok(UnsignedSubmission {
+            message: base64.encode(message.serialize()),
+            required_signers,
+            recent_blockhash: recent_blockhash.to_string(),
+        })
+    }
+}
+
+// Attaches the offline produced signatures to the submission's message. Every required signer
+// must have a signature that verifies against the message bytes.
+pub fn assemble_signed_transaction(
+    submission: &UnsignedSubmission,
+    signatures: &[(Pubkey, Signature)],
+) -> AppResult<VersionedTransaction> {
+    let message_bytes = base64.decode(&submission.message)?;
+    let message: VersionedMessage = bincode::deserialize(&message_bytes)
+        .map_err(|error| AppError::ParsingError(format!("Invalid message: {error}")))?;
+
+    let signatures = submission
+        .required_signers
+        .iter()
+        .map(|signer| {
+            let signer = Pubkey::from_str(signer)
+                .map_err(|error| AppError::ParsingError(format!("Invalid signer {signer}: {error}")))?;
+
+            let (_, signature) = signatures
+                .iter()
+                .find(|(pubkey, _)| *pubkey == signer)
+                .ok_or(AppError::SignerError(SignerError::NotEnoughSigners))?;
+
+            if !signature.verify(signer.as_ref(), &message_bytes) {
+                return Err(AppError::SignerError(SignerError::InvalidInput(format!(
+                    "Signature doesn't verify for signer {signer}"
+                ))));
+            }
+
+            Ok(*signature)
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+
+    Ok(VersionedTransaction {
+        signatures,
+        message,
+    })
+}
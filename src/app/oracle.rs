@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use futures::future::join_all;
+use solana_sdk::pubkey::Pubkey;
+use switchboard_on_demand::OracleAccountData;
+
+use crate::{
+    report::OracleStatus,
+    utils::{parse_oracle_account, parse_oracle_stats_account},
+};
+
+use super::{client::AppClient, error::AppResult};
+
+impl AppClient {
+    // Stake score each oracle earned over its last finalized epoch, read from its OracleStats
+    // account. Oracles whose stats account is missing or unparsable are left out.
+    pub async fn get_oracle_stake_scores(&self, oracles: &[Pubkey]) -> AppResult<HashMap<Pubkey, u64>> {
+        let stats_keys = oracles
+            .iter()
+            .map(OracleAccountData::stats_key)
+            .collect::<Vec<_>>();
+
+        let stats_accounts = self.get_cached_multiple_accounts(&stats_keys).await?;
+
+        let stake_scores = oracles
+            .iter()
+            .zip(stats_accounts)
+            .filter_map(|(oracle_pubkey, stats_account)| {
+                let Some(stats_account) = stats_account else {
+                    tracing::warn!("Missing OracleStats account for - {oracle_pubkey}");
+                    return None;
+                };
+
+                match parse_oracle_stats_account(&stats_account.data) {
+                    Err(app_error) => {
+                        tracing::warn!(
+                            "Failed to parse OracleStats account for - {oracle_pubkey}\n{app_error:#?}"
+                        );
+                        None
+                    }
+                    Ok(oracle_stats) => {
+                        Some((*oracle_pubkey, oracle_stats.finalized_epoch.stake_score))
+                    }
+                }
+            })
+            .collect();
+
+        Ok(stake_scores)
+    }
+
+    // Every oracle of the queue with its gateway, stake score and whether the gateway answers,
+    // gateways are pinged concurrently.
+    pub async fn get_queue_oracle_statuses(&self, queue_pubkey: &Pubkey) -> AppResult<Vec<OracleStatus>> {
+        let queue_account_data = self.load_queue(queue_pubkey).await?;
+        let oracle_keys = queue_account_data.oracle_keys();
+
+        let (oracle_accounts, stake_scores) = tokio::join!(
+            self.get_cached_multiple_accounts(&oracle_keys),
+            self.get_oracle_stake_scores(&oracle_keys)
+        );
+        let oracle_accounts = oracle_accounts?;
+        let stake_scores = stake_scores.unwrap_or_else(|app_error| {
+            tracing::warn!("Failed to read oracle stake scores\n{app_error:#?}");
+            HashMap::new()
+        });
+
+        let statuses = oracle_keys.iter().zip(oracle_accounts).map(async |(oracle_pubkey, oracle_account)| {
+            let gateway_uri = oracle_account.and_then(|oracle_account| {
+                match parse_oracle_account(&oracle_account.data) {
+                    Err(app_error) => {
+                        tracing::warn!("Failed to parse OracleAccountData for - {oracle_pubkey}\n{app_error:#?}");
+                        None
+                    }
+                    Ok(oracle_account_data) => oracle_account_data.gateway_uri(),
+                }
+            });
+
+            let reachable = match &gateway_uri {
+                None => false,
+                Some(gateway_uri) => match self.gateway(gateway_uri.clone()).ping().await {
+                    Err(app_error) => {
+                        tracing::warn!("Gateway - {gateway_uri} of {oracle_pubkey} is unreachable\n{app_error:#?}");
+                        false
+                    }
+                    Ok(()) => true,
+                },
+            };
+
+            OracleStatus {
+                oracle: *oracle_pubkey,
+                gateway_uri,
+                stake_score: stake_scores.get(oracle_pubkey).copied(),
+                reachable,
+            }
+        });
+
+        Ok(join_all(statuses).await)
+    }
+}
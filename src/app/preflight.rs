@@ -0,0 +1,135 @@
+use std::collections::HashSet;
+
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, sysvar};
+use solana_system_interface::program;
+
+use super::{
+    client::AppClient,
+    error::{AppError, AppResult},
+};
+
+// Sysvars and programs the submit instructions pass along. They always exist, and the Instructions
+// sysvar isn't even stored as an account, so getMultipleAccounts would report it as missing.
+const SKIPPED_ACCOUNTS: [Pubkey; 4] = [
+    sysvar::instructions::ID,
+    sysvar::slot_hashes::ID,
+    program::ID,
+    spl_token::ID,
+];
+
+impl AppClient {
+    // Checks that every account the instructions reference exists before a transaction is spent
+    // on them, failing with MissingAccounts listing the ones that don't. Only the accounts a
+    // misconfiguration can leave missing are read (feed, queue, program_state, reward_vault,
+    // token_mint, oracles and their stats), SKIPPED_ACCOUNTS and the invoked programs are not.
+    // Rpc failures are returned as errors rather than reported as missing accounts.
+    pub async fn preflight_instruction_accounts(&self, instructions: &[Instruction]) -> AppResult<()> {
+        let mut seen = SKIPPED_ACCOUNTS
+            .into_iter()
+            .chain(instructions.iter().map(|instruction| instruction.program_id))
+            .collect::<HashSet<_>>();
+        let accounts_pubkey = instructions
+            .iter()
+            .flat_map(|instruction| &instruction.accounts)
+            .map(|account| account.pubkey)
+            .filter(|pubkey| seen.insert(*pubkey))
+            .collect::<Vec<_>>();
+
+        let accounts = self.try_get_multiple_accounts(&accounts_pubkey, None, None).await?;
+
+        let missing_accounts = accounts_pubkey
+            .iter()
+            .zip(accounts)
+            .filter(|(_, account)| account.is_none())
+            .map(|(pubkey, _)| *pubkey)
+            .collect::<Vec<_>>();
+
+        if !missing_accounts.is_empty() {
+            return Err(AppError::MissingAccounts(missing_accounts));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::{
+        account::Account,
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+        sysvar,
+    };
+    use solana_system_interface::program;
+
+    use super::super::{
+        error::AppError,
+        mock_rpc::{MockRpc, requested_pubkeys, rpc_error},
+    };
+
+    // a consensus-shaped instruction: the submission's own accounts next to the sysvars and
+    // programs every submission passes along
+    fn submission_instruction(own_accounts: &[Pubkey]) -> Instruction {
+        let mut accounts = own_accounts
+            .iter()
+            .map(|pubkey| AccountMeta::new(*pubkey, false))
+            .collect::<Vec<_>>();
+        accounts.extend([
+            AccountMeta::new_readonly(sysvar::instructions::ID, false),
+            AccountMeta::new_readonly(sysvar::slot_hashes::ID, false),
+            AccountMeta::new_readonly(program::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ]);
+
+        Instruction::new_with_bytes(Pubkey::new_unique(), &[], accounts)
+    }
+
+    #[tokio::test]
+    async fn skips_sysvars_and_programs() {
+        let own_accounts = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let mock = MockRpc::with_accounts(
+            1,
+            own_accounts
+                .iter()
+                .map(|pubkey| (*pubkey, Account::default()))
+                .collect::<HashMap<_, _>>(),
+        );
+
+        mock.client()
+            .preflight_instruction_accounts(&[submission_instruction(&own_accounts)])
+            .await
+            .unwrap();
+
+        assert_eq!(mock.count(RpcRequest::GetMultipleAccounts), 1);
+        assert_eq!(requested_pubkeys(&mock.requests()[0].1), own_accounts);
+    }
+
+    #[tokio::test]
+    async fn reports_missing_oracle() {
+        let feed = Pubkey::new_unique();
+        let oracle = Pubkey::new_unique();
+        let mock = MockRpc::with_accounts(1, HashMap::from([(feed, Account::default())]));
+
+        let result = mock
+            .client()
+            .preflight_instruction_accounts(&[submission_instruction(&[feed, oracle])])
+            .await;
+
+        assert!(matches!(result, Err(AppError::MissingAccounts(missing)) if missing == vec![oracle]));
+    }
+
+    #[tokio::test]
+    async fn returns_rpc_failures_as_errors() {
+        let mock = MockRpc::new(|_, _| Err(rpc_error("rpc unavailable")));
+
+        let result = mock
+            .client()
+            .preflight_instruction_accounts(&[submission_instruction(&[Pubkey::new_unique()])])
+            .await;
+
+        assert!(matches!(result, Err(app_error) if !matches!(app_error, AppError::MissingAccounts(_))));
+    }
+}
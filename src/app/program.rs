@@ -0,0 +1,82 @@
+use solana_loader_v3_interface::{get_program_data_address, state::UpgradeableLoaderState};
+use solana_sdk::pubkey::Pubkey;
+use switchboard_on_demand::{Discriminator, State};
+
+use super::{
+    client::AppClient,
+    error::{AppError, AppResult},
+};
+
+#[derive(Clone, Debug)]
+pub struct OnDemandProgramInfo {
+    // slot the deployed program was last upgraded at, identifies the deployed version
+    pub deploy_slot: u64,
+    pub upgrade_authority: Option<Pubkey>,
+    // whether the program State account matches the discriminator and size this crate expects
+    pub state_layout_matches: bool,
+}
+
+impl AppClient {
+    // Reads the on-demand ProgramData and State accounts to identify the deployed program and
+    // check its State layout against the switchboard-on-demand version this crate is built with.
+    // A mismatch usually means the program was upgraded and instruction/account layouts may have
+    // moved, so it is logged as a warning rather than failing.
+    pub async fn check_on_demand_program(&self) -> AppResult<OnDemandProgramInfo> {
+        let program_id = self.cluster().program_id();
+        let program_data_pubkey = get_program_data_address(&program_id);
+        let state_pubkey = self.cluster().program_state();
+
+        let (program_data_account, state_account) = tokio::try_join!(
+            self.get_account(&program_data_pubkey, None),
+            self.get_account(&state_pubkey, None)
+        )?;
+
+        let metadata_len = UpgradeableLoaderState::size_of_programdata_metadata();
+        let program_data_state = program_data_account
+            .data
+            .get(..metadata_len)
+            .ok_or_else(|| {
+                AppError::ParsingError(format!(
+                    "ProgramData account too small - {program_data_pubkey}"
+                ))
+            })
+            .and_then(|metadata| {
+                bincode::deserialize::<UpgradeableLoaderState>(metadata).map_err(|error| {
+                    AppError::ParsingError(format!(
+                        "Invalid ProgramData account - {program_data_pubkey}: {error}"
+                    ))
+                })
+            })?;
+
+        let UpgradeableLoaderState::ProgramData {
+            slot: deploy_slot,
+            upgrade_authority_address: upgrade_authority,
+        } = program_data_state
+        else {
+            return Err(AppError::ParsingError(format!(
+                "Unexpected loader state for ProgramData account - {program_data_pubkey}"
+            )));
+        };
+
+        let expected_state_len = State::DISCRIMINATOR.len() + std::mem::size_of::<State>();
+        let state_layout_matches = state_account.data.starts_with(State::DISCRIMINATOR)
+            && state_account.data.len() == expected_state_len;
+
+        if state_layout_matches {
+            tracing::info!(
+                "On-demand program {program_id} deployed at slot {deploy_slot} matches the expected State layout"
+            );
+        } else {
+            tracing::warn!(
+                "On-demand program {program_id} deployed at slot {deploy_slot} diverges from the State layout this crate was built against (expected {expected_state_len} bytes, got {})",
+                state_account.data.len()
+            );
+        }
+
+        Ok(OnDemandProgramInfo {
+            deploy_slot,
+            upgrade_authority,
+            state_layout_matches,
+        })
+    }
+}
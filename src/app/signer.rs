@@ -0,0 +1,64 @@
+use std::{future::Future, pin::Pin};
+
+use solana_sdk::{
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::{Signer, SignerError},
+    transaction::VersionedTransaction,
+};
+
+use super::{client::AppClient, error::AppResult};
+
+pub type SignFuture<'a> = Pin<Box<dyn Future<Output = AppResult<Signature>> + Send + 'a>>;
+
+// Signs the transactions the client builds as fee payer, so key custody can live outside the
+// process (e.g. an HSM backed signing service). Keypair implements it locally and is the default,
+// see AppClientBuilder::signer.
+pub trait TransactionSigner: Send + Sync {
+    fn pubkey(&self) -> Pubkey;
+
+    // signature of the serialized message
+    fn sign_message<'a>(&'a self, message: &'a [u8]) -> SignFuture<'a>;
+}
+
+impl TransactionSigner for Keypair {
+    fn pubkey(&self) -> Pubkey {
+        Signer::pubkey(self)
+    }
+
+    fn sign_message<'a>(&'a self, message: &'a [u8]) -> SignFuture<'a> {
+        Box::pin(async move { Ok(self.try_sign_message(message)?) })
+    }
+}
+
+impl AppClient {
+    // Signs the message with signing_keypairs, or with the client's signer when None. The signer
+    // only covers messages it is the sole signer of, and a signature that doesn't verify against
+    // its pubkey is rejected rather than sent.
+    pub async fn sign_message(
+        &self,
+        message: VersionedMessage,
+        signing_keypairs: Option<&[&Keypair]>,
+    ) -> AppResult<VersionedTransaction> {
+        if let Some(signing_keypairs) = signing_keypairs {
+            return Ok(VersionedTransaction::try_new(message, signing_keypairs)?);
+        }
+
+        if message.header().num_required_signatures != 1 {
+            return Err(SignerError::NotEnoughSigners.into());
+        }
+
+        let serialized_message = message.serialize();
+        let signature = self.signer().sign_message(&serialized_message).await?;
+
+        if !signature.verify(self.keypair_pubkey().as_ref(), &serialized_message) {
+            return Err(SignerError::KeypairPubkeyMismatch.into());
+        }
+
+        Ok(VersionedTransaction {
+            signatures: vec![signature],
+            message,
+        })
+    }
+}
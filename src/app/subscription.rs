@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use futures::{StreamExt, stream::select_all};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
+use solana_sdk::{account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
+    task::JoinHandle,
+};
+
+use crate::utils::backoff_with_jitter;
+
+use super::{
+    client::AppClient,
+    error::AppResult,
+    feed::{FeedResult, parse_feed_account},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeedUpdate {
+    pub feed: Pubkey,
+    // slot the notification was sent at
+    pub slot: u64,
+    // None when the feed was never updated or its account failed to parse
+    pub result: Option<FeedResult>,
+}
+
+// Every watched feed's account subscription multiplexed over a single websocket connection, each
+// feed's updates are routed to its own channel.
+pub struct FeedSubscription {
+    receivers: HashMap<Pubkey, UnboundedReceiver<FeedUpdate>>,
+    handle: JoinHandle<()>,
+}
+
+impl FeedSubscription {
+    // Takes the channel of a feed, None when the feed isn't watched or was already taken.
+    pub fn take_receiver(&mut self, feed_pubkey: &Pubkey) -> Option<UnboundedReceiver<FeedUpdate>> {
+        self.receivers.remove(feed_pubkey)
+    }
+
+    pub fn into_receivers(self) -> HashMap<Pubkey, UnboundedReceiver<FeedUpdate>> {
+        self.receivers
+    }
+
+    // The connection is also closed once every receiver is dropped.
+    pub fn abort(&self) {
+        self.handle.abort();
+    }
+}
+
+impl AppClient {
+    // Subscribes to every feed over one connection to the client's ws_url, reconnecting and
+    // resubscribing with backoff whenever it drops. Updates are only observed while connected, a
+    // reconnect doesn't replay what was missed in between.
+    pub fn subscribe_feeds(&self, feeds: &[Pubkey]) -> FeedSubscription {
+        let (senders, receivers): (HashMap<_, _>, HashMap<_, _>) = feeds
+            .iter()
+            .map(|feed_pubkey| {
+                let (sender, receiver) = unbounded_channel();
+                ((*feed_pubkey, sender), (*feed_pubkey, receiver))
+            })
+            .unzip();
+
+        let ws_url = self.ws_url().to_string();
+        let commitment_config = self.commitment_config();
+        let program_id = self.cluster().program_id();
+        let (retry_base_delay, retry_max_delay, retry_jitter) = self.retry_backoff();
+
+        let handle = tokio::spawn(async move {
+            let mut attempt = 0;
+            loop {
+                match run_feed_subscription(&ws_url, commitment_config, &program_id, &senders).await {
+                    Err(app_error) => {
+                        tracing::warn!("Feed subscription dropped - {ws_url}\n{app_error:#?}");
+                    }
+                    Ok(true) => attempt = 0,
+                    Ok(false) => {}
+                }
+
+                if senders.values().all(UnboundedSender::is_closed) {
+                    tracing::info!("Every feed receiver dropped, closing the subscription - {ws_url}");
+                    return;
+                }
+
+                tokio::time::sleep(backoff_with_jitter(
+                    retry_base_delay,
+                    retry_max_delay,
+                    attempt,
+                    retry_jitter,
+                ))
+                .await;
+                attempt = attempt.saturating_add(1);
+            }
+        });
+
+        FeedSubscription { receivers, handle }
+    }
+}
+
+// Subscribes to every feed and routes updates until the connection drops or every receiver is
+// dropped. Ok(true) when at least one update was received, so the reconnect backoff is reset.
+async fn run_feed_subscription(
+    ws_url: &str,
+    commitment_config: CommitmentConfig,
+    program_id: &Pubkey,
+    senders: &HashMap<Pubkey, UnboundedSender<FeedUpdate>>,
+) -> AppResult<bool> {
+    let pubsub_client = PubsubClient::new(ws_url).await?;
+
+    let config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(commitment_config),
+        ..Default::default()
+    };
+
+    // the streams end with the connection, shutdown below unsubscribes from everything at once
+    let mut streams = Vec::with_capacity(senders.len());
+    for feed_pubkey in senders.keys() {
+        let (stream, _unsubscribe) = pubsub_client
+            .account_subscribe(feed_pubkey, Some(config.clone()))
+            .await?;
+
+        let feed_pubkey = *feed_pubkey;
+        streams.push(stream.map(move |response| (feed_pubkey, response)).boxed());
+    }
+    tracing::info!("Subscribed to {} feeds - {ws_url}", senders.len());
+
+    let mut updates = select_all(streams);
+    let mut received = false;
+    while let Some((feed_pubkey, response)) = updates.next().await {
+        received = true;
+
+        let result = response
+            .value
+            .decode::<Account>()
+            .and_then(|feed_account| {
+                parse_feed_account(&feed_pubkey, &feed_account, program_id)
+                    .inspect_err(|app_error| {
+                        tracing::warn!("Failed to parse PullFeedAccountData - {feed_pubkey}\n{app_error:#?}");
+                    })
+                    .ok()
+            })
+            .and_then(|feed_data| FeedResult::from_feed_data(&feed_data));
+
+        let feed_update = FeedUpdate {
+            feed: feed_pubkey,
+            slot: response.context.slot,
+            result,
+        };
+        route_feed_update(senders, feed_update);
+
+        if senders.values().all(UnboundedSender::is_closed) {
+            break;
+        }
+    }
+
+    drop(updates);
+    if let Err(pubsub_error) = pubsub_client.shutdown().await {
+        tracing::warn!("Failed to shut down feed subscription - {ws_url}\n{pubsub_error:#?}");
+    }
+
+    Ok(received)
+}
+
+// Sends the update to its feed's channel, dropped when the feed isn't watched or its receiver is gone.
+fn route_feed_update(senders: &HashMap<Pubkey, UnboundedSender<FeedUpdate>>, feed_update: FeedUpdate) {
+    let Some(sender) = senders.get(&feed_update.feed) else {
+        tracing::warn!("Update for an unwatched feed - {}", feed_update.feed);
+        return;
+    };
+
+    // the receiver may have been dropped while other feeds are still watched
+    let _ = sender.send(feed_update);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_are_routed_to_their_feed() {
+        let feeds = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let (senders, mut receivers): (HashMap<_, _>, Vec<_>) = feeds
+            .iter()
+            .map(|feed_pubkey| {
+                let (sender, receiver) = unbounded_channel();
+                ((*feed_pubkey, sender), receiver)
+            })
+            .unzip();
+        let feed_update = |feed, slot| FeedUpdate { feed, slot, result: None };
+
+        route_feed_update(&senders, feed_update(feeds[0], 1));
+        route_feed_update(&senders, feed_update(feeds[1], 2));
+        route_feed_update(&senders, feed_update(Pubkey::new_unique(), 3));
+        route_feed_update(&senders, feed_update(feeds[0], 4));
+
+        let received = |receiver: &mut UnboundedReceiver<FeedUpdate>| {
+            std::iter::from_fn(|| receiver.try_recv().ok()).collect::<Vec<_>>()
+        };
+        assert_eq!(received(&mut receivers[0]), [feed_update(feeds[0], 1), feed_update(feeds[0], 4)]);
+        assert_eq!(received(&mut receivers[1]), [feed_update(feeds[1], 2)]);
+
+        // a dropped receiver doesn't affect the other feed
+        drop(receivers.remove(0));
+        route_feed_update(&senders, feed_update(feeds[0], 5));
+        route_feed_update(&senders, feed_update(feeds[1], 6));
+        assert_eq!(received(&mut receivers[0]), [feed_update(feeds[1], 6)]);
+    }
+}
@@ -0,0 +1,464 @@
+use serde::Deserialize;
+use serde_json::json;
+use solana_client::{rpc_config::RpcTransactionConfig, rpc_request::RpcRequest};
+use solana_sdk::{
+    hash::Hash,
+    instruction::Instruction,
+    message::AddressLookupTableAccount,
+    signature::{Keypair, Signature},
+    transaction::TransactionError,
+};
+use solana_transaction_status_client_types::{
+    EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding,
+};
+
+use crate::utils::{
+    ensure_transaction_fits, get_compute_unit_price, get_writable_accounts, set_compute_unit_price,
+};
+
+use super::{
+    client::AppClient,
+    error::{AppError, AppResult, RetryAction},
+};
+
+// number of getTransaction polls before giving up on a freshly confirmed signature
+const GET_TRANSACTION_MAX_POLLS: u32 = 10;
+
+#[derive(Clone, Debug)]
+pub struct ConfirmedTransactionResult {
+    pub signature: Signature,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    // None when the transaction executed successfully
+    pub err: Option<TransactionError>,
+    pub fee: u64,
+    pub compute_units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+}
+
+impl AppClient {
+    // Fetches a confirmed transaction through getTransaction. Right after confirmation the rpc
+    // may not serve it yet, so a missing transaction is polled a few times before erroring.
+    pub async fn get_confirmed_transaction(
+        &self,
+        signature: &Signature,
+    ) -> AppResult<ConfirmedTransactionResult> {
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(self.rpc_client().commitment()),
+            max_supported_transaction_version: Some(0),
+        };
+
+        for attempt in 0..GET_TRANSACTION_MAX_POLLS {
+            let transaction = {
+                let _permit = self.acquire_permit().await?;
+                self.rpc_client()
+                    .send::<Option<EncodedConfirmedTransactionWithStatusMeta>>(
+                        RpcRequest::GetTransaction,
+                        json!([signature.to_string(), config]),
+                    )
+                    .await?
+            };
+
+            let Some(transaction) = transaction else {
+                tracing::info!(
+                    "Transaction {signature} not available yet, {}/{GET_TRANSACTION_MAX_POLLS} polls",
+                    attempt + 1
+                );
+                tokio::time::sleep(self.retry_delay(attempt)).await;
+                continue;
+            };
+
+            let Some(meta) = transaction.transaction.meta else {
+                return Err(AppError::ParsingError(format!(
+                    "Transaction {signature} has no status meta"
+                )));
+            };
+
+            return Ok(ConfirmedTransactionResult {
+                signature: *signature,
+                slot: transaction.slot,
+                block_time: transaction.block_time,
+                err: meta.err,
+                fee: meta.fee,
+                compute_units_consumed: meta.compute_units_consumed.into(),
+                logs: Option::from(meta.log_messages).unwrap_or_default(),
+            });
+        }
+
+        Err(AppError::TransactionNotFound(*signature))
+    }
+}
+
+// Whether the submit pipeline only simulates its transaction or lands it on-chain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubmitMode {
+    #[default]
+    Simulate,
+    Send,
+}
+
+#[derive(Clone, Debug)]
+pub struct SendResult {
+    pub signature: Signature,
+    // number of sends, including the one that landed
+    pub attempts: u32,
+    // compute unit price of the transaction that landed, None when it set none
+    pub compute_unit_price: Option<u64>,
+}
+
+impl AppClient {
+    // Signs and sends the instructions, waiting for confirmation at the client's commitment. The
+    // first attempt uses recent_blockhash when given (e.g. the one the oracles signed), otherwise
+    // the latest one. Messages are built and signed as in call_instructions.
+    //
+    // Transient failures (rpc/network errors, an expired blockhash, a confirmation timeout) are
+    // retried up to send_max_retries times with backoff. The blockhash is only refetched when it
+    // expired or the rpc node fell behind the cluster (see RetryAction), other retries resend with
+    // the same blockhash. Every attempt stays valid until its blockhash expires, so before each
+    // re-send and before returning an error the signatures of all attempts are checked, and one
+    // that landed late is returned instead of paying for another. The program rejecting the
+    // transaction, including in the rpc's preflight simulation, is definitive and returned right
+    // away as TransactionRejected.
+    //
+    // With a priority_fee_escalation every retry raises the compute unit price, starting from the
+    // instructions' own price or the recent median for their writable accounts. The price doesn't
+    // change the units consumed, so the compute unit limit is left as is. Escalated retries refetch
+    // the blockhash so the repriced transaction gets a full validity window.
+    pub async fn send_instructions(
+        &self,
+        alts: Option<&[AddressLookupTableAccount]>,
+        instructions: &[Instruction],
+        recent_blockhash: Option<Hash>,
+        signing_keypairs: Option<&[&Keypair]>,
+    ) -> AppResult<SendResult> {
+        let mut instructions = instructions.to_vec();
+        let mut compute_unit_price = get_compute_unit_price(&instructions);
+
+        // signature and compute unit price of every attempt sent so far
+        let mut previous_sends: Vec<(Signature, Option<u64>)> = Vec::new();
+        // kept across plain retries without escalation, resending the same transaction can't land twice
+        let mut recent_blockhash = recent_blockhash;
+        let mut attempt = 0;
+        loop {
+            if let Some(send_result) = self.find_landed_send(&previous_sends).await? {
+                return Ok(send_result);
+            }
+
+            let blockhash = match recent_blockhash {
+                Some(blockhash) => blockhash,
+                None => self.get_latest_blockhash().await?,
+            };
+            recent_blockhash = Some(blockhash);
+
+            let message = self.build_message(alts, &instructions, blockhash)?;
+            let transaction = self.sign_message(message, signing_keypairs).await?;
+            ensure_transaction_fits(&transaction)?;
+            let signature = transaction.signatures[0];
+
+            let result = {
+                let _permit = self.acquire_permit().await?;
+                self.rpc_client()
+                    .send_and_confirm_transaction(&transaction)
+                    .await
+            };
+
+            let rpc_error = match result {
+                Ok(signature) => {
+                    return Ok(SendResult {
+                        signature,
+                        attempts: attempt + 1,
+                        compute_unit_price,
+                    });
+                }
+                Err(rpc_error) => rpc_error,
+            };
+
+            previous_sends.push((signature, compute_unit_price));
+            attempt += 1;
+
+            let app_error = match rpc_error.get_transaction_error() {
+                Some(transaction_error) if transaction_error != TransactionError::BlockhashNotFound => {
+                    tracing::error!("Transaction {signature} rejected - {transaction_error:#?}");
+                    AppError::TransactionRejected(transaction_error)
+                }
+                _ => AppError::from(rpc_error),
+            };
+
+            let retry_action = app_error.retry_action();
+            if retry_action == RetryAction::Fail || attempt > self.send_max_retries() {
+                // an earlier attempt may have landed, e.g. the rejection is the feed already
+                // holding its update
+                if previous_sends.len() > 1
+                    && let Some(send_result) = self.find_landed_send(&previous_sends).await?
+                {
+                    return Ok(send_result);
+                }
+
+                return Err(app_error);
+            }
+
+            tracing::warn!(
+                "Failed to send transaction {signature}, retrying ({attempt}/{}) - {retry_action:?}\n{app_error:#?}",
+                self.send_max_retries()
+            );
+            tokio::time::sleep(self.retry_delay(attempt - 1)).await;
+
+            if retry_action == RetryAction::RefreshAndRetry {
+                recent_blockhash = None;
+            }
+
+            if let Some(priority_fee_escalation) = self.priority_fee_escalation() {
+                let current_price = match compute_unit_price {
+                    Some(price) => price,
+                    None => {
+                        self.get_priority_fee(&get_writable_accounts(&instructions))
+                            .await?
+                    }
+                };
+                let next_price = priority_fee_escalation.next_price(current_price);
+                tracing::info!("Escalating compute unit price {current_price} => {next_price} micro-lamports");
+
+                set_compute_unit_price(&mut instructions, next_price);
+                compute_unit_price = Some(next_price);
+                // a fresh blockhash doesn't stop the earlier attempts landing, find_landed_send
+                // checks for those, but gives the repriced transaction a full validity window
+                recent_blockhash = None;
+            }
+        }
+    }
+
+    // The first of the sends whose transaction landed at the client's commitment, checking all
+    // their signatures with one getSignatureStatuses request.
+    async fn find_landed_send(
+        &self,
+        previous_sends: &[(Signature, Option<u64>)],
+    ) -> AppResult<Option<SendResult>> {
+        if previous_sends.is_empty() {
+            return Ok(None);
+        }
+
+        let signatures = previous_sends
+            .iter()
+            .map(|(signature, _)| *signature)
+            .collect::<Vec<_>>();
+        let statuses = {
+            let _permit = self.acquire_permit().await?;
+            self.rpc_client().get_signature_statuses(&signatures).await?.value
+        };
+
+        let commitment = self.rpc_client().commitment();
+        let landed_send = previous_sends.iter().zip(statuses).find_map(|(send, status)| {
+            status
+                .is_some_and(|status| status.err.is_none() && status.satisfies_commitment(commitment))
+                .then_some(*send)
+        });
+
+        Ok(landed_send.map(|(signature, compute_unit_price)| {
+            tracing::info!("Previous attempt {signature} landed after all");
+            SendResult {
+                signature,
+                attempts: previous_sends.len() as u32,
+                compute_unit_price,
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use base64::{Engine, engine::general_purpose::STANDARD as base64};
+    use serde_json::{Value, json};
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::{
+        hash::Hash,
+        instruction::Instruction,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::{TransactionError, VersionedTransaction},
+    };
+
+    use crate::{
+        app::AppError,
+        utils::{PriorityFeeEscalation, build_compute_unit_price_ix, get_compute_unit_price},
+    };
+
+    use super::super::mock_rpc::{MockRpc, rpc_error, rpc_response};
+
+    // the signature and compute unit price of a sendTransaction request
+    fn sent_transaction(params: &Value) -> (Signature, Option<u64>) {
+        let wire_transaction = base64.decode(params[0].as_str().unwrap()).unwrap();
+        let transaction: VersionedTransaction = bincode::deserialize(&wire_transaction).unwrap();
+        let message = &transaction.message;
+        let instructions = message
+            .instructions()
+            .iter()
+            .map(|instruction| Instruction {
+                program_id: *instruction.program_id(message.static_account_keys()),
+                accounts: vec![],
+                data: instruction.data.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        (transaction.signatures[0], get_compute_unit_price(&instructions))
+    }
+
+    #[tokio::test]
+    async fn returns_escalated_retry_predecessor_that_landed() {
+        // every send fails, but the first transaction lands before the third send
+        let sent = Arc::new(Mutex::new(Vec::<Signature>::new()));
+        let status_checks = Arc::new(Mutex::new(0));
+        let mock = MockRpc::new({
+            let sent = sent.clone();
+            move |request, params| match request {
+                RpcRequest::GetLatestBlockhash => Ok(rpc_response(
+                    1,
+                    json!({ "blockhash": Hash::new_unique().to_string(), "lastValidBlockHeight": 100 }),
+                )),
+                RpcRequest::SendTransaction => {
+                    sent.lock().unwrap().push(sent_transaction(params).0);
+                    Err(rpc_error("transaction dropped"))
+                }
+                RpcRequest::GetSignatureStatuses => {
+                    let mut status_checks = status_checks.lock().unwrap();
+                    *status_checks += 1;
+                    let first_signature = sent.lock().unwrap()[0].to_string();
+                    let statuses = params[0]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|signature| match (*status_checks, signature.as_str()) {
+                            (2, Some(signature)) if signature == first_signature => json!({
+                                "slot": 1,
+                                "confirmations": null,
+                                "status": { "Ok": null },
+                                "err": null,
+                                "confirmationStatus": "finalized",
+                            }),
+                            _ => Value::Null,
+                        })
+                        .collect::<Vec<_>>();
+                    Ok(rpc_response(1, Value::Array(statuses)))
+                }
+                _ => Err(rpc_error(&format!("unexpected {request}"))),
+            }
+        });
+        let client = mock
+            .builder()
+            .priority_fee_escalation(Some(PriorityFeeEscalation {
+                factor: 2.0,
+                max_micro_lamports: 1_000,
+            }))
+            .build()
+            .unwrap();
+
+        let instructions = [
+            build_compute_unit_price_ix(100),
+            Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![]),
+        ];
+        let send_result = client
+            .send_instructions(None, &instructions, None, None)
+            .await
+            .unwrap();
+
+        let sent_transactions = mock
+            .requests()
+            .iter()
+            .filter(|(request, _)| *request == RpcRequest::SendTransaction)
+            .map(|(_, params)| sent_transaction(params))
+            .collect::<Vec<_>>();
+        assert_eq!(sent_transactions.len(), 2);
+        assert_eq!(sent_transactions[0].1, Some(100));
+        assert_eq!(sent_transactions[1].1, Some(200));
+        // the second check covers both attempts in one request
+        let status_requests = mock
+            .requests()
+            .into_iter()
+            .filter(|(request, _)| *request == RpcRequest::GetSignatureStatuses)
+            .map(|(_, params)| params[0].as_array().unwrap().len())
+            .collect::<Vec<_>>();
+        assert_eq!(status_requests, [1, 2]);
+
+        assert_eq!(send_result.signature, sent_transactions[0].0);
+        assert_eq!(send_result.attempts, 2);
+        assert_eq!(send_result.compute_unit_price, Some(100));
+    }
+
+    // Rpc where the first send is dropped and every later one lands with the given status.
+    fn send_rpc(landed_status: Value) -> MockRpc {
+        let sends = Mutex::new(0);
+        let landed = Mutex::new(None::<Signature>);
+        MockRpc::new(move |request, params| match request {
+            RpcRequest::GetLatestBlockhash => Ok(rpc_response(
+                1,
+                json!({ "blockhash": Hash::new_unique().to_string(), "lastValidBlockHeight": 100 }),
+            )),
+            RpcRequest::SendTransaction => {
+                let mut sends = sends.lock().unwrap();
+                *sends += 1;
+                if *sends == 1 {
+                    return Err(rpc_error("transaction dropped"));
+                }
+
+                let signature = sent_transaction(params).0;
+                *landed.lock().unwrap() = Some(signature);
+                Ok(json!(signature.to_string()))
+            }
+            RpcRequest::GetSignatureStatuses => {
+                let landed = landed.lock().unwrap().map(|signature| signature.to_string());
+                let statuses = params[0]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|signature| match signature.as_str() {
+                        Some(signature) if Some(signature) == landed.as_deref() => landed_status.clone(),
+                        _ => Value::Null,
+                    })
+                    .collect::<Vec<_>>();
+                Ok(rpc_response(1, Value::Array(statuses)))
+            }
+            _ => Err(rpc_error(&format!("unexpected {request}"))),
+        })
+    }
+
+    fn status(err: Value) -> Value {
+        json!({
+            "slot": 1,
+            "confirmations": null,
+            "status": if err.is_null() { json!({ "Ok": null }) } else { json!({ "Err": err }) },
+            "err": err,
+            "confirmationStatus": "finalized",
+        })
+    }
+
+    #[tokio::test]
+    async fn transient_send_failure_is_retried() {
+        let mock = send_rpc(status(Value::Null));
+        let client = mock.builder().send_max_retries(2).build().unwrap();
+
+        let instructions = [Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![])];
+        let send_result = client.send_instructions(None, &instructions, None, None).await.unwrap();
+
+        assert_eq!(mock.count(RpcRequest::SendTransaction), 2);
+        assert_eq!(send_result.attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn rejected_transaction_is_not_retried() {
+        let mock = send_rpc(status(json!({ "InstructionError": [0, { "Custom": 6000 }] })));
+        let client = mock.builder().send_max_retries(2).build().unwrap();
+
+        let instructions = [Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![])];
+        let error = client.send_instructions(None, &instructions, None, None).await.unwrap_err();
+
+        assert!(
+            matches!(error, AppError::TransactionRejected(TransactionError::InstructionError(0, _))),
+            "{error}"
+        );
+        // the dropped first send and the rejected second one
+        assert_eq!(mock.count(RpcRequest::SendTransaction), 2);
+    }
+}
@@ -0,0 +1,367 @@
+use std::{process::ExitCode, sync::Arc};
+
+use solana_sdk::pubkey::Pubkey;
+
+use swb_ix::{
+    Config,
+    app::{AppClient, SubmitMode},
+    fixture::{SubmitFixture, record_submit_fixture, replay_submit_fixture},
+    swb::{execute_pull_feed_submit_consensus_response, execute_pull_feed_submit_response},
+    utils::{dump_instructions, load_keypair},
+};
+
+// swb-ix [--rpc-url <url>] [--keypair <path or key>] [--queue <pk>] <command> ...
+//
+// Global flags override the config before the client is built, wherever they appear on the
+// command line. Returns the arguments left for the command.
+pub fn apply_global_args(config: &mut Config, args: &[String]) -> Result<Vec<String>, String> {
+    let mut remaining_args = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rpc-url" => match args.next() {
+                Some(rpc_url) if rpc_url.starts_with("http://") || rpc_url.starts_with("https://") => {
+                    config.rpc_url = rpc_url.clone();
+                }
+                _ => return Err("--rpc-url expects an http(s) url".to_string()),
+            },
+            "--keypair" => match args.next() {
+                Some(keypair) => match load_keypair(keypair) {
+                    Err(app_error) => return Err(format!("Invalid --keypair\n{app_error}")),
+                    Ok(_) => config.keypair = keypair.clone(),
+                },
+                None => return Err("--keypair expects a path or a key".to_string()),
+            },
+            "--queue" => match args.next().map(|value| value.parse::<Pubkey>()) {
+                Some(Ok(pubkey)) => config.queue = Some(pubkey),
+                _ => return Err("--queue expects a pubkey".to_string()),
+            },
+            _ => remaining_args.push(arg.clone()),
+        }
+    }
+
+    Ok(remaining_args)
+}
+
+// --feed <pk> [--simulate | --send], simulating when neither is given
+fn parse_submit_args(args: &[String]) -> Result<(Pubkey, SubmitMode), String> {
+    let mut feed_pubkey = None;
+    let mut submit_mode = SubmitMode::Simulate;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--feed" => match args.next().map(|value| value.parse::<Pubkey>()) {
+                Some(Ok(pubkey)) => feed_pubkey = Some(pubkey),
+                _ => return Err("--feed expects a pubkey".to_string()),
+            },
+            "--simulate" => submit_mode = SubmitMode::Simulate,
+            "--send" => submit_mode = SubmitMode::Send,
+            unknown => return Err(format!("Unknown argument - {unknown}")),
+        }
+    }
+
+    let feed_pubkey = feed_pubkey.ok_or_else(|| "--feed is required".to_string())?;
+
+    Ok((feed_pubkey, submit_mode))
+}
+
+// swb-ix submit-consensus --feed <pk> [--simulate | --send]
+// swb-ix submit-response --feed <pk> [--simulate | --send]
+//
+// Runs one submission of the feed through the consensus or the per-oracle flow and prints its
+// outcome as JSON.
+pub async fn submit(app_client: Arc<AppClient>, consensus: bool, args: &[String]) -> ExitCode {
+    let (feed_pubkey, submit_mode) = match parse_submit_args(args) {
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::from(2);
+        }
+        Ok(feed_and_submit_mode) => feed_and_submit_mode,
+    };
+
+    let outcome = if consensus {
+        execute_pull_feed_submit_consensus_response(app_client, feed_pubkey, None, None, None, submit_mode).await
+    } else {
+        execute_pull_feed_submit_response(app_client, feed_pubkey, None, None, submit_mode).await
+    };
+    let Some(outcome) = outcome else {
+        eprintln!("Submission failed for - {feed_pubkey}, see the logs");
+        return ExitCode::FAILURE;
+    };
+
+    match serde_json::to_string_pretty(&outcome) {
+        Err(serde_error) => {
+            tracing::error!("Failed to serialize the submit outcome\n{serde_error:#?}");
+            ExitCode::FAILURE
+        }
+        Ok(output) => {
+            println!("{output}");
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+// swb-ix inspect-feed --feed <pk>
+//
+// Prints the feed's queue, latest value and staleness.
+pub async fn inspect_feed(app_client: &AppClient, args: &[String]) -> ExitCode {
+    let feed_pubkey = match args {
+        [flag, feed_pubkey] if flag == "--feed" => feed_pubkey.parse::<Pubkey>().ok(),
+        _ => None,
+    };
+    let Some(feed_pubkey) = feed_pubkey else {
+        eprintln!("inspect-feed expects --feed <pubkey>");
+        return ExitCode::from(2);
+    };
+
+    let feeds = [feed_pubkey];
+    let (feed_data, feed_infos) = tokio::join!(
+        app_client.load_feed(&feed_pubkey, None),
+        app_client.read_feeds(&feeds, None)
+    );
+    let (feed_data, feed_info) = match feed_data.and_then(|feed_data| Ok((feed_data, feed_infos?))) {
+        Err(app_error) => {
+            tracing::error!("Failed to read - {feed_pubkey}\n{app_error:#?}");
+            return ExitCode::FAILURE;
+        }
+        Ok((feed_data, mut feed_infos)) => (feed_data, feed_infos.remove(&feed_pubkey)),
+    };
+    let Some(feed_info) = feed_info else {
+        eprintln!("Failed to read - {feed_pubkey}, see the logs");
+        return ExitCode::FAILURE;
+    };
+
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    println!("feed          {feed_pubkey}");
+    println!("queue         {}", feed_data.queue);
+    println!("value         {}", or_dash(feed_info.value.map(|value| value.to_string())));
+    println!("result_slot   {}", or_dash(feed_info.result_slot.map(|slot| slot.to_string())));
+    println!("updated_at    {}", feed_info.last_update_timestamp);
+    println!("staleness     {}", or_dash(feed_info.staleness.map(|staleness| staleness.to_string())));
+    println!("stale         {}", feed_info.is_stale);
+
+    ExitCode::SUCCESS
+}
+
+// fraction of unreachable oracles above which `oracles` exits non-zero
+const DEFAULT_MAX_UNREACHABLE: f64 = 0.5;
+
+// swb-ix oracles [--queue <pk>] [--json] [--max-unreachable <fraction>]
+//
+// Prints every oracle of the queue with its gateway, stake score and whether the gateway answers.
+pub async fn oracles(app_client: &AppClient, default_queue: Pubkey, args: &[String]) -> ExitCode {
+    let mut queue_pubkey = default_queue;
+    let mut json = false;
+    let mut max_unreachable = DEFAULT_MAX_UNREACHABLE;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--queue" => match args.next().map(|value| value.parse::<Pubkey>()) {
+                Some(Ok(pubkey)) => queue_pubkey = pubkey,
+                _ => {
+                    eprintln!("--queue expects a pubkey");
+                    return ExitCode::from(2);
+                }
+            },
+            "--max-unreachable" => match args.next().map(|value| value.parse::<f64>()) {
+                Some(Ok(fraction)) if (0.0..=1.0).contains(&fraction) => max_unreachable = fraction,
+                _ => {
+                    eprintln!("--max-unreachable expects a fraction between 0 and 1");
+                    return ExitCode::from(2);
+                }
+            },
+            unknown => {
+                eprintln!("Unknown argument - {unknown}");
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let statuses = match app_client.get_queue_oracle_statuses(&queue_pubkey).await {
+        Err(app_error) => {
+            tracing::error!("Failed to read the oracles of queue - {queue_pubkey}\n{app_error:#?}");
+            return ExitCode::FAILURE;
+        }
+        Ok(statuses) => statuses,
+    };
+
+    if json {
+        match serde_json::to_string_pretty(&statuses) {
+            Err(serde_error) => {
+                tracing::error!("Failed to serialize oracle statuses\n{serde_error:#?}");
+                return ExitCode::FAILURE;
+            }
+            Ok(output) => println!("{output}"),
+        }
+    } else {
+        println!("{:<44}  {:<5}  {:>12}  GATEWAY", "ORACLE", "LIVE", "STAKE");
+        for status in &statuses {
+            println!(
+                "{:<44}  {:<5}  {:>12}  {}",
+                status.oracle.to_string(),
+                if status.reachable { "up" } else { "down" },
+                status.stake_score.map(|stake| stake.to_string()).unwrap_or_else(|| "-".to_string()),
+                status.gateway_uri.as_deref().unwrap_or("-"),
+            );
+        }
+    }
+
+    let unreachable = statuses.iter().filter(|status| !status.reachable).count();
+    let unreachable_fraction = if statuses.is_empty() {
+        1.0
+    } else {
+        unreachable as f64 / statuses.len() as f64
+    };
+
+    if unreachable_fraction > max_unreachable {
+        tracing::error!(
+            "{unreachable}/{} oracles of queue - {queue_pubkey} are unreachable",
+            statuses.len()
+        );
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+// swb-ix record <feed> <fixture path>
+//
+// Records every external input of a consensus submission of the feed into a fixture file, see
+// fixture::record_submit_fixture.
+pub async fn record(app_client: &AppClient, args: &[String]) -> ExitCode {
+    let [feed_pubkey, path] = args else {
+        eprintln!("record expects <feed> <fixture path>");
+        return ExitCode::from(2);
+    };
+    let Ok(feed_pubkey) = feed_pubkey.parse::<Pubkey>() else {
+        eprintln!("Invalid feed pubkey - {feed_pubkey}");
+        return ExitCode::from(2);
+    };
+
+    let fixture = match record_submit_fixture(app_client, &feed_pubkey).await {
+        Err(app_error) => {
+            tracing::error!("Failed to record a submission of - {feed_pubkey}\n{app_error:#?}");
+            return ExitCode::FAILURE;
+        }
+        Ok(fixture) => fixture,
+    };
+
+    let written = serde_json::to_vec_pretty(&fixture)
+        .map_err(|serde_error| format!("{serde_error}"))
+        .and_then(|fixture| std::fs::write(path, fixture).map_err(|io_error| format!("{io_error}")));
+    if let Err(error) = written {
+        tracing::error!("Failed to write fixture - {path}\n{error}");
+        return ExitCode::FAILURE;
+    }
+
+    println!("Recorded - {feed_pubkey} to {path}");
+    ExitCode::SUCCESS
+}
+
+// swb-ix replay <fixture path>
+//
+// Rebuilds the instructions of a recorded submission without any network access and prints them
+// as utils::dump_instructions does.
+pub fn replay(args: &[String]) -> ExitCode {
+    let [path] = args else {
+        eprintln!("replay expects <fixture path>");
+        return ExitCode::from(2);
+    };
+
+    let fixture = std::fs::read(path)
+        .map_err(|io_error| format!("{io_error}"))
+        .and_then(|fixture| {
+            serde_json::from_slice::<SubmitFixture>(&fixture).map_err(|serde_error| format!("{serde_error}"))
+        });
+    let fixture = match fixture {
+        Err(error) => {
+            tracing::error!("Failed to read fixture - {path}\n{error}");
+            return ExitCode::FAILURE;
+        }
+        Ok(fixture) => fixture,
+    };
+
+    match replay_submit_fixture(&fixture).and_then(|instructions| dump_instructions(&instructions)) {
+        Err(app_error) => {
+            tracing::error!("Failed to replay fixture - {path}\n{app_error:#?}");
+            ExitCode::FAILURE
+        }
+        Ok(dump) => {
+            println!("{dump}");
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::Keypair;
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn global_args_override_the_config_anywhere_on_the_command_line() {
+        let mut config = Config::from_toml(r#"rpc_url = "http://localhost:8899""#).unwrap();
+        let queue = Pubkey::new_unique();
+        let keypair = Keypair::new().to_base58_string();
+
+        let remaining_args = apply_global_args(
+            &mut config,
+            &args(&[
+                "swb-ix",
+                "--rpc-url",
+                "https://rpc.example",
+                "inspect-feed",
+                "--queue",
+                &queue.to_string(),
+                "--keypair",
+                &keypair,
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(remaining_args, args(&["swb-ix", "inspect-feed"]));
+        assert_eq!(config.rpc_url, "https://rpc.example");
+        assert_eq!(config.queue, Some(queue));
+        assert_eq!(config.keypair, keypair);
+    }
+
+    #[test]
+    fn global_args_are_validated() {
+        let mut config = Config::from_toml(r#"rpc_url = "http://localhost:8899""#).unwrap();
+
+        for invalid_args in [
+            args(&["swb-ix", "--rpc-url", "localhost:8899"]),
+            args(&["swb-ix", "--queue", "not a pubkey"]),
+            args(&["swb-ix", "--keypair", "/does/not/exist.json"]),
+            args(&["swb-ix", "--keypair"]),
+        ] {
+            assert!(apply_global_args(&mut config, &invalid_args).is_err(), "{invalid_args:?}");
+        }
+        assert_eq!(config.rpc_url, "http://localhost:8899");
+    }
+
+    #[test]
+    fn submit_args_require_a_feed_and_default_to_simulate() {
+        let feed = Pubkey::new_unique();
+
+        let (feed_pubkey, submit_mode) = parse_submit_args(&args(&["--feed", &feed.to_string()])).unwrap();
+        assert_eq!(feed_pubkey, feed);
+        assert!(matches!(submit_mode, SubmitMode::Simulate));
+
+        let (_, submit_mode) = parse_submit_args(&args(&["--send", "--feed", &feed.to_string()])).unwrap();
+        assert!(matches!(submit_mode, SubmitMode::Send));
+
+        assert!(parse_submit_args(&args(&["--send"])).is_err());
+        assert!(parse_submit_args(&args(&["--feed", "not a pubkey"])).is_err());
+        assert!(parse_submit_args(&args(&["--feed", &feed.to_string(), "--unknown"])).is_err());
+    }
+}
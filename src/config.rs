@@ -0,0 +1,301 @@
+use std::{env, path::Path, str::FromStr};
+
+use serde::{Deserialize, Deserializer, de::Error as _};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    app::{AppClientBuilder, AppError, AppResult},
+    crossbar::DEFAULT_CROSSBAR_URL,
+    utils::{Cluster, NumSignaturesPolicy},
+};
+
+const DEFAULT_KEYPAIR_PATH: &str = "/path/to/your/solana/id.json";
+
+// What the binary runs against, from the environment or a TOML file:
+//
+//   rpc_url = "https://api.mainnet-beta.solana.com"
+//   keypair = "/path/to/id.json" # or a base58 secret key or a JSON byte array, see load_keypair
+//   feeds = ["<pubkey>", ...]
+//   queue = "<pubkey>" # feeds on another queue are rejected
+//   crossbar_urls = ["https://crossbar.switchboard.xyz"]
+//   num_signatures = "feed" # the default, or a fixed count, see NumSignaturesPolicy
+//   cluster = "mainnet" # the default, "devnet" or the pubkey of a custom on-demand program
+//
+// Everything but rpc_url is optional.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Config {
+    pub rpc_url: String,
+    // a keypair file path or the key itself, keypair_path is still accepted
+    #[serde(default = "default_keypair", alias = "keypair_path")]
+    pub keypair: String,
+    #[serde(default, deserialize_with = "deserialize_pubkeys")]
+    pub feeds: Vec<Pubkey>,
+    // queue every feed must be on, None accepts each feed's own queue. Submissions always go to
+    // the feed's queue, this only guards against feeds from an unexpected one.
+    #[serde(default, deserialize_with = "deserialize_optional_pubkey")]
+    pub queue: Option<Pubkey>,
+    #[serde(default = "default_crossbar_urls")]
+    pub crossbar_urls: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_num_signatures_policy")]
+    pub num_signatures: NumSignaturesPolicy,
+    #[serde(default, deserialize_with = "deserialize_cluster")]
+    pub cluster: Cluster,
+}
+
+impl Config {
+    // RPC_URL (required), KEYPAIR (or KEYPAIR_PATH), SWB_FEEDS and CROSSBAR_URLS (comma
+    // separated), SWB_QUEUE, SWB_NUM_SIGNATURES and SWB_CLUSTER.
+    pub fn from_env() -> AppResult<Self> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> AppResult<Self> {
+        let config = std::fs::read_to_string(path)?;
+
+        Self::from_toml(&config)
+    }
+
+    pub fn from_toml(config: &str) -> AppResult<Self> {
+        toml::from_str::<Self>(config)
+            .map_err(|toml_error| AppError::ParsingError(format!("Invalid config: {toml_error}")))?
+            .validate()
+    }
+
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> AppResult<Self> {
+        let rpc_url = var("RPC_URL").ok_or_else(|| AppError::ParsingError("Missing RPC_URL".to_string()))?;
+
+        let list = |name: &str| {
+            var(name).map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        Self {
+            rpc_url,
+            keypair: var("KEYPAIR")
+                .or_else(|| var("KEYPAIR_PATH"))
+                .unwrap_or_else(default_keypair),
+            feeds: list("SWB_FEEDS")
+                .unwrap_or_default()
+                .iter()
+                .map(|feed| parse_pubkey(feed))
+                .collect::<AppResult<_>>()?,
+            queue: var("SWB_QUEUE").map(|queue| parse_pubkey(&queue)).transpose()?,
+            crossbar_urls: list("CROSSBAR_URLS").unwrap_or_else(default_crossbar_urls),
+            num_signatures: var("SWB_NUM_SIGNATURES")
+                .map(|num_signatures| parse_num_signatures_policy(&num_signatures))
+                .transpose()?
+                .unwrap_or_default(),
+            cluster: var("SWB_CLUSTER")
+                .map(|cluster| parse_cluster(&cluster))
+                .transpose()?
+                .unwrap_or_default(),
+        }
+        .validate()
+    }
+
+    fn validate(self) -> AppResult<Self> {
+        if self.crossbar_urls.is_empty() {
+            return Err(AppError::ParsingError("At least one crossbar url is required".to_string()));
+        }
+
+        Ok(self)
+    }
+
+    pub fn client_builder(&self) -> AppClientBuilder {
+        let builder = AppClientBuilder::new(&self.keypair, self.rpc_url.clone())
+            .crossbar_urls(self.crossbar_urls.clone())
+            .num_signatures_policy(self.num_signatures)
+            .cluster(self.cluster);
+
+        match self.queue {
+            Some(queue) => builder.expected_queue(queue),
+            None => builder,
+        }
+    }
+}
+
+fn default_keypair() -> String {
+    DEFAULT_KEYPAIR_PATH.to_string()
+}
+
+fn default_crossbar_urls() -> Vec<String> {
+    vec![DEFAULT_CROSSBAR_URL.to_string()]
+}
+
+fn parse_pubkey(pubkey: &str) -> AppResult<Pubkey> {
+    Pubkey::from_str(pubkey).map_err(|error| AppError::ParsingError(format!("Invalid pubkey {pubkey}: {error}")))
+}
+
+// a signature count or "feed"
+fn parse_num_signatures_policy(num_signatures: &str) -> AppResult<NumSignaturesPolicy> {
+    match num_signatures.trim() {
+        "feed" => Ok(NumSignaturesPolicy::FromFeed),
+        num_signatures => num_signatures.parse().map(NumSignaturesPolicy::Fixed).map_err(|_| {
+            AppError::ParsingError(format!("Invalid num_signatures {num_signatures}, expected a number or \"feed\""))
+        }),
+    }
+}
+
+// "mainnet", "devnet" or the program id of a custom deployment
+fn parse_cluster(cluster: &str) -> AppResult<Cluster> {
+    match cluster.trim() {
+        "mainnet" => Ok(Cluster::Mainnet),
+        "devnet" => Ok(Cluster::Devnet),
+        program_id => Pubkey::from_str(program_id).map(Cluster::Custom).map_err(|_| {
+            AppError::ParsingError(format!(
+                "Invalid cluster {program_id}, expected \"mainnet\", \"devnet\" or a program id"
+            ))
+        }),
+    }
+}
+
+// pubkeys are written base58 in the config, Pubkey itself deserializes from bytes
+fn deserialize_pubkey<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+    let pubkey = String::deserialize(deserializer)?;
+    parse_pubkey(&pubkey).map_err(D::Error::custom)
+}
+
+fn deserialize_optional_pubkey<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Pubkey>, D::Error> {
+    deserialize_pubkey(deserializer).map(Some)
+}
+
+fn deserialize_pubkeys<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Pubkey>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pubkey| parse_pubkey(pubkey).map_err(D::Error::custom))
+        .collect()
+}
+
+fn deserialize_num_signatures_policy<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<NumSignaturesPolicy, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumSignatures {
+        Count(u32),
+        Named(String),
+    }
+
+    match NumSignatures::deserialize(deserializer)? {
+        NumSignatures::Count(num_signatures) => Ok(NumSignaturesPolicy::Fixed(num_signatures)),
+        NumSignatures::Named(num_signatures) => parse_num_signatures_policy(&num_signatures)
+            .map_err(D::Error::custom),
+    }
+}
+
+fn deserialize_cluster<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Cluster, D::Error> {
+    let cluster = String::deserialize(deserializer)?;
+    parse_cluster(&cluster).map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use solana_sdk::pubkey::Pubkey;
+
+    use crate::{
+        app::AppError,
+        crossbar::DEFAULT_CROSSBAR_URL,
+        utils::{Cluster, NumSignaturesPolicy},
+    };
+
+    use super::{Config, DEFAULT_KEYPAIR_PATH};
+
+    fn from_vars(vars: &[(&str, &str)]) -> Result<Config, AppError> {
+        let vars = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+
+        Config::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn defaults_everything_but_the_rpc_url() {
+        for config in [from_vars(&[("RPC_URL", "http://rpc")]), Config::from_toml(r#"rpc_url = "http://rpc""#)] {
+            let config = config.unwrap();
+            assert_eq!(config.rpc_url, "http://rpc");
+            assert_eq!(config.keypair, DEFAULT_KEYPAIR_PATH);
+            assert!(config.feeds.is_empty());
+            assert_eq!(config.queue, None);
+            assert_eq!(config.crossbar_urls, [DEFAULT_CROSSBAR_URL]);
+            assert_eq!(config.num_signatures, NumSignaturesPolicy::FromFeed);
+            assert_eq!(config.cluster, Cluster::Mainnet);
+        }
+
+        assert!(matches!(from_vars(&[]), Err(AppError::ParsingError(_))));
+        assert!(matches!(Config::from_toml(""), Err(AppError::ParsingError(_))));
+    }
+
+    #[test]
+    fn parses_pubkeys() {
+        let (feed, other_feed, queue) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+
+        let config = from_vars(&[
+            ("RPC_URL", "http://rpc"),
+            ("SWB_FEEDS", &format!("{feed}, {other_feed},")),
+            ("SWB_QUEUE", &queue.to_string()),
+        ])
+        .unwrap();
+        assert_eq!((config.feeds, config.queue), (vec![feed, other_feed], Some(queue)));
+
+        let config = Config::from_toml(&format!(
+            "rpc_url = \"http://rpc\"\nfeeds = [\"{feed}\", \"{other_feed}\"]\nqueue = \"{queue}\""
+        ))
+        .unwrap();
+        assert_eq!((config.feeds, config.queue), (vec![feed, other_feed], Some(queue)));
+
+        assert!(from_vars(&[("RPC_URL", "http://rpc"), ("SWB_FEEDS", &format!("{feed},not-a-pubkey"))]).is_err());
+        assert!(from_vars(&[("RPC_URL", "http://rpc"), ("SWB_QUEUE", "not-a-pubkey")]).is_err());
+        assert!(Config::from_toml("rpc_url = \"http://rpc\"\nfeeds = [\"not-a-pubkey\"]").is_err());
+        assert!(Config::from_toml("rpc_url = \"http://rpc\"\nqueue = \"not-a-pubkey\"").is_err());
+    }
+
+    #[test]
+    fn parses_num_signatures_policy() {
+        let num_signatures = |value: &str| from_vars(&[("RPC_URL", "http://rpc"), ("SWB_NUM_SIGNATURES", value)]);
+
+        assert_eq!(num_signatures("feed").unwrap().num_signatures, NumSignaturesPolicy::FromFeed);
+        assert_eq!(num_signatures(" 3 ").unwrap().num_signatures, NumSignaturesPolicy::Fixed(3));
+        assert!(num_signatures("three").is_err());
+        assert!(num_signatures("-1").is_err());
+
+        let num_signatures = |value: &str| Config::from_toml(&format!("rpc_url = \"http://rpc\"\nnum_signatures = {value}"));
+
+        assert_eq!(num_signatures("\"feed\"").unwrap().num_signatures, NumSignaturesPolicy::FromFeed);
+        assert_eq!(num_signatures("3").unwrap().num_signatures, NumSignaturesPolicy::Fixed(3));
+        assert_eq!(num_signatures("\"3\"").unwrap().num_signatures, NumSignaturesPolicy::Fixed(3));
+        assert!(num_signatures("\"three\"").is_err());
+    }
+
+    #[test]
+    fn parses_cluster() {
+        let program_id = Pubkey::new_unique();
+        let cluster = |value: &str| from_vars(&[("RPC_URL", "http://rpc"), ("SWB_CLUSTER", value)]);
+
+        assert_eq!(cluster("mainnet").unwrap().cluster, Cluster::Mainnet);
+        assert_eq!(cluster("devnet").unwrap().cluster, Cluster::Devnet);
+        assert_eq!(cluster(&program_id.to_string()).unwrap().cluster, Cluster::Custom(program_id));
+        assert!(cluster("testnet").is_err());
+
+        let config = Config::from_toml("rpc_url = \"http://rpc\"\ncluster = \"devnet\"").unwrap();
+        assert_eq!(config.cluster, Cluster::Devnet);
+        assert!(Config::from_toml("rpc_url = \"http://rpc\"\ncluster = \"testnet\"").is_err());
+    }
+
+    #[test]
+    fn requires_a_crossbar_url() {
+        assert!(from_vars(&[("RPC_URL", "http://rpc"), ("CROSSBAR_URLS", " , ")]).is_err());
+        assert!(Config::from_toml("rpc_url = \"http://rpc\"\ncrossbar_urls = []").is_err());
+
+        let config = from_vars(&[("RPC_URL", "http://rpc"), ("CROSSBAR_URLS", "http://a, http://b")]).unwrap();
+        assert_eq!(config.crossbar_urls, ["http://a", "http://b"]);
+    }
+}
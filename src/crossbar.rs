@@ -0,0 +1,283 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use reqwest::{Client, StatusCode};
+use switchboard_on_demand::PullFeedAccountData;
+use switchboard_on_demand_client::oracle_job::OracleJob;
+
+use crate::{
+    app::{AppError, AppResult},
+    utils::{backoff_with_jitter, verify_jobs_hash},
+};
+
+pub const DEFAULT_CROSSBAR_URL: &str = "https://crossbar.switchboard.xyz";
+
+// Fetches feed jobs from crossbar over a shared http client, switchboard_on_demand_client's
+// CrossbarClient builds a new client (and connection pool) every time it is constructed.
+//
+// Every fetch is bounded by its own timeout and retried with backoff, trying each url in order
+// within an attempt, so a slow crossbar fails fast instead of stalling the gateway calls. A 404
+// means the feed's jobs were never registered and is returned right away.
+//
+// Fetched jobs are cached by feed_hash, which is derived from the job definitions. Updating a
+// feed's jobs changes its on-chain feed_hash, so the next fetch misses the cache instead of
+// serving the old definitions, and a cached entry never goes stale.
+//
+// With verify_jobs_hash, fetch_feed_jobs rejects jobs that don't hash to the feed's feed_hash, so a
+// compromised crossbar can't have the oracles sign values for different jobs.
+#[derive(Clone, Debug)]
+pub struct Crossbar {
+    // tried in order, the first one is the primary
+    crossbar_urls: Vec<String>,
+    client: Client,
+    timeout: Duration,
+    max_retries: u32,
+    retry_base_delay: Duration,
+    retry_max_delay: Duration,
+    retry_jitter: f64,
+    // feed_hash => jobs, shared by clones
+    jobs_cache: Arc<Mutex<HashMap<[u8; 32], Vec<OracleJob>>>>,
+    verify_jobs_hash: bool,
+}
+
+impl Crossbar {
+    pub fn new(crossbar_urls: Vec<String>, client: Client) -> Self {
+        Self {
+            crossbar_urls,
+            client,
+            timeout: Duration::from_secs(5),
+            max_retries: 2,
+            retry_base_delay: Duration::from_millis(250),
+            retry_max_delay: Duration::from_secs(5),
+            retry_jitter: 1.0,
+            jobs_cache: Arc::new(Mutex::new(HashMap::new())),
+            verify_jobs_hash: false,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn retry_backoff(mut self, base_delay: Duration, max_delay: Duration, jitter: f64) -> Self {
+        self.retry_base_delay = base_delay;
+        self.retry_max_delay = max_delay;
+        self.retry_jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn verify_jobs_hash(mut self, verify_jobs_hash: bool) -> Self {
+        self.verify_jobs_hash = verify_jobs_hash;
+        self
+    }
+
+    pub fn verifies_jobs_hash(&self) -> bool {
+        self.verify_jobs_hash
+    }
+
+    // Jobs of the feed, checked against its feed_hash when verify_jobs_hash is enabled.
+    pub async fn fetch_feed_jobs(&self, feed_data: &PullFeedAccountData) -> AppResult<Vec<OracleJob>> {
+        let jobs = self.fetch_jobs(&feed_data.feed_hash).await?;
+
+        if self.verify_jobs_hash {
+            verify_jobs_hash(feed_data, &jobs)?;
+        }
+
+        Ok(jobs)
+    }
+
+    pub async fn fetch_jobs(&self, feed_hash: &[u8; 32]) -> AppResult<Vec<OracleJob>> {
+        if let Some(jobs) = self.jobs_cache.lock().unwrap().get(feed_hash) {
+            return Ok(jobs.clone());
+        }
+
+        let jobs = self.fetch_jobs_uncached(&hex::encode(feed_hash)).await?;
+        self.jobs_cache
+            .lock()
+            .unwrap()
+            .insert(*feed_hash, jobs.clone());
+
+        Ok(jobs)
+    }
+
+    async fn fetch_jobs_uncached(&self, feed_hash: &str) -> AppResult<Vec<OracleJob>> {
+        let mut errors = Vec::new();
+
+        for attempt in 0..=self.max_retries {
+            if attempt > 0 {
+                tokio::time::sleep(backoff_with_jitter(
+                    self.retry_base_delay,
+                    self.retry_max_delay,
+                    attempt - 1,
+                    self.retry_jitter,
+                ))
+                .await;
+            }
+
+            for crossbar_url in &self.crossbar_urls {
+                match tokio::time::timeout(self.timeout, self.fetch_jobs_from(crossbar_url, feed_hash))
+                    .await
+                {
+                    Ok(Ok(jobs)) => return Ok(jobs),
+                    Ok(Err(app_error @ AppError::FeedJobsNotFound(_))) => {
+                        tracing::error!(
+                            "Crossbar {crossbar_url} has no jobs for - {feed_hash}, the feed has to be registered on crossbar first"
+                        );
+                        return Err(app_error);
+                    }
+                    Ok(Err(app_error)) => {
+                        tracing::warn!(
+                            "Crossbar {crossbar_url} failed to fetch jobs for - {feed_hash}\n{app_error:#?}"
+                        );
+                        errors.push(format!("{crossbar_url}: {app_error:?}"));
+                    }
+                    Err(_) => {
+                        tracing::warn!(
+                            "Crossbar {crossbar_url} timed out after {:?} fetching jobs for - {feed_hash}",
+                            self.timeout
+                        );
+                        errors.push(format!("{crossbar_url}: timed out after {:?}", self.timeout));
+                    }
+                }
+            }
+        }
+
+        Err(AppError::CrossbarError(format!(
+            "Failed to fetch jobs for {feed_hash} after {} attempts: {errors:?}",
+            self.max_retries + 1
+        )))
+    }
+
+    async fn fetch_jobs_from(&self, crossbar_url: &str, feed_hash: &str) -> AppResult<Vec<OracleJob>> {
+        let url = format!("{crossbar_url}/fetch/{feed_hash}");
+
+        let response = self.client.get(&url).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(AppError::FeedJobsNotFound(feed_hash.to_string()));
+        }
+
+        let mut jobs_data = response
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+
+        let jobs = jobs_data
+            .get_mut("jobs")
+            .map(serde_json::Value::take)
+            .ok_or_else(|| AppError::ParsingError("Crossbar response has no jobs".to_string()))?;
+
+        // A feed_hash commits to the flat list of jobs, there are no composite entries to resolve:
+        // tasks referencing other feeds (OracleTask, TwapTask, ...) carry the feed's address and
+        // are resolved by the oracles. Merging other job sets in would change the hash the oracles
+        // sign and the program checks.
+        let jobs = serde_json::from_value::<Vec<OracleJob>>(jobs)?;
+        if jobs.is_empty() {
+            return Err(AppError::ParsingError(format!(
+                "Crossbar returned an empty job list for - {feed_hash}"
+            )));
+        }
+
+        Ok(jobs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use reqwest::Client;
+    use serde_json::json;
+    use solana_sdk::pubkey::Pubkey;
+    use switchboard_on_demand::PullFeedAccountData;
+    use switchboard_on_demand_client::oracle_job::OracleJob;
+    use crate::{
+        app::{AppError, mock_rpc::serve_http},
+        utils::compute_feed_hash,
+    };
+
+    use super::Crossbar;
+
+    // A crossbar answering every request with status and body, counting the requests.
+    async fn serve_crossbar(status: &'static str, body: String) -> (Crossbar, Arc<AtomicUsize>) {
+        let (crossbar_url, requests) = serve_http(status, body).await;
+
+        (Crossbar::new(vec![crossbar_url], Client::new()).max_retries(0), requests)
+    }
+
+    #[tokio::test]
+    async fn jobs_referencing_other_feeds_are_used_as_listed() {
+        // the first job reads another switchboard feed, which the oracles resolve themselves
+        let jobs = json!([
+            { "tasks": [{ "oracleTask": { "switchboardAddress": Pubkey::new_unique().to_string() } }] },
+            { "tasks": [{ "valueTask": { "value": 1.5 } }] },
+        ]);
+        let (crossbar, requests) = serve_crossbar("200 OK", json!({ "jobs": jobs }).to_string()).await;
+
+        let mut feed_data: PullFeedAccountData = bytemuck::Zeroable::zeroed();
+        feed_data.queue = Pubkey::new_unique();
+        feed_data.feed_hash =
+            compute_feed_hash(&feed_data.queue, &serde_json::from_value::<Vec<OracleJob>>(jobs).unwrap());
+
+        let fetched_jobs = crossbar.verify_jobs_hash(true).fetch_feed_jobs(&feed_data).await.unwrap();
+
+        // nothing is merged in, the jobs still hash to the feed's feed_hash
+        assert_eq!(fetched_jobs.len(), 2);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn unusable_job_lists_are_errors() {
+        let (crossbar, _) = serve_crossbar("200 OK", json!({ "jobs": [] }).to_string()).await;
+        assert!(matches!(crossbar.fetch_jobs(&[1; 32]).await, Err(AppError::CrossbarError(_))));
+
+        let (crossbar, _) = serve_crossbar("200 OK", json!({}).to_string()).await;
+        assert!(matches!(crossbar.fetch_jobs(&[1; 32]).await, Err(AppError::CrossbarError(_))));
+
+        let (crossbar, requests) = serve_crossbar("404 Not Found", String::new()).await;
+        assert!(matches!(crossbar.fetch_jobs(&[1; 32]).await, Err(AppError::FeedJobsNotFound(_))));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn updated_feed_hash_misses_the_jobs_cache() {
+        let jobs = json!([{ "tasks": [{ "valueTask": { "value": 1.5 } }] }]);
+        let (crossbar, requests) = serve_crossbar("200 OK", json!({ "jobs": jobs }).to_string()).await;
+
+        crossbar.fetch_jobs(&[1; 32]).await.unwrap();
+        crossbar.clone().fetch_jobs(&[1; 32]).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // the feed's jobs were updated on crossbar, changing its feed_hash
+        crossbar.fetch_jobs(&[2; 32]).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn tampered_jobs_fail_the_jobs_hash_check() {
+        let mut feed_data: PullFeedAccountData = bytemuck::Zeroable::zeroed();
+        feed_data.queue = Pubkey::new_unique();
+        let jobs = json!([{ "tasks": [{ "valueTask": { "value": 1.5 } }] }]);
+        feed_data.feed_hash =
+            compute_feed_hash(&feed_data.queue, &serde_json::from_value::<Vec<OracleJob>>(jobs).unwrap());
+
+        let tampered_jobs = json!([{ "tasks": [{ "valueTask": { "value": 2.5 } }] }]);
+        let (crossbar, _) = serve_crossbar("200 OK", json!({ "jobs": tampered_jobs }).to_string()).await;
+
+        // only checked when enabled
+        crossbar.clone().verify_jobs_hash(false).fetch_feed_jobs(&feed_data).await.unwrap();
+        let error = crossbar.verify_jobs_hash(true).fetch_feed_jobs(&feed_data).await.unwrap_err();
+        assert!(matches!(error, AppError::JobsHashMismatch(_)), "{error}");
+    }
+}
@@ -0,0 +1,248 @@
+use std::str::FromStr;
+
+use base64::{Engine, engine::general_purpose::STANDARD as base64};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{hash::Hash, instruction::Instruction, pubkey::Pubkey};
+use switchboard_on_demand_client::{
+    FetchSignaturesConsensusResponse, SolanaSubmitSignaturesParams, oracle_job::OracleJob,
+    prost::Message,
+};
+
+use crate::{
+    app::{AppClient, AppError, AppResult},
+    utils::{
+        Cluster, ExpectedChecksum, SecpMessageFormat, detect_feed_source_unavailable,
+        ensure_min_responses_reachable, get_consensus_signatures, get_update_consensus_ix,
+        parse_oracle_account, parse_queue_account, parse_swb_ignore_alignment,
+        validate_feed_config, validate_secp256k1_instruction, verify_jobs_hash,
+    },
+};
+
+// Every external input of a consensus submission, enough to rebuild its instructions without any
+// network access. Accounts and jobs are kept as the raw bytes that were read, base64 encoded.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmitFixture {
+    pub feed: String,
+    pub payer: String,
+    pub feed_account: String,
+    pub queue_account: String,
+    // every oracle of the queue in queue order, None when its account was missing
+    pub oracle_accounts: Vec<(String, Option<String>)>,
+    // protobuf encoded jobs as served by crossbar
+    pub jobs: Vec<String>,
+    // gateway the response came from, informational
+    pub gateway: String,
+    pub gateway_response: FetchSignaturesConsensusResponse,
+    pub recent_blockhash: String,
+    pub recent_slot: u64,
+    // client settings the instruction builders depend on
+    pub strict_checksums: bool,
+    pub strict_jobs_hash: bool,
+    pub secp_message_format: SecpMessageFormat,
+    // on-demand program the instructions target, fixtures recorded without it are mainnet
+    #[serde(default)]
+    pub program_id: Option<String>,
+}
+
+// Reads everything a consensus submission of the feed needs, asking the queue's gateways in queue
+// order until one answers. Gateway ordering, cross checking and the value change threshold are
+// left out, the fixture captures what the instruction builders saw, not how the gateway was picked.
+pub async fn record_submit_fixture(app_client: &AppClient, feed_pubkey: &Pubkey) -> AppResult<SubmitFixture> {
+    let feed_account = app_client.get_account(feed_pubkey, None).await?;
+    // the same owner and queue checks as load_submission_accounts
+    let feed_data = app_client.parse_submission_feed(feed_pubkey, &feed_account)?;
+
+    let queue_account = app_client.get_account(&feed_data.queue, None).await?;
+    let queue_data = parse_queue_account(&queue_account.data)?;
+
+    let oracle_keys = queue_data.oracle_keys();
+    let oracle_accounts = app_client.get_multiple_accounts(&oracle_keys, None, None).await?;
+
+    let (recent_blockhash, recent_slot) = app_client
+        .get_submission_blockhash_and_slot_after(feed_data.result.result_slot())
+        .await?;
+
+    let gateways = oracle_accounts
+        .iter()
+        .flatten()
+        .filter_map(|oracle_account| parse_oracle_account(&oracle_account.data).ok()?.gateway_uri())
+        .map(|gateway_uri| app_client.gateway(gateway_uri))
+        .collect::<Vec<_>>();
+
+    let mut last_error = None;
+    let mut gateway_response = None;
+    for gateway in &gateways {
+        match get_consensus_signatures(
+            &feed_data,
+            app_client.crossbar(),
+            gateway,
+            recent_blockhash,
+            app_client.num_signatures_policy(),
+        )
+        .await
+        {
+            Err(app_error) => {
+                tracing::warn!("Failed to record consensus signatures from - {}\n{app_error:#?}", gateway.gateway_url());
+                last_error = Some(app_error);
+            }
+            Ok(response) => {
+                gateway_response = Some((gateway.gateway_url().to_string(), response));
+                break;
+            }
+        }
+    }
+    let Some((gateway, gateway_response)) = gateway_response else {
+        return Err(last_error.unwrap_or(AppError::NoGatewaysAvailable {
+            oracles_inspected: oracle_keys.len(),
+        }));
+    };
+
+    // served from the crossbar cache filled by get_consensus_signatures
+    let jobs = app_client.crossbar().fetch_jobs(&feed_data.feed_hash).await?;
+
+    Ok(SubmitFixture {
+        feed: feed_pubkey.to_string(),
+        payer: app_client.keypair_pubkey().to_string(),
+        feed_account: base64.encode(&feed_account.data),
+        queue_account: base64.encode(&queue_account.data),
+        oracle_accounts: oracle_keys
+            .iter()
+            .zip(oracle_accounts)
+            .map(|(oracle_pubkey, oracle_account)| {
+                (
+                    oracle_pubkey.to_string(),
+                    oracle_account.map(|oracle_account| base64.encode(&oracle_account.data)),
+                )
+            })
+            .collect(),
+        jobs: jobs.iter().map(|job| base64.encode(job.encode_to_vec())).collect(),
+        gateway,
+        gateway_response,
+        recent_blockhash: recent_blockhash.to_string(),
+        recent_slot,
+        strict_checksums: app_client.strict_checksums(),
+        strict_jobs_hash: app_client.crossbar().verifies_jobs_hash(),
+        secp_message_format: app_client.secp_message_format(),
+        program_id: Some(app_client.cluster().program_id().to_string()),
+    })
+}
+
+// Runs the recorded inputs through the same checks and instruction builders as
+// execute_pull_feed_submit_consensus_response, up to (excluding) the priority fee and the rpc
+// preflight. Deterministic, the same fixture always builds the same instructions.
+pub fn replay_submit_fixture(fixture: &SubmitFixture) -> AppResult<Vec<Instruction>> {
+    let feed_pubkey = parse_pubkey(&fixture.feed)?;
+    let payer = parse_pubkey(&fixture.payer)?;
+    let recent_blockhash = Hash::from_str(&fixture.recent_blockhash)
+        .map_err(|error| AppError::ParsingError(format!("Invalid recent_blockhash: {error}")))?;
+    let cluster = fixture
+        .program_id
+        .as_deref()
+        .map(parse_pubkey)
+        .transpose()?
+        .map_or(Cluster::Mainnet, Cluster::Custom);
+
+    let feed_data = parse_swb_ignore_alignment(&base64.decode(&fixture.feed_account)?)?;
+    parse_queue_account(&base64.decode(&fixture.queue_account)?)?;
+
+    let live_oracles = fixture
+        .oracle_accounts
+        .iter()
+        .filter_map(|(_, oracle_account)| oracle_account.as_ref())
+        .map(|oracle_account| parse_oracle_account(&base64.decode(oracle_account)?))
+        .collect::<AppResult<Vec<_>>>()?
+        .iter()
+        .filter(|oracle_account_data| oracle_account_data.gateway_uri().is_some())
+        .count();
+
+    validate_feed_config(&feed_data)?;
+    ensure_min_responses_reachable(&feed_data, live_oracles)?;
+
+    if fixture.strict_jobs_hash {
+        let jobs = fixture
+            .jobs
+            .iter()
+            .map(|job| {
+                OracleJob::decode(base64.decode(job)?.as_slice())
+                    .map_err(|error| AppError::ParsingError(format!("Invalid job: {error}")))
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+        verify_jobs_hash(&feed_data, &jobs)?;
+    }
+
+    detect_feed_source_unavailable(
+        fixture
+            .gateway_response
+            .oracle_responses
+            .iter()
+            .flat_map(|oracle_response| &oracle_response.feed_responses)
+            .map(|feed_response| feed_response.failure_error.as_str()),
+    )?;
+
+    let params = SolanaSubmitSignaturesParams {
+        feed: feed_pubkey,
+        payer,
+        queue: feed_data.queue,
+    };
+    let expected_checksum = fixture.strict_checksums.then_some(ExpectedChecksum {
+        feed_data: &feed_data,
+        signed_hash: recent_blockhash,
+    });
+    let secp_signature_count = fixture.gateway_response.oracle_responses.len();
+    let instructions = get_update_consensus_ix(
+        params,
+        fixture.gateway_response.clone(),
+        fixture.recent_slot,
+        0,
+        expected_checksum,
+        fixture.secp_message_format,
+        cluster,
+    )?;
+
+    validate_secp256k1_instruction(&instructions, 0, secp_signature_count)?;
+
+    Ok(instructions)
+}
+
+fn parse_pubkey(pubkey: &str) -> AppResult<Pubkey> {
+    Pubkey::from_str(pubkey).map_err(|error| AppError::ParsingError(format!("Invalid pubkey {pubkey}: {error}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::secp256k1_program;
+
+    use super::*;
+    use crate::utils::parse_instruction_dump;
+
+    const FIXTURE: &str = include_str!("../tests/fixtures/submit_fixture.json");
+    const EXPECTED_INSTRUCTIONS: &str = include_str!("../tests/fixtures/submit_fixture_instructions.json");
+
+    fn fixture() -> SubmitFixture {
+        serde_json::from_str(FIXTURE).unwrap()
+    }
+
+    #[test]
+    fn replays_the_checked_in_fixture() {
+        let fixture = fixture();
+        let instructions = replay_submit_fixture(&fixture).unwrap();
+
+        assert_eq!(instructions, parse_instruction_dump(EXPECTED_INSTRUCTIONS).unwrap());
+        // the secp verification precedes the submit, which updates the feed
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(instructions[0].program_id, secp256k1_program::ID);
+        assert_eq!(instructions[1].program_id, Cluster::Mainnet.program_id());
+        let feed = parse_pubkey(&fixture.feed).unwrap();
+        assert!(instructions[1].accounts.iter().any(|meta| meta.pubkey == feed && meta.is_writable));
+    }
+
+    #[test]
+    fn tampered_median_fails_the_checksum() {
+        let mut fixture = fixture();
+        fixture.gateway_response.median_responses[0].value = "1600000000000000000".to_string();
+
+        let error = replay_submit_fixture(&fixture).unwrap_err();
+        assert!(matches!(error, AppError::ChecksumMismatch(_)), "{error}");
+    }
+}
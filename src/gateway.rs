@@ -0,0 +1,164 @@
+use reqwest::{
+    Client,
+    header::{CONTENT_TYPE, HeaderMap},
+};
+use serde_json::json;
+use solana_sdk::hash::Hash;
+use switchboard_on_demand_client::{
+    FeedEvalResponseSingle, FetchSignaturesConsensusParams, FetchSignaturesConsensusResponse,
+    FetchSignaturesParams,
+};
+
+use crate::app::AppResult;
+
+// Client for an oracle's gateway, mirrors switchboard_on_demand_client::Gateway (same endpoints
+// and request bodies) but attaches per gateway headers, e.g. auth for permissioned gateways.
+#[derive(Clone, Debug)]
+pub struct GatewayClient {
+    // gateway uri including the api path, see gateway_api_url
+    api_url: String,
+    client: Client,
+    headers: HeaderMap,
+}
+
+impl GatewayClient {
+    // client is shared across gateways, it has to skip certificate verification as Switchboard
+    // does its own keypair authentication
+    pub fn new(api_url: String, client: Client, headers: HeaderMap) -> Self {
+        Self {
+            api_url,
+            client,
+            headers,
+        }
+    }
+
+    pub fn gateway_url(&self) -> &str {
+        &self.api_url
+    }
+
+    pub async fn fetch_signatures_from_encoded(
+        &self,
+        params: FetchSignaturesParams,
+    ) -> AppResult<FeedEvalResponseSingle> {
+        let body = json!({
+            "api_version": "1.0.0",
+            "jobs_b64_encoded": params.encoded_jobs,
+            "recent_chainhash": params.recent_hash.unwrap_or_else(default_recent_hash),
+            "signature_scheme": "Secp256k1",
+            "hash_scheme": "Sha256",
+            "num_oracles": params.num_signatures,
+            "max_variance": (params.max_variance.unwrap_or(1) as f64 * 1e9) as u64,
+            "min_responses": params.min_responses.unwrap_or(1),
+            "use_timestamp": params.use_timestamp.unwrap_or(false),
+        });
+
+        self.post("fetch_signatures", &body).await
+    }
+
+    pub async fn fetch_signatures_consensus(
+        &self,
+        params: FetchSignaturesConsensusParams,
+    ) -> AppResult<FetchSignaturesConsensusResponse> {
+        let use_timestamp = params.use_timestamp.unwrap_or(false);
+        let feed_requests = params
+            .feed_configs
+            .iter()
+            .map(|config| {
+                json!({
+                    "jobs_b64_encoded": config.encoded_jobs,
+                    "max_variance": (config.max_variance.unwrap_or(1) as f64 * 1e9) as u64,
+                    "min_responses": config.min_responses.unwrap_or(1),
+                    "use_timestamp": use_timestamp,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let body = json!({
+            "api_version": "1.0.0",
+            "recent_hash": params.recent_hash.unwrap_or_else(default_recent_hash),
+            "signature_scheme": "Secp256k1",
+            "hash_scheme": "Sha256",
+            "feed_requests": feed_requests,
+            "num_oracles": params.num_signatures.unwrap_or(1),
+        });
+
+        self.post("fetch_signatures_consensus", &body).await
+    }
+
+    // Liveness check, succeeds when the gateway answers its test endpoint with a 2xx.
+    pub async fn ping(&self) -> AppResult<()> {
+        let url = format!("{}/test", self.api_url);
+
+        self.client
+            .get(&url)
+            .headers(self.headers.clone())
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn post<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        body: &serde_json::Value,
+    ) -> AppResult<T> {
+        let url = format!("{}/{endpoint}", self.api_url);
+
+        let response = self
+            .client
+            .post(&url)
+            .headers(self.headers.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<T>()
+            .await?;
+
+        Ok(response)
+    }
+}
+
+// weight of the latest result in GatewayStats::success_rate
+const SUCCESS_RATE_SMOOTHING: f64 = 0.2;
+
+// Outcome of the signature requests sent to a gateway over the process lifetime.
+#[derive(Clone, Copy, Debug)]
+pub struct GatewayStats {
+    pub successes: u64,
+    pub failures: u64,
+    // exponential moving average of recent results, 1.0 when every recent request succeeded
+    pub success_rate: f64,
+}
+
+impl Default for GatewayStats {
+    // untried gateways rank with the reliable ones until they fail
+    fn default() -> Self {
+        Self {
+            successes: 0,
+            failures: 0,
+            success_rate: 1.0,
+        }
+    }
+}
+
+impl GatewayStats {
+    pub fn record(&mut self, success: bool) {
+        if success {
+            self.successes += 1;
+        } else {
+            self.failures += 1;
+        }
+
+        let result = if success { 1.0 } else { 0.0 };
+        self.success_rate += SUCCESS_RATE_SMOOTHING * (result - self.success_rate);
+    }
+}
+
+// base58 encoded zero hash, what the gateway expects when no recent hash is given
+fn default_recent_hash() -> String {
+    Hash::default().to_string()
+}
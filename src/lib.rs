@@ -0,0 +1,36 @@
+// Switchboard on-demand pull feed updater: loads feeds and their queue's oracles, fetches signed
+// responses from the oracle gateways and builds (and simulates or sends) the submit instructions.
+//
+// AppClient (built through AppClientBuilder or Config::client_builder) owns the rpc and gateway
+// access, the swb flows run a whole submission for a feed and utils holds the instruction builders
+// they are made of.
+
+pub mod app;
+pub mod config;
+pub mod crossbar;
+pub mod fixture;
+pub mod gateway;
+pub mod report;
+pub mod scheduler;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod swb;
+pub mod utils;
+
+use solana_sdk::pubkey::Pubkey;
+
+pub use app::{AppClient, AppClientBuilder, AppError, AppResult, SubmitMode};
+pub use config::Config;
+pub use report::{BatchSubmitReport, SubmitOutcome, SubmitReport};
+pub use swb::{
+    execute_pull_feed_submit_batch, execute_pull_feed_submit_consensus_response,
+    execute_pull_feed_submit_consensus_response_by_hash, execute_pull_feed_submit_response,
+    resume_pull_feed_submit_batch,
+};
+pub use utils::{
+    get_consensus_signatures, get_oracle_submissions, get_solana_submit_signatures_ix,
+    get_update_consensus_ix, parse_oracle_account, parse_queue_account, parse_swb_ignore_alignment,
+};
+
+pub const SWITCHBOARD_ACCOUNT_QUEUE: Pubkey =
+    Pubkey::from_str_const("A43DyUGA7s8eXPxqEjJY6EBu1KKbNgfxF8h17VAHn13w");
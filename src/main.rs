@@ -1,37 +1,28 @@
-mod app;
-mod swb;
-mod utils;
+mod cli;
 
-use app::AppClient;
 use dotenv::dotenv;
-use solana_sdk::pubkey::Pubkey;
-use std::{env, sync::Arc};
+use std::{env, process::ExitCode, sync::Arc};
+#[cfg(feature = "server")]
+use swb_ix::server;
+use swb_ix::{
+    Config, SWITCHBOARD_ACCOUNT_QUEUE, swb,
+    utils::{self, load_keypair},
+};
 use tracing_subscriber::FmtSubscriber;
-use switchboard_on_demand_client::FetchUpdateManyParams;
-use switchboard_on_demand_client::PullFeed;
 use switchboard_on_demand_client::SbContext;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_client::rpc_config::{RpcSimulateTransactionConfig};
 use solana_sdk::commitment_config::CommitmentConfig;
-use solana_sdk::signer::keypair::Keypair;
-use solana_sdk::signature::EncodableKey;
 use solana_sdk::signature::Signer;
 use solana_sdk::transaction::{VersionedTransaction};
 use solana_sdk::message::{Message, VersionedMessage};
 use switchboard_on_demand_client::CrossbarClient;
 use switchboard_on_demand_client::QueueAccountData;
 
-pub const SWITCHBOARD_ACCOUNT_QUEUE: Pubkey =
-    Pubkey::from_str_const("A43DyUGA7s8eXPxqEjJY6EBu1KKbNgfxF8h17VAHn13w");
-
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     tracing::info!("lfg🚀🚀");
     let _ = dotenv().ok();
-    let private_key = "/path/to/your/solana/id.json";
-    let kp = Keypair::read_from_file(&private_key).unwrap();
-    let rpc_url =
-        env::var("RPC_URL").expect("Missing 'SOLANA_HTTP_URL' in environment variables");
 
     let subscriber = FmtSubscriber::builder()
         // all spans/events with a level higher than TRACE (e.g, debug, info, warn, etc.)
@@ -42,27 +33,124 @@ async fn main() {
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
-    let app_client = Arc::new(AppClient::new(&private_key, rpc_url.clone()));
+    // SWB_CONFIG points at a TOML config, otherwise everything is read from the environment
+    let config = match env::var("SWB_CONFIG") {
+        Ok(path) => Config::from_file(&path),
+        Err(_) => Config::from_env(),
+    };
+    let mut config = match config {
+        Err(app_error) => {
+            eprintln!("Invalid config\n{app_error:#?}");
+            return ExitCode::from(2);
+        }
+        Ok(config) => config,
+    };
+
+    // see cli::apply_global_args, the remaining args still start with the binary name
+    let args = match cli::apply_global_args(&mut config, &env::args().collect::<Vec<_>>()) {
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::from(2);
+        }
+        Ok(args) => args,
+    };
+
+    let app_client = match config.client_builder().build() {
+        Err(app_error) => {
+            eprintln!("Failed to build the client\n{app_error:#?}");
+            return ExitCode::from(2);
+        }
+        Ok(app_client) => Arc::new(app_client),
+    };
+
+    if args.get(1).map(String::as_str) == Some("submit-consensus") {
+        return cli::submit(app_client, true, &args[2..]).await;
+    }
+    if args.get(1).map(String::as_str) == Some("submit-response") {
+        return cli::submit(app_client, false, &args[2..]).await;
+    }
+    if args.get(1).map(String::as_str) == Some("inspect-feed") {
+        return cli::inspect_feed(&app_client, &args[2..]).await;
+    }
+    if args.get(1).map(String::as_str) == Some("oracles") {
+        return cli::oracles(&app_client, config.queue.unwrap_or(SWITCHBOARD_ACCOUNT_QUEUE), &args[2..]).await;
+    }
+    if args.get(1).map(String::as_str) == Some("record") {
+        return cli::record(&app_client, &args[2..]).await;
+    }
+    if args.get(1).map(String::as_str) == Some("replay") {
+        return cli::replay(&args[2..]);
+    }
+
+    // swb-ix serve [<addr>]
+    #[cfg(feature = "server")]
+    if args.get(1).map(String::as_str) == Some("serve") {
+        let addr = args.get(2).map(String::as_str).unwrap_or("127.0.0.1:8080");
+        let Ok(addr) = addr.parse() else {
+            eprintln!("Invalid listen address - {addr}");
+            return ExitCode::from(2);
+        };
+
+        return match server::serve(app_client, addr).await {
+            Err(io_error) => {
+                tracing::error!("Server stopped\n{io_error:#?}");
+                ExitCode::FAILURE
+            }
+            Ok(()) => ExitCode::SUCCESS,
+        };
+    }
+
+    if config.feeds.is_empty() {
+        eprintln!("No feeds configured, set SWB_FEEDS or feeds in the config file");
+        return ExitCode::from(2);
+    }
+
+    let kp = match load_keypair(&config.keypair) {
+        Err(app_error) => {
+            eprintln!("Invalid keypair\n{app_error:#?}");
+            return ExitCode::from(2);
+        }
+        Ok(kp) => kp,
+    };
 
     let ctx = SbContext::new();
-    let rpc_client = Arc::new(RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed()));
-    let crossbar = CrossbarClient::new("https://crossbar.switchboard.xyz", true);
+    let rpc_client = Arc::new(RpcClient::new_with_commitment(config.rpc_url.clone(), CommitmentConfig::confirmed()));
+    let crossbar = CrossbarClient::new(&config.crossbar_urls[0], true);
 
-    let queue_account_data = QueueAccountData::load(&rpc_client, &SWITCHBOARD_ACCOUNT_QUEUE).await.unwrap();
+    // the gateway comes from the queue of the first feed unless one is configured
+    let queue = match config.queue {
+        Some(queue) => queue,
+        None => app_client.load_feed(&config.feeds[0], None).await.unwrap().queue,
+    };
+    let queue_account_data = QueueAccountData::load(&rpc_client, &queue).await.unwrap();
     let gw = queue_account_data.fetch_gateway_from_crossbar(&crossbar).await.unwrap();
-    let (instructions, lookup_tables) = PullFeed::fetch_update_consensus_ix(
+    // SWB_DEBUG=true prints the raw gateway responses, see swb::fetch_update_instructions
+    let debug = env::var("SWB_DEBUG").is_ok_and(|debug| debug == "true" || debug == "1");
+    let (instructions, _lookup_tables) = swb::fetch_update_instructions(
         ctx,
         &rpc_client,
-        FetchUpdateManyParams {
-            crossbar: Some(crossbar),
-            debug: Some(true),
-            feeds: vec![Pubkey::from_str_const("EUQQD2fNN7h7su5TbWpUnf22zeGtF3RjEX2hgX2YPfLd")],
-            gateway: gw,
-            num_signatures: Some(1),
-            payer: kp.pubkey(),
-        },
+        crossbar,
+        gw,
+        config.feeds.clone(),
+        kp.pubkey(),
+        debug,
     ).await.unwrap();
 
+    // --dump-instructions prints the instructions instead of simulating them, see
+    // utils::dump_instructions
+    if args.iter().any(|arg| arg == "--dump-instructions") {
+        return match utils::dump_instructions(&instructions) {
+            Err(app_error) => {
+                tracing::error!("Failed to dump instructions\n{app_error:#?}");
+                ExitCode::FAILURE
+            }
+            Ok(dump) => {
+                println!("{dump}");
+                ExitCode::SUCCESS
+            }
+        };
+    }
+
     let recent_blockhash = rpc_client.get_latest_blockhash().await.unwrap();
     let mut message = Message::new(&instructions, Some(&kp.pubkey()));
     message.recent_blockhash = recent_blockhash;
@@ -75,4 +163,6 @@ async fn main() {
     };
     let sim_res = rpc_client.simulate_transaction_with_config(&versioned_tx, sim_config).await.unwrap();
     println!("sim res: {:?}", sim_res);
+
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,94 @@
+use std::fmt::Display;
+
+use rust_decimal::Decimal;
+use serde::{Serialize, Serializer, ser::SerializeSeq};
+use solana_sdk::{hash::Hash, pubkey::Pubkey, signature::Signature};
+
+// Everything a single run of the submit pipeline did, returned by the execute functions.
+#[derive(Clone, Debug, Serialize)]
+pub struct SubmitReport {
+    #[serde(serialize_with = "serialize_display")]
+    pub feed: Pubkey,
+    #[serde(serialize_with = "serialize_display")]
+    pub queue: Pubkey,
+    // gateway the submitted signatures came from
+    pub gateway: String,
+    // stake score of the gateway's oracle, only read when gateways are weighted by stake
+    pub gateway_stake_score: Option<u64>,
+    pub gateways_tried: usize,
+    // oracles that returned signatures, in the same order as values for per-oracle submissions
+    #[serde(serialize_with = "serialize_display_seq")]
+    pub oracles: Vec<Pubkey>,
+    // consensus medians, or one value per oracle for the per-oracle submission; None when unparsable
+    pub values: Vec<Option<Decimal>>,
+    pub recent_slot: u64,
+    #[serde(serialize_with = "serialize_display")]
+    pub blockhash: Hash,
+    pub units_consumed: Option<u64>,
+    // None while the submission is only simulated
+    #[serde(serialize_with = "serialize_display_option")]
+    pub signature: Option<Signature>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub enum SubmitOutcome {
+    // boxed, the report dwarfs NoChangeNeeded
+    Submitted(Box<SubmitReport>),
+    // the fresh consensus value is within value_change_threshold of the feed's current value
+    NoChangeNeeded {
+        #[serde(serialize_with = "serialize_display")]
+        feed: Pubkey,
+        current_value: Decimal,
+        new_value: Decimal,
+    },
+}
+
+// Result of a batch of submissions, the feeds that failed can be retried with
+// resume_pull_feed_submit_batch.
+#[derive(Clone, Debug, Serialize)]
+pub struct BatchSubmitReport {
+    // slot every submission of the batch was built for
+    pub recent_slot: u64,
+    pub outcomes: Vec<SubmitOutcome>,
+    #[serde(serialize_with = "serialize_display_seq")]
+    pub failed: Vec<Pubkey>,
+}
+
+// One oracle of a queue as seen by AppClient::get_queue_oracle_statuses.
+#[derive(Clone, Debug, Serialize)]
+pub struct OracleStatus {
+    #[serde(serialize_with = "serialize_display")]
+    pub oracle: Pubkey,
+    // None when the oracle account is missing or advertises no gateway
+    pub gateway_uri: Option<String>,
+    // finalized epoch stake score, None when the stats account is missing or unparsable
+    pub stake_score: Option<u64>,
+    // whether the gateway answered a ping
+    pub reachable: bool,
+}
+
+// pubkeys, hashes and signatures serialize as byte arrays by default, base58 reads better in JSON
+fn serialize_display<S: Serializer, T: Display>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+fn serialize_display_option<S: Serializer, T: Display>(
+    value: &Option<T>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.collect_str(value),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn serialize_display_seq<S: Serializer, T: Display>(
+    values: &[T],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(values.len()))?;
+    for value in values {
+        seq.serialize_element(&value.to_string())?;
+    }
+    seq.end()
+}
@@ -0,0 +1,145 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    app::{AppClient, AppResult, SubmitMode},
+    swb::execute_pull_feed_submit_consensus_response,
+};
+
+// ~400ms per slot on mainnet
+const SLOT_DURATION: Duration = Duration::from_millis(400);
+
+// Slots left before a feed exceeds its max staleness, 0 when it is due. A feed that was never
+// updated is always due.
+pub fn slots_until_due(staleness: Option<u64>, max_staleness: u64) -> u64 {
+    staleness.map_or(0, |staleness| max_staleness.saturating_sub(staleness))
+}
+
+#[derive(Clone, Debug)]
+pub struct ScheduledTick {
+    // feeds a submission was started for, most overdue first
+    pub submitted: Vec<Pubkey>,
+    // time until the soonest of the remaining feeds goes stale
+    pub next_tick_in: Duration,
+}
+
+// Submits feeds only once they reach their max staleness instead of on every loop. Submissions
+// go through the AppClient so they share its rate limiter, and a feed is never submitted again
+// while a previous submission for it is still in flight.
+pub struct Scheduler {
+    app_client: Arc<AppClient>,
+    // feed => max staleness in slots
+    feeds: HashMap<Pubkey, u64>,
+    cross_check: Option<usize>,
+    submit_mode: SubmitMode,
+    in_flight: Arc<Mutex<HashSet<Pubkey>>>,
+}
+
+impl Scheduler {
+    pub fn new(
+        app_client: Arc<AppClient>,
+        feeds: HashMap<Pubkey, u64>,
+        cross_check: Option<usize>,
+        submit_mode: SubmitMode,
+    ) -> Self {
+        Self {
+            app_client,
+            feeds,
+            cross_check,
+            submit_mode,
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    // Reads every feed once, starts a submission for each due feed and returns how long to wait
+    // before the next tick.
+    pub async fn tick(&self) -> AppResult<ScheduledTick> {
+        let feed_pubkeys = self.feeds.keys().copied().collect::<Vec<_>>();
+        let feed_infos = self.app_client.read_feeds(&feed_pubkeys, None).await?;
+
+        let mut due_feeds = Vec::new();
+        let mut next_due_slots = None::<u64>;
+
+        for (feed_pubkey, max_staleness) in &self.feeds {
+            let Some(feed_info) = feed_infos.get(feed_pubkey) else {
+                tracing::warn!("Scheduler couldn't read - {feed_pubkey}, skipping it this tick");
+                continue;
+            };
+
+            match slots_until_due(feed_info.staleness, *max_staleness) {
+                0 => due_feeds.push((*feed_pubkey, feed_info.staleness)),
+                slots => {
+                    next_due_slots = Some(next_due_slots.map_or(slots, |next| next.min(slots)))
+                }
+            }
+        }
+
+        // most overdue first, never updated feeds ahead of everything
+        due_feeds.sort_by_key(|(_, staleness)| std::cmp::Reverse(staleness.unwrap_or(u64::MAX)));
+
+        let mut submitted = Vec::new();
+        for (feed_pubkey, _) in due_feeds {
+            if !self.in_flight.lock().unwrap().insert(feed_pubkey) {
+                tracing::info!("Submission already in flight for - {feed_pubkey}");
+                continue;
+            }
+
+            let app_client = self.app_client.clone();
+            let in_flight = self.in_flight.clone();
+            let cross_check = self.cross_check;
+            let submit_mode = self.submit_mode;
+            tokio::spawn(async move {
+                execute_pull_feed_submit_consensus_response(
+                    app_client,
+                    feed_pubkey,
+                    cross_check,
+                    None,
+                    None,
+                    submit_mode,
+                )
+                .await;
+                in_flight.lock().unwrap().remove(&feed_pubkey);
+            });
+
+            submitted.push(feed_pubkey);
+        }
+
+        // when every feed is due (or in flight) check again after a single slot
+        let next_tick_in = SLOT_DURATION * next_due_slots.unwrap_or(1).max(1) as u32;
+
+        Ok(ScheduledTick {
+            submitted,
+            next_tick_in,
+        })
+    }
+
+    pub async fn run(&self) {
+        let mut attempt = 0;
+
+        while !self.app_client.is_closed() {
+            match self.tick().await {
+                Err(app_error) => {
+                    tracing::error!("Scheduler tick failed\n{app_error:#?}");
+                    tokio::time::sleep(self.app_client.retry_delay(attempt)).await;
+                    attempt += 1;
+                }
+                Ok(ScheduledTick {
+                    submitted,
+                    next_tick_in,
+                }) => {
+                    tracing::info!(
+                        "Submitted {} feeds, next tick in {next_tick_in:?} - {submitted:#?}",
+                        submitted.len()
+                    );
+                    tokio::time::sleep(next_tick_in).await;
+                    attempt = 0;
+                }
+            }
+        }
+    }
+}
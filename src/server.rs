@@ -0,0 +1,242 @@
+use std::{convert::Infallible, fmt::Display, net::SocketAddr, sync::Arc};
+
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    Method, Request, Response, StatusCode,
+    body::{Body, Bytes},
+    header::{CONTENT_TYPE, HeaderValue},
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use solana_sdk::pubkey::Pubkey;
+use tokio::net::TcpListener;
+
+use crate::{
+    app::{AppClient, SubmitMode},
+    swb::execute_pull_feed_submit_consensus_response,
+};
+
+#[derive(Deserialize)]
+struct SubmitRequest {
+    feed: String,
+    // see execute_pull_feed_submit_consensus_response
+    cross_check: Option<usize>,
+    // "simulate" (default) or "send"
+    #[serde(default)]
+    mode: SubmitMode,
+}
+
+// Serves the submit pipeline over HTTP until the listener fails:
+//   POST /submit {"feed": "<pubkey>", "cross_check": null, "mode": "simulate"} => SubmitOutcome
+//   GET /feed/<pubkey> => FeedResult
+// There is no authentication, bind it to an internal address.
+pub async fn serve(app_client: Arc<AppClient>, addr: SocketAddr) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("Listening on - {addr}");
+
+    loop {
+        let (stream, peer_addr) = listener.accept().await?;
+        let app_client = app_client.clone();
+
+        tokio::spawn(async move {
+            let service = service_fn(move |request| handle(app_client.clone(), request));
+
+            if let Err(hyper_error) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::warn!("Failed to serve connection from - {peer_addr}\n{hyper_error:#?}");
+            }
+        });
+    }
+}
+
+// Generic over the request body so the routes can be exercised without a connection.
+async fn handle<B: Body<Error: Display>>(
+    app_client: Arc<AppClient>,
+    request: Request<B>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let path = request.uri().path().to_string();
+
+    let response = match (request.method(), path.as_str()) {
+        (&Method::POST, "/submit") => submit(app_client, request).await,
+        (&Method::GET, path) if path.starts_with("/feed/") => {
+            feed(&app_client, &path["/feed/".len()..]).await
+        }
+        _ => error_response(StatusCode::NOT_FOUND, "Not found"),
+    };
+
+    Ok(response)
+}
+
+async fn submit<B: Body<Error: Display>>(app_client: Arc<AppClient>, request: Request<B>) -> Response<Full<Bytes>> {
+    let body = match request.into_body().collect().await {
+        Err(hyper_error) => {
+            return error_response(StatusCode::BAD_REQUEST, &format!("{hyper_error}"));
+        }
+        Ok(body) => body.to_bytes(),
+    };
+
+    let submit_request = match serde_json::from_slice::<SubmitRequest>(&body) {
+        Err(serde_error) => {
+            return error_response(StatusCode::BAD_REQUEST, &format!("{serde_error}"));
+        }
+        Ok(submit_request) => submit_request,
+    };
+
+    let Ok(feed_pubkey) = submit_request.feed.parse::<Pubkey>() else {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid feed pubkey");
+    };
+
+    // the pipeline logs why it gave up, the caller only learns that it did
+    match execute_pull_feed_submit_consensus_response(
+        app_client,
+        feed_pubkey,
+        submit_request.cross_check,
+        None,
+        None,
+        submit_request.mode,
+    )
+    .await
+    {
+        None => error_response(StatusCode::BAD_GATEWAY, "Submission failed, see the server logs"),
+        Some(submit_outcome) => json_response(StatusCode::OK, &submit_outcome),
+    }
+}
+
+async fn feed(app_client: &AppClient, feed_pubkey: &str) -> Response<Full<Bytes>> {
+    let Ok(feed_pubkey) = feed_pubkey.parse::<Pubkey>() else {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid feed pubkey");
+    };
+
+    match app_client.get_feed_result(&feed_pubkey, None).await {
+        Err(app_error) => {
+            tracing::error!("Failed to read feed result - {feed_pubkey}\n{app_error:#?}");
+            // rpc errors can carry the rpc url, the details stay in the server logs
+            error_response(StatusCode::BAD_GATEWAY, "Failed to read the feed, see the server logs")
+        }
+        Ok(feed_result) => json_response(StatusCode::OK, &feed_result),
+    }
+}
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<Full<Bytes>> {
+    let body = match serde_json::to_vec(body) {
+        Err(serde_error) => {
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("{serde_error}"));
+        }
+        Ok(body) => body,
+    };
+
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    response
+}
+
+fn error_response(status: StatusCode, error: &str) -> Response<Full<Bytes>> {
+    json_response(status, &json!({ "error": error }))
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use http_body_util::{BodyExt, Full};
+    use hyper::{Request, StatusCode, body::Bytes};
+    use serde_json::Value;
+    use solana_sdk::pubkey::Pubkey;
+
+    use crate::{
+        app::mock_rpc::{MockRpc, feed_account, rpc_error},
+        utils::Cluster,
+    };
+
+    use super::handle;
+
+    async fn call(mock: &MockRpc, method: &str, uri: &str, body: &str) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Full::new(Bytes::from(body.to_string())))
+            .unwrap();
+        let response = handle(Arc::new(mock.client()), request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn feed_returns_the_latest_result() {
+        let feed = Pubkey::new_unique();
+        let mock = MockRpc::with_accounts(
+            1,
+            HashMap::from([(
+                feed,
+                feed_account(Cluster::default().program_id(), |feed_data| {
+                    feed_data.result.value = 1_500_000_000_000_000_000;
+                    feed_data.result.slot = 42;
+                    feed_data.last_update_timestamp = 1_700_000_000;
+                }),
+            )]),
+        );
+
+        let (status, body) = call(&mock, "GET", &format!("/feed/{feed}"), "").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["value"], "1.500000000000000000");
+        assert_eq!(body["slot"], 42);
+        assert_eq!(body["timestamp"], 1_700_000_000);
+    }
+
+    #[tokio::test]
+    async fn feed_keeps_rpc_errors_out_of_the_response() {
+        let mock = MockRpc::new(|_, _| Err(rpc_error("https://rpc.example/secret-api-key unavailable")));
+
+        let (status, body) = call(&mock, "GET", &format!("/feed/{}", Pubkey::new_unique()), "").await;
+
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(body["error"], "Failed to read the feed, see the server logs");
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_requests() {
+        let mock = MockRpc::new(|_, _| unreachable!());
+
+        let (status, body) = call(&mock, "GET", "/feed/not-a-pubkey", "").await;
+        assert_eq!((status, &body["error"]), (StatusCode::BAD_REQUEST, &Value::from("Invalid feed pubkey")));
+
+        let (status, _) = call(&mock, "POST", "/submit", "{").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, body) = call(&mock, "POST", "/submit", r#"{"feed": "not-a-pubkey"}"#).await;
+        assert_eq!((status, &body["error"]), (StatusCode::BAD_REQUEST, &Value::from("Invalid feed pubkey")));
+
+        let (status, _) = call(&mock, "GET", "/submit", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        assert!(mock.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn submit_reports_a_failed_pipeline() {
+        let mock = MockRpc::new(|_, _| Err(rpc_error("rpc unavailable")));
+
+        let (status, body) = call(
+            &mock,
+            "POST",
+            "/submit",
+            &format!(r#"{{"feed": "{}", "mode": "simulate"}}"#, Pubkey::new_unique()),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(body["error"], "Submission failed, see the server logs");
+    }
+}
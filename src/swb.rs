@@ -1,367 +1,1014 @@
-use std::{cell::RefCell, sync::Arc};
+use std::{collections::HashMap, sync::Arc};
 
-use solana_sdk::pubkey::Pubkey;
-use switchboard_on_demand::{OracleAccountData, PullFeedAccountData};
-use switchboard_on_demand_client::{Gateway, QueueAccountData, SolanaSubmitSignaturesParams};
+use rust_decimal::Decimal;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    hash::Hash, instruction::Instruction, message::AddressLookupTableAccount, pubkey::Pubkey,
+    signature::Signature,
+};
+use switchboard_on_demand::PullFeedAccountData;
+use switchboard_on_demand_client::{
+    CrossbarClient, FetchUpdateManyParams, Gateway, PullFeed, SbContext,
+    SolanaSubmitSignaturesParams,
+};
+use tracing::{Instrument, Span, field, info_span};
 
 use crate::{
-    SWITCHBOARD_ACCOUNT_QUEUE,
-    app::AppClient,
+    app::{AppClient, AppError, AppResult, SubmitMode},
+    gateway::GatewayClient,
+    report::{BatchSubmitReport, SubmitOutcome, SubmitReport},
     utils::{
-        UrlType, construct_url, get_consensus_signatures, get_oracle_submissions,
-        get_solana_submit_signatures_ix, get_update_consensus_ix, parse_swb_ignore_alignment,
+        ExpectedChecksum, ensure_gateways_available,
+        ensure_min_responses_reachable,
+        exceeds_value_change_threshold,
+        extract_oracle_timestamps, format_submit_account_metas, get_consensus_signatures,
+        get_cross_checked_consensus_signatures, get_oracle_submissions,
+        get_solana_submit_signatures_ix, get_update_consensus_ix,
+        validate_secp256k1_instruction, VALUE_SCALE,
     },
 };
 
-pub async fn execute_pull_feed_submit_consensus_response(app_client: Arc<AppClient>) {
-    let feed_pubkey = Pubkey::from_str_const("6CyMpkE6kb1MkcxhNH5PM7wAPwm2Agu2P4Qa51nQgWfi");
-
-    let feed_account = match app_client.get_account(&feed_pubkey).await {
+// Startup self-check, confirms the deployed on-demand program still matches the layout this crate
+// builds instructions for before any submission is attempted.
+pub async fn execute_on_demand_program_self_check(app_client: Arc<AppClient>) -> bool {
+    match app_client.check_on_demand_program().await {
         Err(app_error) => {
-            tracing::error!(
-                "Failed to retrieve PullFeedAccountData for - {feed_pubkey}\n{app_error:#?}"
-            );
-            return;
+            tracing::error!("Failed to check the on-demand program\n{app_error:#?}");
+            false
         }
-        Ok(account) => account,
-    };
+        Ok(program_info) => {
+            tracing::info!("On-demand program - {program_info:#?}");
+            program_info.state_layout_matches
+        }
+    }
+}
 
-    let mut mut_account_data = feed_account.data.clone();
-    let swb_feed_data = RefCell::new(&mut mut_account_data[..]);
-    let swb_feed_data = swb_feed_data.borrow();
-    let pull_feed_account_data = match parse_swb_ignore_alignment(swb_feed_data) {
-        Err(on_demand_error) => {
-            tracing::error!(
-                "Failed to parse swb_feed_data to PullFeedAccountData for SWB-on-Demand - {feed_pubkey}\n{on_demand_error:#?}"
+// Consensus update instructions built by switchboard_on_demand_client for the given feeds.
+// debug makes the client print the raw gateway responses to stdout, the gateway request itself is
+// the same either way. Keep it off in production, the responses are large and printed on every
+// call, and enable it while developing to see what the oracles returned.
+pub async fn fetch_update_instructions(
+    context: Arc<SbContext>,
+    rpc_client: &RpcClient,
+    crossbar: CrossbarClient,
+    gateway: Gateway,
+    feeds: Vec<Pubkey>,
+    payer: Pubkey,
+    debug: bool,
+) -> AppResult<(Vec<Instruction>, Vec<AddressLookupTableAccount>)> {
+    PullFeed::fetch_update_consensus_ix(
+        context,
+        rpc_client,
+        FetchUpdateManyParams {
+            crossbar: Some(crossbar),
+            debug: Some(debug),
+            feeds,
+            gateway,
+            num_signatures: Some(1),
+            payer,
+        },
+    )
+    .await
+    .map_err(|anyhow_error| AppError::OnDemandClientError(format!("{anyhow_error:#}")))
+}
+
+// Logs the labeled accounts of the submit instruction, to spot a wrong account meta after a failed
+// simulation.
+fn log_submit_account_metas(instructions: &[Instruction]) {
+    for instruction in instructions {
+        if let Ok(account_metas) = format_submit_account_metas(instruction) {
+            tracing::warn!("Submit instruction accounts:\n{account_metas}");
+        }
+    }
+}
+
+// Simulates the submission, or signs and sends it with SubmitMode::Send, returning the units
+// consumed (only known when simulated) and the signature (only when sent).
+async fn submit_instructions(
+    app_client: &AppClient,
+    lookup_tables: &[AddressLookupTableAccount],
+    instructions: &[Instruction],
+    latest_blockhash: Hash,
+    submit_mode: SubmitMode,
+) -> AppResult<(Option<u64>, Option<Signature>)> {
+    let alts = Some(lookup_tables).filter(|lookup_tables| !lookup_tables.is_empty());
+
+    match submit_mode {
+        SubmitMode::Simulate => {
+            let sim = app_client
+                .call_instructions(alts, instructions, latest_blockhash, None)
+                .instrument(info_span!("simulate"))
+                .await?;
+            tracing::info!("Simulation result: {}", app_client.log_value(&sim));
+
+            Ok((sim.response.value.units_consumed, None))
+        }
+        SubmitMode::Send => {
+            let send_result = app_client
+                .send_instructions(alts, instructions, Some(latest_blockhash), None)
+                .instrument(info_span!("send"))
+                .await?;
+            tracing::info!(
+                "Transaction {} confirmed after {} attempt(s)",
+                send_result.signature,
+                send_result.attempts
             );
-            return;
+
+            Ok((None, Some(send_result.signature)))
+        }
+    }
+}
+
+// Puts the gateways of higher staked oracles first when weight_gateways_by_stake is enabled,
+// otherwise keeps the queue order. Also returns each gateway's oracle stake score (in the returned
+// order) when they were read.
+async fn order_gateways_by_stake(
+    app_client: &AppClient,
+    gateways: Vec<(Pubkey, GatewayClient)>,
+) -> (Vec<GatewayClient>, Option<Vec<u64>>) {
+    if !app_client.weight_gateways_by_stake() {
+        return (gateways.into_iter().map(|(_, gateway)| gateway).collect(), None);
+    }
+
+    let oracles = gateways.iter().map(|(oracle, _)| *oracle).collect::<Vec<_>>();
+    let stake_scores = match app_client.get_oracle_stake_scores(&oracles).await {
+        Err(app_error) => {
+            tracing::warn!("Failed to read oracle stake scores, keeping the queue order\n{app_error:#?}");
+            return (gateways.into_iter().map(|(_, gateway)| gateway).collect(), None);
         }
-        Ok(pull_feed_account_data) => pull_feed_account_data,
+        Ok(stake_scores) => stake_scores,
     };
 
-    // pull_feed_account_data.f
+    let mut weighted_gateways = gateways
+        .into_iter()
+        .map(|(oracle, gateway)| (stake_scores.get(&oracle).copied().unwrap_or(0), gateway))
+        .collect::<Vec<_>>();
+    weighted_gateways.sort_by_key(|(stake_score, _)| std::cmp::Reverse(*stake_score));
 
-    let feed_data = &pull_feed_account_data;
+    let (stake_scores, gateways) = weighted_gateways.into_iter().unzip();
 
-    tracing::info!(
-        "Successfully deserialized - {feed_pubkey} to PullFeedAccountData - {pull_feed_account_data:#?}"
-    );
+    (gateways, Some(stake_scores))
+}
 
-    let queue_account_data =
-        match QueueAccountData::load(app_client.rpc_client(), &SWITCHBOARD_ACCOUNT_QUEUE).await {
-            Err(error) => {
-                tracing::error!(
-                    "Failed to retrieve QueueAccountData - {SWITCHBOARD_ACCOUNT_QUEUE}\n{error:#?}"
-                );
-                return;
-            }
-            Ok(data) => data,
-        };
+// Blockhash and slot supplied by the caller, e.g. shared across a batch of feeds, whichever is None
+// is fetched through get_submission_blockhash_and_slot_after. A supplied slot the recent_slot_strategy
+// rejects for this feed (not past its last update) is replaced along with its blockhash.
+async fn resolve_blockhash_and_slot(
+    app_client: &AppClient,
+    recent_blockhash: Option<Hash>,
+    recent_slot: Option<u64>,
+    last_update_slot: Option<u64>,
+) -> AppResult<(Hash, u64)> {
+    let slot_rejected = recent_slot.is_some_and(|recent_slot| {
+        !app_client
+            .recent_slot_strategy()
+            .accepts(recent_slot, last_update_slot)
+    });
+    if slot_rejected {
+        tracing::info!("Supplied recent slot {recent_slot:?} isn't past the feed's last update, refetching");
+    }
+
+    match (recent_blockhash, recent_slot) {
+        (Some(recent_blockhash), Some(recent_slot)) if !slot_rejected => Ok((recent_blockhash, recent_slot)),
+        (None, Some(recent_slot)) if !slot_rejected => {
+            let (latest_blockhash, _) = app_client.get_submission_blockhash_and_slot().await?;
+            Ok((latest_blockhash, recent_slot))
+        }
+        _ => {
+            let (latest_blockhash, latest_slot) = app_client
+                .get_submission_blockhash_and_slot_after(last_update_slot)
+                .await?;
+            let recent_blockhash = recent_blockhash.filter(|_| !slot_rejected);
+            Ok((recent_blockhash.unwrap_or(latest_blockhash), latest_slot))
+        }
+    }
+}
 
-    let queue_oracle_keys = queue_account_data.oracle_keys();
+// Puts the gateways that succeeded most often in this process first when
+// weight_gateways_by_success_rate is enabled. The sort is stable, so equally reliable gateways keep
+// their stake order, and stake scores move along with their gateway.
+fn order_gateways_by_success_rate(
+    app_client: &AppClient,
+    gateways: Vec<GatewayClient>,
+    stake_scores: Option<Vec<u64>>,
+) -> (Vec<GatewayClient>, Option<Vec<u64>>) {
+    if !app_client.weight_gateways_by_success_rate() {
+        return (gateways, stake_scores);
+    }
 
-    let oracle_accounts = match app_client
-        .get_multiple_accounts(&queue_oracle_keys, None)
-        .await
-    {
+    let mut ranked_gateways = gateways
+        .into_iter()
+        .enumerate()
+        .map(|(index, gateway)| (app_client.gateway_success_rate(&gateway), index, gateway))
+        .collect::<Vec<_>>();
+    ranked_gateways.sort_by(|(success_rate, ..), (other_success_rate, ..)| {
+        other_success_rate.total_cmp(success_rate)
+    });
+
+    let stake_scores = stake_scores.map(|stake_scores| {
+        ranked_gateways
+            .iter()
+            .map(|(_, index, _)| stake_scores[*index])
+            .collect()
+    });
+    let gateways = ranked_gateways
+        .into_iter()
+        .map(|(_, _, gateway)| gateway)
+        .collect();
+
+    (gateways, stake_scores)
+}
+
+// What both submit paths need before asking the gateways: the feed, its queue's gateways in the
+// order they are tried and the blockhash and slot to submit with.
+struct FeedContext {
+    feed_data: PullFeedAccountData,
+    queue_pubkey: Pubkey,
+    gateways: Vec<GatewayClient>,
+    gateway_stake_scores: Option<Vec<u64>>,
+    latest_blockhash: Hash,
+    recent_slot: u64,
+}
+
+// Loads the feed and its queue's oracles and orders their gateways, None (logged) when the feed
+// can't be submitted.
+async fn prepare_feed_context(
+    app_client: &AppClient,
+    feed_pubkey: Pubkey,
+    recent_blockhash: Option<Hash>,
+    recent_slot: Option<u64>,
+) -> Option<FeedContext> {
+    // a configured expected_queue is the feed's queue (or the submission fails), so it's read along
+    // with the feed. Batches preload each group's queue into the account cache instead.
+    let queue_hint = app_client.expected_queue();
+    let submission_accounts = match app_client.load_submission_accounts(&feed_pubkey, queue_hint).await {
         Err(app_error) => {
             tracing::error!(
-                "Failed to get multiple accounts - {queue_oracle_keys:#?}\n{app_error:#?}"
+                "Failed to load the accounts of the submission for SWB-on-Demand - {feed_pubkey}\n{app_error:#?}"
             );
-            return;
+            return None;
         }
-        Ok(accounts) => accounts,
+        Ok(submission_accounts) => submission_accounts,
     };
 
-    // gather all the gateway uris the retrieved oracle_accounts contain
-    let queue_gateways = oracle_accounts
-        .iter()
-        .zip(queue_oracle_keys)
-        .into_iter()
-        .filter_map(|(account, oracle_pubkey)| {
-            let Some(oracle_account) = account else {
-                tracing::warn!("getMultipleAccounts returned None for - oracle_pubkey: {oracle_pubkey}");
-                return None;
-            };
+    let feed_data = submission_accounts.feed;
 
-            let bytes_data = &oracle_account.data[8..];
-            let oracle_account_data: &OracleAccountData =
-                bytemuck::try_from_bytes(bytes_data).unwrap();
+    tracing::info!(
+        "Successfully deserialized - {feed_pubkey} to PullFeedAccountData - {}",
+        app_client.log_account_data(&feed_data)
+    );
 
-            let gateway_uri = oracle_account_data.gateway_uri();
-            tracing::info!("Successfully deserialized - {oracle_pubkey}\n{oracle_account_data:#?} with gateway - {gateway_uri:#?}");
+    // submissions go to the feed's own queue, feeds may be spread across several
+    let queue_pubkey = feed_data.queue;
+    Span::current().record("queue", field::display(queue_pubkey));
 
-            let Some(gateway_uri) = gateway_uri else {
+    // gather all the gateway uris the oracles of the queue advertise
+    let gateways = submission_accounts
+        .oracles
+        .iter()
+        .filter_map(|(oracle_pubkey, oracle_account_data)| {
+            let Some(oracle_account_data) = oracle_account_data else {
+                tracing::warn!("Skipping missing or unparsable oracle - {oracle_pubkey}");
                 return None;
             };
 
-            Some(Gateway::new(gateway_uri))
+            let gateway_uri = oracle_account_data.gateway_uri();
+            tracing::info!(
+                "Successfully deserialized - {oracle_pubkey}\n{} with gateway - {gateway_uri:#?}",
+                app_client.log_account_data(oracle_account_data)
+            );
+
+            Some((*oracle_pubkey, app_client.gateway(gateway_uri?)))
         })
         .collect::<Vec<_>>();
 
-    tracing::info!("Constructed queue_gateways => {queue_gateways:#?}");
+    let (gateways, gateway_stake_scores) = order_gateways_by_stake(app_client, gateways).await;
+    let (gateways, gateway_stake_scores) =
+        order_gateways_by_success_rate(app_client, gateways, gateway_stake_scores);
 
-    let mut retry = 0;
-    let max_retry = queue_gateways.len();
+    tracing::info!("Constructed queue_gateways => {}", app_client.log_gateways(&gateways));
 
-    let (latest_blockhash_result, recent_slot_result) =
-        tokio::join!(app_client.get_latest_blockhash(), app_client.get_slot());
+    if let Err(app_error) = ensure_gateways_available(gateways.len(), submission_accounts.oracles.len())
+        .and_then(|()| ensure_min_responses_reachable(&feed_data, gateways.len()))
+    {
+        tracing::error!("Not submitting - {feed_pubkey}\n{app_error:#?}");
+        return None;
+    }
 
-    let latest_blockhash = match latest_blockhash_result {
+    // The submitted slot is checked against the SlotHashes sysvar: a too fresh (processed) slot is
+    // fine, a too stale one falls out of it. Accounts are still read at confirmed.
+    let (latest_blockhash, recent_slot) = match resolve_blockhash_and_slot(
+        app_client,
+        recent_blockhash,
+        recent_slot,
+        feed_data.result.result_slot(),
+    )
+    .await
+    {
         Err(app_error) => {
-            tracing::error!("Failed to retrieve latest blockhash\n{app_error:#?}");
-            return;
+            tracing::error!("Failed to retrieve latest blockhash and current slot\n{app_error:#?}");
+            return None;
         }
-        Ok(blockhash) => blockhash,
+        Ok(blockhash_and_slot) => blockhash_and_slot,
     };
 
-    let recent_slot = match recent_slot_result {
-        Err(app_error) => {
-            tracing::error!("Failed to retrieve current slot\n{app_error:#?}");
-            return;
-        }
-        Ok(slot) => slot,
-    };
+    Some(FeedContext {
+        feed_data,
+        queue_pubkey,
+        gateways,
+        gateway_stake_scores,
+        latest_blockhash,
+        recent_slot,
+    })
+}
 
-    let price_signatures;
+// Asks the gateways in order, each at most once, until one answers, returning the response along
+// with the index of the gateway that gave it. name is what is fetched, for the logs.
+async fn fetch_from_gateways<'a, T, F, Fut>(
+    app_client: &AppClient,
+    feed_pubkey: &Pubkey,
+    gateways: &'a [GatewayClient],
+    name: &str,
+    fetch: F,
+) -> Option<(T, usize)>
+where
+    F: Fn(&'a GatewayClient) -> Fut,
+    Fut: Future<Output = AppResult<T>>,
+{
+    let mut retry = 0;
+    let max_retry = gateways.len();
 
     loop {
-        let gateway = &queue_gateways[retry];
+        let Some(gateway) = gateways.get(retry) else {
+            tracing::error!("No gateway left to retrieve {name} for - {feed_pubkey}");
+            return None;
+        };
 
-        let function_params_as_string = format!(
-            "feed_data: {feed_data:#?} gateway: {gateway:#?} latest_blockhash: {latest_blockhash}"
+        tracing::info!(
+            "#{retry} attempt using - {}",
+            app_client.log_gateways(std::slice::from_ref(gateway))
         );
-        match get_consensus_signatures(feed_data, gateway, latest_blockhash).await {
+
+        match fetch(gateway)
+            .instrument(info_span!("fetch_signatures", gateway = gateway.gateway_url()))
+            .await
+        {
             Err(app_error) => {
-                tracing::warn!("Failed to retrieve consensus_signatures\n{app_error:#?}");
+                tracing::warn!("Failed to retrieve {name}\n{app_error:#?}");
+
+                // every gateway would hit the same missing or mismatching crossbar jobs or broken data source
+                if matches!(
+                    app_error,
+                    AppError::FeedJobsNotFound(_)
+                        | AppError::FeedSourceUnavailable(_)
+                        | AppError::JobsHashMismatch(_)
+                ) {
+                    return None;
+                }
+
+                // crossbar failures aren't the gateway's
+                if !matches!(app_error, AppError::CrossbarError(_)) {
+                    app_client.record_gateway_result(gateway, false);
+                }
 
                 retry += 1;
 
                 if retry < max_retry {
-                    tracing::warn!(
-                        "Retrying to get consensus signatures after {retry}/{max_retry} tries",
-                    );
+                    tracing::warn!("Retrying to get {name} after {retry}/{max_retry} tries");
+                    tokio::time::sleep(app_client.retry_delay(retry as u32 - 1)).await;
                     continue;
                 }
-                tracing::error!("Failed to retrieve consensus_signatures\n{app_error:#?}.");
+                tracing::error!("Failed to retrieve {name}\n{app_error:#?}.");
 
-                return;
+                return None;
             }
-            Ok(consensus_response) => {
-                tracing::info!(
-                    "get_consensus_signatures() from {function_params_as_string} => {consensus_response:#?}"
-                );
-                price_signatures = consensus_response;
-                break;
+            Ok(response) => {
+                app_client.record_gateway_result(gateway, true);
+                Span::current().record("gateway", gateway.gateway_url());
+                return Some((response, retry));
             }
-        };
+        }
     }
+}
 
-    let params = SolanaSubmitSignaturesParams {
-        feed: feed_pubkey,
-        payer: app_client.keypair_pubkey(),
-        queue: SWITCHBOARD_ACCOUNT_QUEUE,
-    };
-    let instructions = match get_update_consensus_ix(params, price_signatures, recent_slot) {
+// Preflights the submit instructions, appends the priority fee and submits them with the
+// configured lookup tables. secp_signature_count: checks the leading secp256k1 ix against the
+// final transaction layout. name is the submitted ix, for the logs.
+async fn submit_feed_instructions(
+    app_client: &AppClient,
+    feed_pubkey: &Pubkey,
+    name: &str,
+    instructions: Vec<Instruction>,
+    secp_signature_count: Option<usize>,
+    latest_blockhash: Hash,
+    submit_mode: SubmitMode,
+) -> Option<(Option<u64>, Option<Signature>)> {
+    if let Err(app_error) = app_client
+        .preflight_instruction_accounts(&instructions)
+        .instrument(info_span!("preflight"))
+        .await
+    {
+        tracing::error!("Submission preflight failed for - {feed_pubkey}\n{app_error:#?}");
+        return None;
+    }
+
+    let instructions = match app_client.with_priority_fee(instructions).await {
         Err(app_error) => {
-            tracing::error!("Failed to construct pull_feed_submit_consensus ix\n{app_error:#?}");
-            return;
+            tracing::error!("Failed to estimate priority fee\n{app_error:#?}");
+            return None;
         }
         Ok(ixs) => ixs,
     };
 
-    let sim = match app_client
-        .call_instructions(
-            None,
-            &instructions,
-            //[instructions[0].clone()],
-            latest_blockhash,
-            None,
-        )
+    // the secp256k1 ix was built for index 0
+    if let Some(secp_signature_count) = secp_signature_count
+        && let Err(app_error) = validate_secp256k1_instruction(&instructions, 0, secp_signature_count)
+    {
+        tracing::error!("Invalid secp256k1 instruction for - {feed_pubkey}\n{app_error:#?}");
+        return None;
+    }
+
+    let lookup_tables = match app_client
+        .load_lookup_tables(app_client.lookup_tables())
         .await
     {
         Err(app_error) => {
-            tracing::error!("Failed to execute pull_feed_submit_consensus ix\n{app_error:#?}");
-            return;
+            tracing::error!("Failed to resolve lookup tables\n{app_error:#?}");
+            return None;
         }
-        Ok(tx) => tx,
+        Ok(lookup_tables) => lookup_tables,
     };
 
-    // let tx_url = construct_url(UrlType::SolscanTx(tx_signature.to_string()));
+    let units_consumed_and_signature =
+        match submit_instructions(app_client, &lookup_tables, &instructions, latest_blockhash, submit_mode)
+            .await
+        {
+            Err(app_error) => {
+                tracing::error!("Failed to execute {name} ix\n{app_error:#?}");
+                if let AppError::SimulationFailed { .. } | AppError::TransactionRejected(_) = app_error {
+                    log_submit_account_metas(&instructions);
+                }
+                return None;
+            }
+            Ok(units_consumed_and_signature) => units_consumed_and_signature,
+        };
+
+    tracing::info!("🎉🎉 Successfully executed {name} ix.");
 
-    tracing::info!("Simulation result: {sim:#?}");
-    tracing::info!("🎉🎉 Successfully executed pull_feed_submit_consensus ix.");
+    Some(units_consumed_and_signature)
 }
 
-pub async fn execute_pull_feed_submit_response(app_client: Arc<AppClient>) {
-    let feed_pubkey = Pubkey::from_str_const("6CyMpkE6kb1MkcxhNH5PM7wAPwm2Agu2P4Qa51nQgWfi");
+// cross_check: fetch consensus signatures from that many gateways concurrently and only submit
+// when their median values agree within the feed's max_variance.
+// recent_blockhash/recent_slot: reused instead of fetched when given, see resolve_blockhash_and_slot.
+//
+// Runs in a submit span carrying the feed, queue and gateway, with child spans per phase
+// (fetch_signatures, preflight, simulate). Any tracing layer sees them, e.g. tracing-opentelemetry
+// to export them to an OTLP collector.
+#[tracing::instrument(
+    name = "submit_consensus",
+    skip_all,
+    fields(feed = %feed_pubkey, queue = field::Empty, gateway = field::Empty)
+)]
+pub async fn execute_pull_feed_submit_consensus_response(
+    app_client: Arc<AppClient>,
+    feed_pubkey: Pubkey,
+    cross_check: Option<usize>,
+    recent_blockhash: Option<Hash>,
+    recent_slot: Option<u64>,
+    submit_mode: SubmitMode,
+) -> Option<SubmitOutcome> {
+    let FeedContext {
+        feed_data,
+        queue_pubkey,
+        gateways: queue_gateways,
+        gateway_stake_scores,
+        latest_blockhash,
+        recent_slot,
+    } = prepare_feed_context(&app_client, feed_pubkey, recent_blockhash, recent_slot).await?;
+    let feed_data = &feed_data;
+
+    let (price_signatures, gateway_index, gateways_tried) = if let Some(cross_check) = cross_check {
+        let price_signatures = match get_cross_checked_consensus_signatures(
+            feed_data,
+            app_client.crossbar(),
+            &queue_gateways,
+            latest_blockhash,
+            cross_check,
+            app_client.num_signatures_policy(),
+        )
+        .instrument(info_span!("fetch_signatures", cross_check))
+        .await
+        {
+            Err(app_error) => {
+                tracing::error!("Failed to cross check consensus_signatures\n{app_error:#?}");
+                return None;
+            }
+            Ok(consensus_response) => consensus_response,
+        };
+        // the cross checked response is the first gateway's
+        Span::current().record("gateway", queue_gateways[0].gateway_url());
+        (price_signatures, 0, cross_check)
+    } else {
+        let (price_signatures, gateway_index) = fetch_from_gateways(
+            &app_client,
+            &feed_pubkey,
+            &queue_gateways,
+            "consensus_signatures",
+            |gateway| {
+                get_consensus_signatures(
+                    feed_data,
+                    app_client.crossbar(),
+                    gateway,
+                    latest_blockhash,
+                    app_client.num_signatures_policy(),
+                )
+            },
+        )
+        .await?;
+        tracing::info!(
+            "get_consensus_signatures() from {} with latest_blockhash: {latest_blockhash} => {}",
+            queue_gateways[gateway_index].gateway_url(),
+            app_client.log_value(&price_signatures)
+        );
+        (price_signatures, gateway_index, gateway_index + 1)
+    };
+
+    let oracle_timestamps = extract_oracle_timestamps(&price_signatures);
+    tracing::info!("Oracle response timestamps => {oracle_timestamps:#?}");
+
+    let oracles = oracle_timestamps
+        .iter()
+        .map(|(oracle_pubkey, _)| *oracle_pubkey)
+        .collect::<Vec<_>>();
+    let values = price_signatures
+        .median_responses
+        .iter()
+        .map(|median_response| {
+            median_response
+                .value
+                .parse::<i128>()
+                .ok()
+                .and_then(|value| Decimal::try_from_i128_with_scale(value, VALUE_SCALE).ok())
+        })
+        .collect::<Vec<_>>();
 
-    let feed_account = match app_client.get_account(&feed_pubkey).await {
+    if values.iter().any(Option::is_none) {
+        tracing::warn!(
+            "Consensus returned unparsable or out of range median values for - {feed_pubkey}, falling back to per-oracle submission"
+        );
+        return execute_pull_feed_submit_response(
+            app_client,
+            feed_pubkey,
+            Some(latest_blockhash),
+            Some(recent_slot),
+            submit_mode,
+        )
+        .await;
+    }
+
+    if let Some(threshold) = app_client.value_change_threshold()
+        && let Some(current_value) = feed_data.result.value()
+        && let Some(Some(new_value)) = values.first()
+        && !exceeds_value_change_threshold(current_value, *new_value, threshold)
+    {
+        tracing::info!(
+            "Skipping submission for - {feed_pubkey}, {new_value} is within {threshold} of the current value {current_value}"
+        );
+        return Some(SubmitOutcome::NoChangeNeeded {
+            feed: feed_pubkey,
+            current_value,
+            new_value: *new_value,
+        });
+    }
+
+    let params = SolanaSubmitSignaturesParams {
+        feed: feed_pubkey,
+        payer: app_client.keypair_pubkey(),
+        queue: queue_pubkey,
+    };
+    // the secp256k1 ix opens the transaction, the priority fee ix is appended after the submit ix
+    let expected_checksum = app_client.strict_checksums().then_some(ExpectedChecksum {
+        feed_data,
+        signed_hash: latest_blockhash,
+    });
+    let secp_signature_count = price_signatures.oracle_responses.len();
+    let instructions = match get_update_consensus_ix(
+        params,
+        price_signatures,
+        recent_slot,
+        0,
+        expected_checksum,
+        app_client.secp_message_format(),
+        app_client.cluster(),
+    ) {
         Err(app_error) => {
-            tracing::error!(
-                "Failed to retrieve PullFeedAccountData for - {feed_pubkey}\n{app_error:#?}"
-            );
-            return;
+            tracing::error!("Failed to construct pull_feed_submit_consensus ix\n{app_error:#?}");
+            return None;
         }
-        Ok(account) => account,
+        Ok(ixs) => ixs,
     };
 
-    let mut mut_account_data = feed_account.data.clone();
-    let swb_feed_data = RefCell::new(&mut mut_account_data[..]);
-    let swb_feed_data = swb_feed_data.borrow();
-    let pull_feed_account_data = match parse_swb_ignore_alignment(swb_feed_data) {
-        Err(on_demand_error) => {
-            tracing::error!(
-                "Failed to parse swb_feed_data to PullFeedAccountData for SWB-on-Demand - {feed_pubkey}\n{on_demand_error:#?}"
-            );
-            return;
+    let (units_consumed, signature) = submit_feed_instructions(
+        &app_client,
+        &feed_pubkey,
+        "pull_feed_submit_consensus",
+        instructions,
+        Some(secp_signature_count),
+        latest_blockhash,
+        submit_mode,
+    )
+    .await?;
+
+    // let tx_url = construct_url(UrlType::SolscanTx(tx_signature.to_string()));
+
+    Some(SubmitOutcome::Submitted(Box::new(SubmitReport {
+        feed: feed_pubkey,
+        queue: queue_pubkey,
+        gateway: queue_gateways[gateway_index].gateway_url().to_string(),
+        gateway_stake_score: gateway_stake_scores.map(|stake_scores| stake_scores[gateway_index]),
+        gateways_tried,
+        oracles,
+        values,
+        recent_slot,
+        blockhash: latest_blockhash,
+        units_consumed,
+        signature,
+    })))
+}
+
+// Same as execute_pull_feed_submit_consensus_response for a feed only known by its crossbar
+// feed_hash (hex, optionally 0x prefixed).
+pub async fn execute_pull_feed_submit_consensus_response_by_hash(
+    app_client: Arc<AppClient>,
+    feed_hash: &str,
+    cross_check: Option<usize>,
+    recent_blockhash: Option<Hash>,
+    recent_slot: Option<u64>,
+    submit_mode: SubmitMode,
+) -> Option<SubmitOutcome> {
+    let feed_hash_bytes = match hex::decode(feed_hash.trim_start_matches("0x"))
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+    {
+        None => {
+            tracing::error!("Invalid feed_hash, expected 32 hex encoded bytes - {feed_hash}");
+            return None;
         }
-        Ok(pull_feed_account_data) => pull_feed_account_data,
+        Some(feed_hash_bytes) => feed_hash_bytes,
     };
 
-    // pull_feed_account_data.f
+    let feed_pubkey = match app_client.find_feed_by_hash(&feed_hash_bytes).await {
+        Err(app_error) => {
+            tracing::error!("Failed to find a PullFeed for feed_hash - {feed_hash}\n{app_error:#?}");
+            return None;
+        }
+        Ok(feed_pubkey) => feed_pubkey,
+    };
 
-    let feed_data = &pull_feed_account_data;
+    tracing::info!("Resolved feed_hash {feed_hash} to - {feed_pubkey}");
+
+    execute_pull_feed_submit_consensus_response(
+        app_client,
+        feed_pubkey,
+        cross_check,
+        recent_blockhash,
+        recent_slot,
+        submit_mode,
+    )
+    .await
+}
 
+// Spans as execute_pull_feed_submit_consensus_response.
+#[tracing::instrument(
+    name = "submit",
+    skip_all,
+    fields(feed = %feed_pubkey, queue = field::Empty, gateway = field::Empty)
+)]
+pub async fn execute_pull_feed_submit_response(
+    app_client: Arc<AppClient>,
+    feed_pubkey: Pubkey,
+    recent_blockhash: Option<Hash>,
+    recent_slot: Option<u64>,
+    submit_mode: SubmitMode,
+) -> Option<SubmitOutcome> {
+    let FeedContext {
+        feed_data,
+        queue_pubkey,
+        gateways: queue_gateways,
+        gateway_stake_scores,
+        latest_blockhash,
+        recent_slot,
+    } = prepare_feed_context(&app_client, feed_pubkey, recent_blockhash, recent_slot).await?;
+    let feed_data = &feed_data;
+
+    let (oracle_responses, gateway_index) = fetch_from_gateways(
+        &app_client,
+        &feed_pubkey,
+        &queue_gateways,
+        "oracle_submissions",
+        |gateway| get_oracle_submissions(feed_data, app_client.crossbar(), gateway, latest_blockhash),
+    )
+    .await?;
     tracing::info!(
-        "Successfully deserialized - {feed_pubkey} to PullFeedAccountData - {pull_feed_account_data:#?}"
+        "Retrieved oracle_responses for - feed_pubkey: {feed_pubkey}\n{}",
+        app_client.log_value(&oracle_responses)
     );
 
-    let queue_account_data =
-        match QueueAccountData::load(app_client.rpc_client(), &SWITCHBOARD_ACCOUNT_QUEUE).await {
-            Err(error) => {
-                tracing::error!(
-                    "Failed to retrieve QueueAccountData - {SWITCHBOARD_ACCOUNT_QUEUE}\n{error:#?}"
-                );
-                return;
-            }
-            Ok(data) => data,
-        };
-
-    let queue_oracle_keys = queue_account_data.oracle_keys();
+    let (oracles, values) = oracle_responses
+        .iter()
+        .map(|oracle_response| (oracle_response.oracle, oracle_response.value))
+        .unzip::<_, _, Vec<_>, Vec<_>>();
 
-    let oracle_accounts = match app_client
-        .get_multiple_accounts(&queue_oracle_keys, None)
-        .await
-    {
+    let params = SolanaSubmitSignaturesParams {
+        queue: queue_pubkey,
+        feed: feed_pubkey,
+        payer: app_client.keypair_pubkey(),
+    };
+    let pull_feed_submit_response_ix = match get_solana_submit_signatures_ix(
+        recent_slot,
+        oracle_responses,
+        params,
+        app_client.missing_value_policy(),
+        None,
+        app_client.cluster(),
+    ) {
         Err(app_error) => {
-            tracing::error!(
-                "Failed to get multiple accounts - {queue_oracle_keys:#?}\n{app_error:#?}"
-            );
-            return;
+            tracing::error!("Failed to construct pull_feed_submit ix\n{app_error:#?}");
+            return None;
         }
-        Ok(accounts) => accounts,
+        Ok(ix) => ix,
     };
 
-    // gather all the gateway uris the retrieved oracle_accounts contain
-    let queue_gateways = oracle_accounts
-        .iter()
-        .zip(queue_oracle_keys)
-        .into_iter()
-        .filter_map(|(account, oracle_pubkey)| {
-            let Some(oracle_account) = account else {
-                tracing::warn!("getMultipleAccounts returned None for - oracle_pubkey: {oracle_pubkey}");
-                return None;
-            };
-
-            let bytes_data = &oracle_account.data[8..];
-            let oracle_account_data: &OracleAccountData =
-                bytemuck::try_from_bytes(bytes_data).unwrap();
+    let (units_consumed, signature) = submit_feed_instructions(
+        &app_client,
+        &feed_pubkey,
+        "pull_feed_submit",
+        vec![pull_feed_submit_response_ix],
+        None,
+        latest_blockhash,
+        submit_mode,
+    )
+    .await?;
+
+    Some(SubmitOutcome::Submitted(Box::new(SubmitReport {
+        feed: feed_pubkey,
+        queue: queue_pubkey,
+        gateway: queue_gateways[gateway_index].gateway_url().to_string(),
+        gateway_stake_score: gateway_stake_scores.map(|stake_scores| stake_scores[gateway_index]),
+        gateways_tried: gateway_index + 1,
+        oracles,
+        values,
+        recent_slot,
+        blockhash: latest_blockhash,
+        units_consumed,
+        signature,
+    })))
+}
 
-            let gateway_uri = oracle_account_data.gateway_uri();
-            tracing::info!("Successfully deserialized - {oracle_pubkey}\n{oracle_account_data:#?} with gateway - {gateway_uri:#?}");
+// Submits every feed with one shared blockhash and slot, recording which feeds failed so a rerun
+// only has to retry those (see resume_pull_feed_submit_batch).
+pub async fn execute_pull_feed_submit_batch(
+    app_client: Arc<AppClient>,
+    feeds: &[Pubkey],
+    cross_check: Option<usize>,
+    submit_mode: SubmitMode,
+) -> Option<BatchSubmitReport> {
+    let (latest_blockhash, recent_slot) = match app_client.get_submission_blockhash_and_slot().await {
+        Err(app_error) => {
+            tracing::error!("Failed to retrieve latest blockhash and current slot\n{app_error:#?}");
+            return None;
+        }
+        Ok(blockhash_and_slot) => blockhash_and_slot,
+    };
 
-            let Some(gateway_uri) = gateway_uri else {
-                return None;
-            };
+    // unreadable feeds are still submitted, they fail (and are reported) like any other
+    let feeds_by_queue = match app_client.group_feeds_by_queue(feeds).await {
+        Err(app_error) => {
+            tracing::warn!("Failed to group feeds by queue, submitting them as one group\n{app_error:#?}");
+            HashMap::from([(None, feeds.to_vec())])
+        }
+        Ok(feeds_by_queue) => feeds_by_queue,
+    };
 
-            Some(Gateway::new(gateway_uri))
-        })
-        .collect::<Vec<_>>();
+    let mut report = BatchSubmitReport {
+        recent_slot,
+        outcomes: Vec::new(),
+        failed: Vec::new(),
+    };
 
-    tracing::info!("Constructed queue_gateways => {queue_gateways:#?}");
+    for (queue_pubkey, queue_feeds) in feeds_by_queue {
+        // loads the queue's oracles and gateways into the account cache once for the whole group
+        if let Some(queue_pubkey) = queue_pubkey
+            && let Err(app_error) = app_client.preload_queue(&queue_pubkey).await
+        {
+            tracing::warn!("Failed to preload queue - {queue_pubkey}\n{app_error:#?}");
+        }
 
-    let mut retry = 0;
-    let max_retry = queue_gateways.len();
+        let outcomes = futures::future::join_all(queue_feeds.iter().map(|feed_pubkey| {
+            execute_pull_feed_submit_consensus_response(
+                app_client.clone(),
+                *feed_pubkey,
+                cross_check,
+                Some(latest_blockhash),
+                Some(recent_slot),
+                submit_mode,
+            )
+        }))
+        .await;
+
+        for (feed_pubkey, outcome) in queue_feeds.iter().zip(outcomes) {
+            match outcome {
+                None => report.failed.push(*feed_pubkey),
+                Some(outcome) => report.outcomes.push(outcome),
+            }
+        }
+    }
 
-    let (latest_blockhash_result, recent_slot_result) =
-        tokio::join!(app_client.get_latest_blockhash(), app_client.get_slot());
+    tracing::info!(
+        "Batch submitted {}/{} feeds, failed - {:?}",
+        report.outcomes.len(),
+        feeds.len(),
+        report.failed
+    );
 
-    let latest_blockhash = match latest_blockhash_result {
-        Err(app_error) => {
-            tracing::error!("Failed to retrieve latest blockhash\n{app_error:#?}");
-            return;
-        }
-        Ok(blockhash) => blockhash,
-    };
+    Some(report)
+}
 
-    let recent_slot = match recent_slot_result {
+// Retries the failed feeds of a previous batch. Feeds that got a result at or after the previous
+// batch's slot in the meantime, e.g. a submission that landed after being reported failed or
+// another keeper's update, are skipped rather than paid for twice.
+pub async fn resume_pull_feed_submit_batch(
+    app_client: Arc<AppClient>,
+    previous_report: &BatchSubmitReport,
+    cross_check: Option<usize>,
+    submit_mode: SubmitMode,
+) -> Option<BatchSubmitReport> {
+    let feed_infos = match app_client.read_feeds(&previous_report.failed, None).await {
         Err(app_error) => {
-            tracing::error!("Failed to retrieve current slot\n{app_error:#?}");
-            return;
+            tracing::error!("Failed to read the failed feeds of the previous batch\n{app_error:#?}");
+            return None;
         }
-        Ok(slot) => slot,
+        Ok(feed_infos) => feed_infos,
     };
 
-    let oracle_responses;
-
-    loop {
-        let gateway = &queue_gateways[retry + 8];
+    let remaining_feeds = previous_report
+        .failed
+        .iter()
+        .filter(|feed_pubkey| {
+            let updated = feed_infos
+                .get(feed_pubkey)
+                .and_then(|feed_info| feed_info.result_slot)
+                .is_some_and(|result_slot| result_slot >= previous_report.recent_slot);
+
+            if updated {
+                tracing::info!("Skipping feed already updated since the previous batch - {feed_pubkey}");
+            }
 
-        tracing::info!("#{retry} attempt using - {gateway:#?}");
+            !updated
+        })
+        .copied()
+        .collect::<Vec<_>>();
 
-        match get_oracle_submissions(feed_data, gateway, latest_blockhash).await {
-            Err(app_error) => {
-                tracing::warn!("Failed to retrieve oracle_submissions\n{app_error:#?}");
+    execute_pull_feed_submit_batch(app_client, &remaining_feeds, cross_check, submit_mode).await
+}
 
-                retry += 1;
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicUsize, Ordering},
+        },
+    };
 
-                if retry < max_retry {
-                    tracing::warn!(
-                        "Retrying to get oracle submissions after {retry}/{max_retry} tries",
-                    );
-                    continue;
-                }
-                tracing::error!("Failed to retrieve oracle_submissions\n{app_error:#?}.");
+    use serde_json::json;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::account::Account;
+
+    use super::*;
+    use crate::{
+        app::mock_rpc::{
+            MockRpc, accounts_response, feed_account, oracle_account, queue_account, rpc_error,
+            rpc_response, serve_http,
+        },
+        utils::Cluster,
+    };
 
-                return;
-            }
-            Ok(response) => {
-                tracing::info!(
-                    "Retrieved oracle_responses for - feed_pubkey: {feed_pubkey}\n{response:#?}"
-                );
-                oracle_responses = response;
-                break;
-            }
-        };
+    // A feed of queue holding 100, last updated at result_slot.
+    fn feed_of_100(queue: Pubkey, result_slot: u64) -> Account {
+        feed_account(Cluster::default().program_id(), |feed_data| {
+            feed_data.queue = queue;
+            feed_data.min_responses = 1;
+            feed_data.min_sample_size = 1;
+            feed_data.result.value = 100_000_000_000_000_000_000;
+            feed_data.result.slot = result_slot;
+        })
     }
 
-    let params = SolanaSubmitSignaturesParams {
-        queue: SWITCHBOARD_ACCOUNT_QUEUE,
-        feed: feed_pubkey,
-        payer: app_client.keypair_pubkey(),
-    };
-    let pull_feed_submit_response_ix =
-        get_solana_submit_signatures_ix(recent_slot, oracle_responses, params);
+    // Crossbar and a gateway reaching a consensus of 100.5 for every feed. Returns their urls and
+    // the number of consensus requests the gateway served.
+    async fn serve_consensus_of_100_5() -> (String, String, Arc<AtomicUsize>) {
+        let jobs = json!([{ "tasks": [{ "valueTask": { "value": 100.5 } }] }]);
+        let (crossbar_url, _) = serve_http("200 OK", json!({ "jobs": jobs }).to_string()).await;
+        let gateway_response = json!({
+            "median_responses": [{ "value": "100500000000000000000", "feed_hash": "00" }],
+            "oracle_responses": [],
+        });
+        let (gateway_url, gateway_requests) = serve_http("200 OK", gateway_response.to_string()).await;
+
+        (crossbar_url, gateway_url, gateway_requests)
+    }
 
-    let sim = match app_client
-        .call_instructions(
-            None,
-            &[pull_feed_submit_response_ix],
-            //[instructions[0].clone()],
-            latest_blockhash,
+    #[tokio::test]
+    async fn value_within_the_threshold_is_not_submitted() {
+        let (feed, queue, oracle) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (crossbar_url, gateway_url, gateway_requests) = serve_consensus_of_100_5().await;
+        let mock = MockRpc::with_accounts(
+            10,
+            HashMap::from([
+                (feed, feed_of_100(queue, 1)),
+                (queue, queue_account(&[oracle])),
+                (oracle, oracle_account(&gateway_url)),
+            ]),
+        );
+        let app_client = mock
+            .builder()
+            .crossbar_urls(vec![crossbar_url])
+            .value_change_threshold(Some(Decimal::new(1, 2)))
+            .build()
+            .unwrap();
+
+        // 100 -> 100.5 moved 0.5%, below the 1% threshold
+        let outcome = execute_pull_feed_submit_consensus_response(
+            Arc::new(app_client),
+            feed,
             None,
+            Some(Hash::new_unique()),
+            Some(10),
+            SubmitMode::Simulate,
         )
-        .await
-    {
-        Err(app_error) => {
-            tracing::error!("Failed to execute pull_feed_submit ix\n{app_error:#?}");
-            return;
-        }
-        Ok(tx) => tx,
-    };
-    tracing::info!("Simulation result: {sim:#?}");
+        .await;
 
+        assert!(
+            matches!(outcome, Some(SubmitOutcome::NoChangeNeeded { new_value, .. }) if new_value == Decimal::new(1005, 1)),
+            "{outcome:?}"
+        );
+        assert_eq!(gateway_requests.load(Ordering::SeqCst), 1);
+        assert_eq!(mock.count(RpcRequest::SimulateTransaction), 0);
+    }
 
-    tracing::info!("🎉🎉 Successfully executed pull_feed_submit ix.");
+    #[tokio::test]
+    async fn resumed_batch_only_retries_the_remaining_failures() {
+        let (queue, oracle) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let feeds = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let (crossbar_url, gateway_url, gateway_requests) = serve_consensus_of_100_5().await;
+
+        // the last two feeds can't be read during the first batch
+        let accounts = Arc::new(Mutex::new(HashMap::from([
+            (feeds[0], feed_of_100(queue, 1)),
+            (queue, queue_account(&[oracle])),
+            (oracle, oracle_account(&gateway_url)),
+        ])));
+        let mock = MockRpc::new({
+            let accounts = accounts.clone();
+            move |request, params| match request {
+                RpcRequest::GetLatestBlockhash => Ok(rpc_response(
+                    10,
+                    json!({ "blockhash": Hash::new_unique().to_string(), "lastValidBlockHeight": 100 }),
+                )),
+                RpcRequest::GetSlot => Ok(json!(10)),
+                _ => accounts_response(10, request, params, &accounts.lock().unwrap())
+                    .ok_or_else(|| rpc_error(&format!("unexpected {request}"))),
+            }
+        });
+        let app_client = Arc::new(
+            mock.builder()
+                .crossbar_urls(vec![crossbar_url])
+                .value_change_threshold(Some(Decimal::new(1, 2)))
+                .build()
+                .unwrap(),
+        );
+
+        let report = execute_pull_feed_submit_batch(app_client.clone(), &feeds, None, SubmitMode::Simulate)
+            .await
+            .unwrap();
+        assert_eq!(report.outcomes.len(), 1);
+        assert_eq!(report.failed, feeds[1..]);
+        assert_eq!(gateway_requests.load(Ordering::SeqCst), 1);
+
+        // the second feed's submission landed after all, the third is still at its old result
+        accounts.lock().unwrap().extend([(feeds[1], feed_of_100(queue, 10)), (feeds[2], feed_of_100(queue, 1))]);
+
+        let resumed_report = resume_pull_feed_submit_batch(app_client, &report, None, SubmitMode::Simulate)
+            .await
+            .unwrap();
+        assert!(resumed_report.failed.is_empty());
+        assert!(matches!(
+            resumed_report.outcomes[..],
+            [SubmitOutcome::NoChangeNeeded { feed, .. }] if feed == feeds[2]
+        ));
+        assert_eq!(gateway_requests.load(Ordering::SeqCst), 2);
+    }
 }
@@ -1,24 +1,70 @@
-use std::cell::Ref;
+use std::{
+    fmt::{self, Debug, Display},
+    path::Path,
+    time::Duration,
+};
 
 use anchor_lang::prelude::AccountMeta;
 use base64::{Engine, engine::general_purpose::STANDARD as base64};
+use rand::Rng;
 use rust_decimal::Decimal;
-use solana_sdk::{hash::Hash, instruction::Instruction, pubkey::Pubkey};
+use serde::{Deserialize, Serialize};
+use solana_client::{
+    rpc_filter::{Memcmp, RpcFilterType},
+    rpc_response::RpcSimulateTransactionResult,
+};
+use solana_sdk::{
+    bs58,
+    hash::{Hash, hashv},
+    instruction::Instruction,
+    keccak,
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    secp256k1_program,
+    secp256k1_recover::secp256k1_recover,
+    signature::{EncodableKey, Keypair},
+    transaction::VersionedTransaction,
+};
 use solana_system_interface::program;
 use spl_associated_token_account::get_associated_token_address;
 use switchboard_on_demand::{
-    Discriminator, ON_DEMAND_MAINNET_PID, OracleAccountData, PullFeedAccountData, State,
+    Discriminator, ON_DEMAND_DEVNET_PID, ON_DEMAND_MAINNET_PID, OracleAccountData,
+    OracleStatsAccountData, PRECISION, PullFeedAccountData, QUEUE_ACCOUNT_DISCRIMINATOR, State,
 };
 use switchboard_on_demand_client::{
-    CrossbarClient, FeedConfig, FetchSignaturesConsensusParams, FetchSignaturesConsensusResponse,
-    FetchSignaturesParams, Gateway, NATIVE_MINT, OracleResponse, PullFeedSubmitResponse,
+    FeedConfig, FetchSignaturesConsensusParams, FetchSignaturesConsensusResponse,
+    FetchSignaturesParams, NATIVE_MINT, OracleResponse, PullFeedSubmitResponse,
     PullFeedSubmitResponseConsensus, PullFeedSubmitResponseConsensusParams,
-    PullFeedSubmitResponseParams, SolanaSubmitSignaturesParams, Submission, encode_jobs,
+    PullFeedSubmitResponseParams, QueueAccountData, SolanaSubmitSignaturesParams, Submission,
+    encode_jobs, get_discriminator,
     oracle_job::OracleJob,
+    prost::Message,
     secp256k1::{Secp256k1InstructionUtils, SecpSignature},
 };
 
-use crate::app::{AppError, AppResult};
+use crate::{
+    app::{AppError, AppResult},
+    crossbar::Crossbar,
+    gateway::GatewayClient,
+};
+
+// decimals of every value the on-demand program stores or is submitted, as an i128 mantissa
+pub const VALUE_SCALE: u32 = PRECISION;
+
+// i128 mantissa of the value at VALUE_SCALE. Decimal only holds 96 bit mantissas, so large values
+// can't be rescaled all the way: the remaining powers of ten are applied on the i128, erroring
+// instead of wrapping when the result doesn't fit.
+pub fn scale_value(value: Decimal) -> AppResult<i128> {
+    let mut scaled = value;
+    scaled.rescale(VALUE_SCALE);
+
+    10i128
+        .checked_pow(VALUE_SCALE - scaled.scale())
+        .and_then(|factor| scaled.mantissa().checked_mul(factor))
+        .ok_or_else(|| {
+            AppError::ParsingError(format!("value overflows i128 after scaling - {value}"))
+        })
+}
 
 fn build_oracle_accounts(oracles: &[Pubkey]) -> Vec<AccountMeta> {
     oracles
@@ -32,50 +78,359 @@ fn build_oracle_accounts(oracles: &[Pubkey]) -> Vec<AccountMeta> {
         .collect()
 }
 
+// How get_solana_submit_signatures_ix encodes an oracle response that carries no value.
+//
+// The on-demand program has no notion of a missing value: every submission is verified against
+// the oracle's secp256k1 signature and then treated as a real sample, so a sentinel either fails
+// verification or lands as a price sample. A missing value is therefore never encoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingValuePolicy {
+    // leave the oracle (and its accounts) out of the submission
+    #[default]
+    Drop,
+    // fail building the instruction
+    Error,
+}
+
+// Number of oracle signatures requested from the consensus endpoint.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NumSignaturesPolicy {
+    Fixed(u32),
+    // required_signatures of the feed, as the per-oracle path requests
+    #[default]
+    FromFeed,
+}
+
+impl NumSignaturesPolicy {
+    pub fn num_signatures(self, feed_data: &PullFeedAccountData) -> u32 {
+        match self {
+            NumSignaturesPolicy::Fixed(num_signatures) => num_signatures,
+            NumSignaturesPolicy::FromFeed => required_signatures(feed_data),
+        }
+    }
+}
+
+// Solana cluster the on-demand program is deployed to, selecting the program id instructions are
+// built for and accounts are checked against. Custom covers localnet and forked deployments.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Cluster {
+    #[default]
+    Mainnet,
+    Devnet,
+    Custom(Pubkey),
+}
+
+impl Cluster {
+    pub fn program_id(self) -> Pubkey {
+        match self {
+            Cluster::Mainnet => ON_DEMAND_MAINNET_PID,
+            Cluster::Devnet => ON_DEMAND_DEVNET_PID,
+            Cluster::Custom(program_id) => program_id,
+        }
+    }
+
+    // program State PDA, State::get_pda always derives it from the mainnet program id
+    pub fn program_state(self) -> Pubkey {
+        State::get_program_pda(Some(self.program_id()))
+    }
+}
+
+// The feed's min_sample_size plus a third of it (rounded up) as margin for failing oracles, e.g.
+// 1 -> 2, 3 -> 4 and 7 -> 10.
+pub fn required_signatures(feed_data: &PullFeedAccountData) -> u32 {
+    let min_sample_size = u32::from(feed_data.min_sample_size);
+    min_sample_size.saturating_add(min_sample_size.div_ceil(3))
+}
+
+// How a submission's recent_slot is picked relative to the feed's last update. The program rejects
+// a second update within the same slot, so a recent_slot at or before the last update is bound to
+// fail with "already updated this slot".
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecentSlotStrategy {
+    // the latest processed slot as is
+    Latest,
+    // refetch the slot (one per retry_delay) until it is past the feed's last update, erroring
+    // with SlotNotAdvanced after max_refetches
+    AfterLastUpdate { max_refetches: u32 },
+}
+
+impl Default for RecentSlotStrategy {
+    fn default() -> Self {
+        // a slot is ~400ms, a handful of refetches covers the next slot or two
+        RecentSlotStrategy::AfterLastUpdate { max_refetches: 5 }
+    }
+}
+
+impl RecentSlotStrategy {
+    // Whether recent_slot may be submitted for a feed last updated at last_update_slot (None when
+    // never updated).
+    pub fn accepts(self, recent_slot: u64, last_update_slot: Option<u64>) -> bool {
+        match self {
+            RecentSlotStrategy::Latest => true,
+            RecentSlotStrategy::AfterLastUpdate { .. } => {
+                last_update_slot.is_none_or(|last_update_slot| recent_slot > last_update_slot)
+            }
+        }
+    }
+}
+
+// Message the oracles' secp256k1 signatures are over. The secp256k1 program keccak256-hashes the
+// message exactly as it appears in the instruction, so a prefixed signing convention has to be
+// reflected in the message itself. The on-demand program still compares that message with the
+// checksum it computes, so anything but Raw only lands once the program expects it too.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecpMessageFormat {
+    // the 32 byte checksum as is, what oracles currently sign
+    #[default]
+    Raw,
+    // EIP-191 personal_sign: "\x19Ethereum Signed Message:\n" + message length + checksum
+    PersonalSign,
+}
+
+impl SecpMessageFormat {
+    pub fn prepare_message(self, checksum: &[u8]) -> Vec<u8> {
+        match self {
+            SecpMessageFormat::Raw => checksum.to_vec(),
+            SecpMessageFormat::PersonalSign => {
+                let mut message =
+                    format!("\x19Ethereum Signed Message:\n{}", checksum.len()).into_bytes();
+                message.extend_from_slice(checksum);
+                message
+            }
+        }
+    }
+}
+
+// Recovers the signer of the secp256k1 signature the way the secp256k1 program does and makes sure
+// it is the claimed eth_address, catching a wrong message format before the transaction fails.
+pub fn verify_secp_signature(secp_signature: &SecpSignature) -> AppResult<()> {
+    let message_hash = keccak::hash(&secp_signature.message);
+
+    let signer = secp256k1_recover(
+        message_hash.as_ref(),
+        secp_signature.recovery_id,
+        &secp_signature.signature,
+    )
+    .map_err(|e| AppError::SignatureVerificationFailed(format!("Failed to recover signer: {e}")))?;
+
+    // eth address, last 20 bytes of the keccak256 hash of the uncompressed pubkey
+    let signer_address = &keccak::hash(&signer.to_bytes()).to_bytes()[12..];
+
+    if signer_address != secp_signature.eth_address {
+        return Err(AppError::SignatureVerificationFailed(format!(
+            "recovered {}, expected {}",
+            hex::encode(signer_address),
+            hex::encode(secp_signature.eth_address)
+        )));
+    }
+
+    Ok(())
+}
+
+// verify_secp_signature for each signature, naming the position of the first one that fails.
+pub fn verify_secp_signatures(secp_signatures: &[SecpSignature]) -> AppResult<()> {
+    for (index, secp_signature) in secp_signatures.iter().enumerate() {
+        if let Err(AppError::SignatureVerificationFailed(error)) = verify_secp_signature(secp_signature) {
+            return Err(AppError::SignatureVerificationFailed(format!("signature {index}: {error}")));
+        }
+    }
+
+    Ok(())
+}
+
+// size of one SecpSignatureOffsets entry following the signature count
+const SECP_SIGNATURE_OFFSETS_SIZE: usize = 11;
+// 64 byte signature followed by its recovery id
+const SECP_SIGNATURE_SIZE: usize = 65;
+const ETH_ADDRESS_SIZE: usize = 20;
+
+// Checks the secp256k1 instruction at secp_instruction_index of the transaction's instructions the
+// way the precompile will: every offset entry must point within the instruction it references and
+// every signature must recover to its eth address. A secp instruction that doesn't land at the
+// index it was built for reads the wrong data and only fails on-chain as an invalid signature,
+// this reports which entry is off before sending.
+pub fn validate_secp256k1_instruction(
+    instructions: &[Instruction],
+    secp_instruction_index: usize,
+    expected_signatures: usize,
+) -> AppResult<()> {
+    let invalid = |reason: String| AppError::InvalidSecpInstruction(reason);
+
+    let secp_ix = instructions
+        .get(secp_instruction_index)
+        .filter(|instruction| instruction.program_id == secp256k1_program::ID)
+        .ok_or_else(|| invalid(format!("No secp256k1 instruction at index {secp_instruction_index}")))?;
+
+    let Some((&signature_count, offsets_data)) = secp_ix.data.split_first() else {
+        return Err(invalid("Empty instruction data".to_string()));
+    };
+
+    let signature_count = usize::from(signature_count);
+    if signature_count != expected_signatures {
+        return Err(invalid(format!(
+            "{signature_count} signatures, expected {expected_signatures}"
+        )));
+    }
+
+    if offsets_data.len() < signature_count * SECP_SIGNATURE_OFFSETS_SIZE {
+        return Err(invalid(format!(
+            "{} bytes of offsets, {signature_count} signatures need {}",
+            offsets_data.len(),
+            signature_count * SECP_SIGNATURE_OFFSETS_SIZE
+        )));
+    }
+
+    // bytes [offset, offset + size) of the instruction at instruction_index
+    let read = |entry: usize, name: &str, instruction_index: u8, offset: u16, size: usize| {
+        let data = &instructions
+            .get(usize::from(instruction_index))
+            .ok_or_else(|| {
+                invalid(format!(
+                    "Entry {entry}: {name} instruction index {instruction_index} is out of bounds ({} instructions)",
+                    instructions.len()
+                ))
+            })?
+            .data;
+
+        let start = usize::from(offset);
+        data.get(start..start + size).ok_or_else(|| {
+            invalid(format!(
+                "Entry {entry}: {name} [{start}, {}) is out of bounds of instruction {instruction_index} ({} bytes)",
+                start + size,
+                data.len()
+            ))
+        })
+    };
+
+    for (entry, offsets) in offsets_data
+        .chunks_exact(SECP_SIGNATURE_OFFSETS_SIZE)
+        .take(signature_count)
+        .enumerate()
+    {
+        let u16_at = |index: usize| u16::from_le_bytes([offsets[index], offsets[index + 1]]);
+
+        let signature = read(entry, "signature", offsets[2], u16_at(0), SECP_SIGNATURE_SIZE)?;
+        let eth_address = read(entry, "eth_address", offsets[5], u16_at(3), ETH_ADDRESS_SIZE)?;
+        let message = read(
+            entry,
+            "message",
+            offsets[10],
+            u16_at(6),
+            usize::from(u16_at(8)),
+        )?;
+
+        let signer = secp256k1_recover(
+            keccak::hash(message).as_ref(),
+            signature[64],
+            &signature[..64],
+        )
+        .map_err(|e| invalid(format!("Entry {entry}: failed to recover signer: {e}")))?;
+
+        let signer_address = &keccak::hash(&signer.to_bytes()).to_bytes()[12..];
+        if signer_address != eth_address {
+            return Err(invalid(format!(
+                "Entry {entry}: signature recovers to {}, expected {}",
+                hex::encode(signer_address),
+                hex::encode(eth_address)
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+// The on-demand program reads its accounts positionally, so colliding metas can't be merged without
+// shifting every account after them (Message compilation already merges the key and its
+// privileges). A collision means a misconfiguration, e.g. the payer set to the queue, or a gateway
+// returning the same oracle twice, and is reported before it fails on-chain.
+pub fn ensure_unique_account_metas(accounts: &[AccountMeta]) -> AppResult<()> {
+    let mut seen = std::collections::HashSet::with_capacity(accounts.len());
+
+    match accounts.iter().find(|account| !seen.insert(account.pubkey)) {
+        Some(account) => Err(AppError::DuplicateAccount(account.pubkey)),
+        None => Ok(()),
+    }
+}
+
+// slot_offsets, when given, holds one entry per response. An oracle that couldn't produce a
+// response at the requested `slot` may answer with an older signed value, the offset is the number
+// of slots before `slot` that value was signed at. Defaults to 0 for every submission.
 pub fn get_solana_submit_signatures_ix(
     slot: u64,
     responses: Vec<OracleResponse>,
     params: SolanaSubmitSignaturesParams,
-) -> Instruction {
+    missing_value_policy: MissingValuePolicy,
+    slot_offsets: Option<&[u8]>,
+    cluster: Cluster,
+) -> AppResult<Instruction> {
+    if let Some(slot_offsets) = slot_offsets
+        && slot_offsets.len() != responses.len()
+    {
+        return Err(AppError::ParsingError(format!(
+            "Expected {} slot offsets, got {}",
+            responses.len(),
+            slot_offsets.len()
+        )));
+    }
+
     let mut remaining_accounts = Vec::new();
     let mut submissions = Vec::new();
+    let mut oracle_keys = Vec::new();
 
-    for OracleResponse {
-        recovery_id,
-        signature,
-        value,
-        ..
-    } in responses.clone().into_iter()
+    for (
+        index,
+        OracleResponse {
+            oracle,
+            recovery_id,
+            signature,
+            value,
+            ..
+        },
+    ) in responses.into_iter().enumerate()
     {
-        let mut value_i128 = i128::MAX;
-
-        if let Some(mut val) = value {
-            val.rescale(18);
-            value_i128 = val.mantissa();
-        }
+        let value_i128 = match (value, missing_value_policy) {
+            (Some(val), _) => scale_value(val)?,
+            (None, MissingValuePolicy::Drop) => {
+                tracing::warn!("Dropping submission without value for - oracle: {oracle}");
+                continue;
+            }
+            (None, MissingValuePolicy::Error) => {
+                return Err(AppError::ParsingError(format!(
+                    "Oracle {oracle} returned no value"
+                )));
+            }
+        };
 
         submissions.push(Submission {
             value: value_i128,
             signature,
             recovery_id,
-            offset: 0,
+            offset: slot_offsets.map_or(0, |slot_offsets| slot_offsets[index]),
         });
+        oracle_keys.push(oracle);
     }
 
-    let oracle_keys: Vec<Pubkey> = responses.iter().map(|resp| resp.oracle).collect();
     remaining_accounts.extend(build_oracle_accounts(&oracle_keys));
 
     // pull_feed_submit_response ix
     let mut submit_ix = Instruction {
-        program_id: ON_DEMAND_MAINNET_PID,
+        program_id: cluster.program_id(),
         data: PullFeedSubmitResponseParams { slot, submissions }.data(),
         accounts: PullFeedSubmitResponse {
             feed: params.feed,
             queue: params.queue,
-            program_state: State::get_pda(),
+            program_state: cluster.program_state(),
             recent_slothashes: solana_sdk::sysvar::slot_hashes::ID,
             payer: params.payer,
             system_program: program::ID,
+            // The on-demand program declares reward_vault, token_program and token_mint as fixed
+            // positional accounts of pull_feed_submit_response, with no flag to skip rewards.
+            // Leaving them out shifts the oracle remaining accounts into their slots and the
+            // instruction fails account validation, so they are always included, even for dry runs.
+            // There is no reward recipient account either: reward_vault is the queue's wrapped SOL
+            // vault the payer funds, and rewards accrue to the submitting oracles' stats accounts,
+            // so they can't be redirected to an account of the caller's choosing.
             reward_vault: get_associated_token_address(&params.queue, &NATIVE_MINT),
             token_program: spl_token::ID,
             token_mint: *NATIVE_MINT,
@@ -84,30 +439,241 @@ pub fn get_solana_submit_signatures_ix(
     };
 
     submit_ix.accounts.extend(remaining_accounts);
+    ensure_unique_account_metas(&submit_ix.accounts)?;
+
+    Ok(submit_ix)
+}
+
+pub const DEFAULT_GATEWAY_API_PATH: &str = "/gateway/api/v1";
+
+// Gateway uris are matched ignoring a trailing slash, oracles register them either way.
+pub fn normalize_gateway_uri(gateway_uri: &str) -> &str {
+    gateway_uri.trim().trim_end_matches('/')
+}
+
+// Base url of a gateway's api from the uri its oracle advertises, which may or may not already
+// include api_path.
+pub fn gateway_api_url(gateway_uri: &str, api_path: &str) -> String {
+    let gateway_uri = normalize_gateway_uri(gateway_uri);
+    let api_path = format!("/{}", api_path.trim_matches('/'));
+
+    if gateway_uri.ends_with(&api_path) {
+        gateway_uri.to_string()
+    } else {
+        format!("{gateway_uri}{api_path}")
+    }
+}
+
+// Wire (bincode) size of the transaction, the size that counts against the packet limit. Errors with
+// TransactionTooLarge when it wouldn't fit, the rpc would otherwise reject it with an opaque error.
+pub fn ensure_transaction_fits(transaction: &VersionedTransaction) -> AppResult<usize> {
+    let serialized_size = bincode::serialized_size(transaction)
+        .map_err(|error| AppError::ParsingError(format!("Failed to serialize transaction: {error}")))?
+        as usize;
+
+    if serialized_size > PACKET_DATA_SIZE {
+        return Err(AppError::TransactionTooLarge(serialized_size));
+    }
+
+    Ok(serialized_size)
+}
+
+// log lines explaining a failed simulation: anchor/program errors, panics and the failing
+// program's exit line
+const SIMULATION_ERROR_LOG_PATTERNS: [&str; 4] = ["Error", " failed", "panicked", "insufficient"];
+
+// Ok for a successful simulation, otherwise SimulationFailed carrying the log lines that explain
+// the failure, or every log line when none of them does.
+pub fn check_simulation_result(result: &RpcSimulateTransactionResult) -> AppResult<()> {
+    let Some(err) = result.err.clone() else {
+        return Ok(());
+    };
+
+    let logs = result.logs.clone().unwrap_or_default();
+    let error_logs = logs
+        .iter()
+        .filter(|line| {
+            SIMULATION_ERROR_LOG_PATTERNS
+                .iter()
+                .any(|pattern| line.contains(pattern))
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+
+    Err(AppError::SimulationFailed {
+        err,
+        logs: if error_logs.is_empty() { logs } else { error_logs },
+    })
+}
+
+// A keypair from a file path, a base58 encoded secret key or a JSON byte array (the solana CLI
+// keypair file format), so the key can also be passed inline. The env source is the KEYPAIR (or
+// KEYPAIR_PATH) variable Config::from_env reads, which takes any of these forms.
+pub fn load_keypair(keypair: &str) -> AppResult<Keypair> {
+    let keypair = keypair.trim();
+
+    let bytes = if keypair.starts_with('[') {
+        serde_json::from_str::<Vec<u8>>(keypair)?
+    } else if Path::new(keypair).is_file() {
+        return Keypair::read_from_file(keypair)
+            .map_err(|error| AppError::ParsingError(format!("Failed to read keypair file {keypair}: {error}")));
+    } else {
+        bs58::decode(keypair).into_vec().map_err(|_| {
+            AppError::ParsingError(
+                "Invalid keypair, expected a file path, a base58 secret key or a JSON byte array".to_string(),
+            )
+        })?
+    };
+
+    Keypair::try_from(bytes.as_slice())
+        .map_err(|error| AppError::ParsingError(format!("Invalid keypair bytes: {error}")))
+}
+
+// Websocket url of an http(s) rpc url, the solana convention of the same host and path.
+pub fn derive_ws_url(rpc_url: &str) -> String {
+    if let Some(rest) = rpc_url.strip_prefix("https://") {
+        format!("wss://{rest}")
+    } else if let Some(rest) = rpc_url.strip_prefix("http://") {
+        format!("ws://{rest}")
+    } else {
+        rpc_url.to_string()
+    }
+}
+
+// getProgramAccounts filters matching the PullFeed account whose feed_hash equals the given one.
+pub fn build_feed_hash_filters(feed_hash: &[u8; 32]) -> Vec<RpcFilterType> {
+    // account data starts with the 8 byte discriminator
+    let feed_hash_offset =
+        PullFeedAccountData::DISCRIMINATOR.len() + std::mem::offset_of!(PullFeedAccountData, feed_hash);
 
-    submit_ix
+    vec![
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            0,
+            PullFeedAccountData::DISCRIMINATOR,
+        )),
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(feed_hash_offset, feed_hash)),
+    ]
+}
+
+// Whether new_value moved more than threshold (relative to current_value) away from it.
+pub fn exceeds_value_change_threshold(
+    current_value: Decimal,
+    new_value: Decimal,
+    threshold: Decimal,
+) -> bool {
+    if current_value.is_zero() {
+        return !new_value.is_zero();
+    }
+
+    (new_value - current_value).abs() / current_value.abs() > threshold
+}
+
+// Oracles report a failure_error per feed evaluation. When every evaluation failed with the same
+// error the feed's jobs themselves are broken, e.g. a dead data source, which no other gateway or
+// retry will fix.
+pub fn detect_feed_source_unavailable<'a>(
+    failure_errors: impl IntoIterator<Item = &'a str>,
+) -> AppResult<()> {
+    let mut failure_errors = failure_errors.into_iter().map(str::trim);
+
+    let Some(first_error) = failure_errors.next() else {
+        return Ok(());
+    };
+
+    if !first_error.is_empty() && failure_errors.all(|failure_error| failure_error == first_error) {
+        return Err(AppError::FeedSourceUnavailable(first_error.to_string()));
+    }
+
+    Ok(())
+}
+
+// feed_hash of a feed on queue running jobs: sha256 of the queue followed by every job, length
+// delimited protobuf encoded, the same way the switchboard sdks derive it at feed creation.
+pub fn compute_feed_hash(queue: &Pubkey, jobs: &[OracleJob]) -> [u8; 32] {
+    let encoded_jobs = jobs
+        .iter()
+        .map(|job| job.encode_length_delimited_to_vec())
+        .collect::<Vec<_>>();
+
+    let mut hash_input = vec![queue.as_ref()];
+    hash_input.extend(encoded_jobs.iter().map(Vec::as_slice));
+
+    hashv(&hash_input).to_bytes()
+}
+
+// Errors when the jobs don't hash to the feed's feed_hash, i.e. crossbar served different jobs
+// than the feed was created with.
+pub fn verify_jobs_hash(feed_data: &PullFeedAccountData, jobs: &[OracleJob]) -> AppResult<()> {
+    let jobs_hash = compute_feed_hash(&feed_data.queue, jobs);
+
+    if jobs_hash != feed_data.feed_hash {
+        return Err(AppError::JobsHashMismatch(format!(
+            "Jobs hash to {}, the feed's feed_hash is {}",
+            hex::encode(jobs_hash),
+            hex::encode(feed_data.feed_hash)
+        )));
+    }
+
+    Ok(())
+}
+
+// Errors when none of the oracle accounts inspected advertises a gateway, there is nobody to ask
+// for signatures.
+pub fn ensure_gateways_available(gateways: usize, oracles_inspected: usize) -> AppResult<()> {
+    if gateways == 0 {
+        return Err(AppError::NoGatewaysAvailable { oracles_inspected });
+    }
+
+    Ok(())
+}
+
+// Errors when the queue has fewer live oracles (advertising a gateway) than the feed's
+// min_responses, no gateway could ever gather enough responses.
+pub fn ensure_min_responses_reachable(
+    feed_data: &PullFeedAccountData,
+    live_oracles: usize,
+) -> AppResult<()> {
+    if (live_oracles as u64) < u64::from(feed_data.min_responses) {
+        return Err(AppError::ParsingError(format!(
+            "feed min_responses exceeds available oracles: min_responses {}, live oracles {live_oracles}",
+            feed_data.min_responses
+        )));
+    }
+
+    Ok(())
+}
+
+// Rejects feeds the program would refuse submissions for, typically uninitialized or broken ones.
+pub fn validate_feed_config(feed_data: &PullFeedAccountData) -> AppResult<()> {
+    if feed_data.min_responses == 0 {
+        return Err(AppError::ParsingError(
+            "Invalid feed config: min_responses must be at least 1".to_string(),
+        ));
+    }
+
+    if u32::from(feed_data.min_sample_size) < feed_data.min_responses {
+        return Err(AppError::ParsingError(format!(
+            "Invalid feed config: min_sample_size ({}) is lower than min_responses ({})",
+            feed_data.min_sample_size, feed_data.min_responses
+        )));
+    }
+
+    Ok(())
 }
 
 pub async fn get_oracle_submissions(
     feed_data: &PullFeedAccountData,
-    gateway: &Gateway,
+    crossbar: &Crossbar,
+    gateway: &GatewayClient,
     recent_blockhash: Hash,
 ) -> AppResult<Vec<OracleResponse>> {
-    let crossbar = CrossbarClient::default();
-
-    let feed_hash = hex::encode(feed_data.feed_hash);
+    validate_feed_config(feed_data)?;
 
-    let jobs_data = crossbar
-        .fetch(&feed_hash)
-        .await
-        .map_err(|error| AppError::ParsingError(format!("{error:#?}")))?;
-
-    let jobs: Vec<OracleJob> = serde_json::from_value(jobs_data.get("jobs").unwrap().clone())?;
+    let jobs = crossbar.fetch_feed_jobs(feed_data).await?;
 
     let encoded_jobs = encode_jobs(&jobs);
 
-    let num_signatures = (feed_data.min_sample_size as f64
-        + ((feed_data.min_sample_size as f64) / 3.0).ceil()) as u32;
+    let num_signatures = required_signatures(feed_data);
 
     let price_signatures = gateway
         .fetch_signatures_from_encoded(FetchSignaturesParams {
@@ -118,45 +684,109 @@ pub async fn get_oracle_submissions(
             min_responses: Some(feed_data.min_responses),
             use_timestamp: Some(false),
         })
-        .await
-        .map_err(|error| AppError::ParsingError(format!("{error:#?}")))?;
+        .await?;
+
+    detect_feed_source_unavailable(
+        price_signatures
+            .responses
+            .iter()
+            .map(|response| response.failure_error.as_str()),
+    )?;
 
-    let oracle_responses: Vec<OracleResponse> = price_signatures
+    // an oracle that failed to evaluate the jobs signed no value, submitting it only wastes compute
+    let (responses, failed_responses): (Vec<_>, Vec<_>) = price_signatures
         .responses
         .iter()
+        .partition(|response| response.failure_error.is_empty());
+    for failed_response in &failed_responses {
+        tracing::warn!(
+            "Dropping failed response of oracle - {}: {}",
+            failed_response.oracle_pubkey,
+            failed_response.failure_error
+        );
+    }
+
+    if (responses.len() as u64) < u64::from(feed_data.min_responses) {
+        return Err(AppError::InsufficientResponses {
+            received: responses.len(),
+            min_responses: feed_data.min_responses,
+        });
+    }
+
+    let oracle_responses = responses
+        .into_iter()
         .map(|x| {
             let value = x.success_value.parse::<i128>().ok();
             let mut formatted_value = None;
             if let Some(val) = value {
-                formatted_value = Some(Decimal::from_i128_with_scale(val, 18));
+                // None for values wider than Decimal's 96 bit mantissa, like unparsable ones
+                formatted_value = Decimal::try_from_i128_with_scale(val, VALUE_SCALE).ok();
             }
-            OracleResponse {
+            Ok(OracleResponse {
                 value: formatted_value,
                 error: x.failure_error.clone(),
                 oracle: Pubkey::new_from_array(
-                    hex::decode(x.oracle_pubkey.clone())
-                        .unwrap()
+                    hex::decode(&x.oracle_pubkey)
+                        .map_err(|e| AppError::ParsingError(format!("Failed to decode oracle pubkey: {e}")))?
                         .try_into()
-                        .unwrap(),
+                        .map_err(|_| AppError::ParsingError("Invalid oracle pubkey length".to_string()))?,
                 ),
-                recovery_id: x.recovery_id as u8,
-                signature: base64
-                    .decode(x.signature.clone())
-                    .unwrap_or_default()
-                    .try_into()
-                    .unwrap_or([0; 64]),
-            }
+                recovery_id: normalize_recovery_id(x.recovery_id)?,
+                signature: decode_oracle_signature(&x.signature)?,
+            })
         })
-        .collect();
+        .collect::<AppResult<Vec<_>>>()?;
 
     Ok(oracle_responses)
 }
 
-fn extract_consensus_values(price_signatures: &FetchSignaturesConsensusResponse) -> Vec<i128> {
+// Gateways either return the raw ethereum style recovery id (27/28) or the normalized one (0/1),
+// the secp256k1 program expects the normalized form.
+pub fn normalize_recovery_id(recovery_id: i32) -> AppResult<u8> {
+    let normalized = if recovery_id >= 27 {
+        recovery_id - 27
+    } else {
+        recovery_id
+    };
+
+    match u8::try_from(normalized) {
+        Ok(recovery_id @ 0..=3) => Ok(recovery_id),
+        _ => Err(AppError::ParsingError(format!(
+            "Invalid recovery_id: {recovery_id}"
+        ))),
+    }
+}
+
+// Base64 secp256k1 signature returned by a gateway, anything but exactly 64 bytes is rejected
+// rather than submitted and left to fail on-chain.
+pub fn decode_oracle_signature(signature: &str) -> AppResult<[u8; 64]> {
+    base64
+        .decode(signature)
+        .map_err(|e| AppError::ParsingError(format!("Invalid signature: {e}")))?
+        .try_into()
+        .map_err(|bytes: Vec<u8>| {
+            AppError::ParsingError(format!("Invalid signature length: {}", bytes.len()))
+        })
+}
+
+// The median values as submitted, an unparsable median is an error rather than a value the
+// program would store as a real price.
+fn extract_consensus_values(price_signatures: &FetchSignaturesConsensusResponse) -> AppResult<Vec<i128>> {
     price_signatures
         .median_responses
         .iter()
-        .map(|mr| mr.value.parse::<i128>().unwrap_or(i128::MAX))
+        .map(|median_response| {
+            median_response.value.parse::<i128>().map_err(|_| {
+                AppError::ParsingError(format!(
+                    "Consensus response contains unparsable median values: {:?}",
+                    price_signatures
+                        .median_responses
+                        .iter()
+                        .map(|median_response| &median_response.value)
+                        .collect::<Vec<_>>()
+                ))
+            })
+        })
         .collect()
 }
 
@@ -182,7 +812,39 @@ fn extract_oracle_keys(price_signatures: &FetchSignaturesConsensusResponse) -> A
         .collect()
 }
 
-fn build_secp_signatures(price_signatures: &FetchSignaturesConsensusResponse) -> AppResult<Vec<SecpSignature>> {
+// Timestamp each oracle attached to its feed response (when the gateway provides one), useful to
+// spot oracles that consistently report lagging data. Oracles with an undecodable pubkey are skipped.
+pub fn extract_oracle_timestamps(
+    price_signatures: &FetchSignaturesConsensusResponse,
+) -> Vec<(Pubkey, Option<i64>)> {
+    price_signatures
+        .oracle_responses
+        .iter()
+        .filter_map(|oracle_response| {
+            let feed_response = oracle_response.feed_responses.first()?;
+
+            let oracle_pubkey = hex::decode(&feed_response.oracle_pubkey)
+                .ok()
+                .and_then(|decoded| <[u8; 32]>::try_from(decoded).ok())
+                .map(Pubkey::new_from_array);
+
+            let Some(oracle_pubkey) = oracle_pubkey else {
+                tracing::warn!(
+                    "Skipping timestamp of undecodable oracle pubkey - {}",
+                    feed_response.oracle_pubkey
+                );
+                return None;
+            };
+
+            Some((oracle_pubkey, feed_response.timestamp))
+        })
+        .collect()
+}
+
+fn build_secp_signatures(
+    price_signatures: &FetchSignaturesConsensusResponse,
+    message_format: SecpMessageFormat,
+) -> AppResult<Vec<SecpSignature>> {
     price_signatures
         .oracle_responses
         .iter()
@@ -192,58 +854,225 @@ fn build_secp_signatures(price_signatures: &FetchSignaturesConsensusResponse) ->
                 .try_into()
                 .map_err(|_| AppError::ParsingError("Invalid eth_address length".to_string()))?;
 
-            let signature = base64
-                .decode(&oracle_response.signature)
-                .map_err(|e| AppError::ParsingError(format!("Invalid signature: {e}")))?
-                .try_into()
-                .map_err(|_| AppError::ParsingError("Invalid signature length".to_string()))?;
+            let signature = decode_oracle_signature(&oracle_response.signature)?;
 
-            let message = base64
+            let checksum: [u8; 32] = base64
                 .decode(&oracle_response.checksum)
                 .map_err(|e| AppError::ParsingError(format!("Invalid checksum: {e}")))?
                 .try_into()
                 .map_err(|_| AppError::ParsingError("Invalid checksum length".to_string()))?;
+            let message = message_format.prepare_message(&checksum);
 
             Ok(SecpSignature {
                 eth_address,
                 signature,
                 message,
-                recovery_id: oracle_response.recovery_id as u8,
+                recovery_id: normalize_recovery_id(oracle_response.recovery_id)?,
             })
         })
         .collect()
 }
 
-fn build_consensus_instruction_accounts(params: &SolanaSubmitSignaturesParams, oracle: Pubkey) -> Vec<AccountMeta> {
-    vec![
-        AccountMeta::new(params.feed, false),
-        AccountMeta::new_readonly(oracle, false),
-        AccountMeta::new(OracleAccountData::stats_key(&oracle), false),
-    ]
+// What the oracles are expected to have signed for a consensus update: the checksum covers the
+// queue, the feed's hash/max_variance/min_responses, the hash sent to the gateway and the medians.
+pub struct ExpectedChecksum<'a> {
+    pub feed_data: &'a PullFeedAccountData,
+    // hash passed as recent_hash when fetching the signatures
+    pub signed_hash: Hash,
+}
+
+// Recomputes the consensus checksum and makes sure every oracle signed exactly that message.
+pub fn verify_consensus_checksums(
+    price_signatures: &FetchSignaturesConsensusResponse,
+    queue: &Pubkey,
+    expected_checksum: &ExpectedChecksum,
+    values: &[i128],
+) -> AppResult<()> {
+    // same layout as switchboard_on_demand_client::generate_combined_checksum for a single feed,
+    // which takes the client crate's own PullFeedAccountData type
+    let feed_data = expected_checksum.feed_data;
+    let max_variance = feed_data.max_variance.to_le_bytes();
+    let min_responses = feed_data.min_responses.to_le_bytes();
+    let signed_hash = expected_checksum.signed_hash.to_bytes();
+    let values = values.iter().map(|value| value.to_le_bytes()).collect::<Vec<_>>();
+
+    let mut checksum_input: Vec<&[u8]> = vec![
+        queue.as_ref(),
+        &feed_data.feed_hash,
+        &max_variance,
+        &min_responses,
+        &signed_hash,
+    ];
+    checksum_input.extend(values.iter().map(|value| value.as_slice()));
+    let checksum = hashv(&checksum_input).to_bytes();
+
+    for oracle_response in &price_signatures.oracle_responses {
+        let signed_checksum = base64
+            .decode(&oracle_response.checksum)
+            .map_err(|e| AppError::ParsingError(format!("Invalid checksum: {e}")))?;
+
+        if signed_checksum != checksum {
+            return Err(AppError::ChecksumMismatch(format!(
+                "oracle {} signed {}, expected {}",
+                oracle_response.oracle_pubkey,
+                hex::encode(&signed_checksum),
+                hex::encode(checksum)
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+// The feed followed by an oracle/oracle_stats pair per responding oracle, in the order of their
+// signatures in the secp256k1 ix.
+fn build_consensus_instruction_accounts(params: &SolanaSubmitSignaturesParams, oracles: &[Pubkey]) -> Vec<AccountMeta> {
+    let mut accounts = vec![AccountMeta::new(params.feed, false)];
+    accounts.extend(build_oracle_accounts(oracles));
+
+    accounts
+}
+
+// Fixed positional accounts of pull_feed_submit_response, see PullFeedSubmitResponse
+const SUBMIT_RESPONSE_ACCOUNT_LABELS: [&str; 9] = [
+    "feed",
+    "queue",
+    "program_state",
+    "recent_slothashes",
+    "payer",
+    "system_program",
+    "reward_vault",
+    "token_program",
+    "token_mint",
+];
+
+// Fixed positional accounts of pull_feed_submit_response_consensus, see
+// PullFeedSubmitResponseConsensus
+const SUBMIT_RESPONSE_CONSENSUS_ACCOUNT_LABELS: [&str; 9] = [
+    "queue",
+    "program_state",
+    "recent_slothashes",
+    "payer",
+    "system_program",
+    "reward_vault",
+    "token_program",
+    "token_mint",
+    "instructions_sysvar",
+];
+
+// Labels every account meta of a pull_feed_submit_response(_consensus) instruction with its role,
+// following the positional order the on-demand program expects. The remaining accounts are the
+// (writable) feeds of a consensus submission followed by oracle/oracle_stats pairs.
+pub fn label_submit_account_metas(instruction: &Instruction) -> AppResult<Vec<(String, &AccountMeta)>> {
+    let discriminator = instruction.data.get(..8).unwrap_or_default();
+    let fixed_labels = if discriminator == get_discriminator("pull_feed_submit_response") {
+        SUBMIT_RESPONSE_ACCOUNT_LABELS
+    } else if discriminator == get_discriminator("pull_feed_submit_response_consensus") {
+        SUBMIT_RESPONSE_CONSENSUS_ACCOUNT_LABELS
+    } else {
+        return Err(AppError::ParsingError(
+            "Not a pull_feed_submit_response(_consensus) instruction".to_string(),
+        ));
+    };
+
+    if instruction.accounts.len() < fixed_labels.len() {
+        return Err(AppError::ParsingError(format!(
+            "Expected at least {} accounts, got {}",
+            fixed_labels.len(),
+            instruction.accounts.len()
+        )));
+    }
+
+    let (fixed_accounts, remaining_accounts) = instruction.accounts.split_at(fixed_labels.len());
+    let feed_count = remaining_accounts
+        .iter()
+        .take_while(|account_meta| account_meta.is_writable)
+        .count();
+
+    let mut labeled_account_metas = fixed_labels
+        .iter()
+        .map(|label| label.to_string())
+        .zip(fixed_accounts)
+        .collect::<Vec<_>>();
+
+    for (index, account_meta) in remaining_accounts.iter().enumerate() {
+        let label = if index < feed_count {
+            format!("feed[{index}]")
+        } else if (index - feed_count) % 2 == 0 {
+            format!("oracle[{}]", (index - feed_count) / 2)
+        } else {
+            format!("oracle_stats[{}]", (index - feed_count) / 2)
+        };
+
+        labeled_account_metas.push((label, account_meta));
+    }
+
+    Ok(labeled_account_metas)
+}
+
+// One line per account meta: position, label, pubkey and flags, see label_submit_account_metas.
+pub fn format_submit_account_metas(instruction: &Instruction) -> AppResult<String> {
+    let lines = label_submit_account_metas(instruction)?
+        .into_iter()
+        .enumerate()
+        .map(|(position, (label, account_meta))| {
+            format!(
+                "{position:>3}  {label:<20}  {:<44}  {}{}",
+                account_meta.pubkey.to_string(),
+                if account_meta.is_writable { "w" } else { "-" },
+                if account_meta.is_signer { "s" } else { "-" },
+            )
+        })
+        .collect::<Vec<_>>();
+
+    Ok(lines.join("\n"))
 }
 
+// Returns [secp256k1 ix, submit ix]. secp_instruction_index is the position the secp256k1
+// instruction will have in the final transaction: its signature offsets point at that instruction's
+// own data, so the runtime (and the submit instruction relying on it) only verifies when the secp
+// ix actually lands there. Pass 0 when the two instructions are the start of the transaction.
+//
+// Every signature must recover to its oracle's eth_address under message_format (see
+// verify_secp_signatures), a malformed response fails here instead of in the simulation.
+// expected_checksum enables the strict check that the oracles signed the message this crate
+// expects (see verify_consensus_checksums), None skips it.
 pub fn get_update_consensus_ix(
     params: SolanaSubmitSignaturesParams,
     price_signatures: FetchSignaturesConsensusResponse,
     slot: u64,
+    secp_instruction_index: u8,
+    expected_checksum: Option<ExpectedChecksum>,
+    message_format: SecpMessageFormat,
+    cluster: Cluster,
 ) -> AppResult<Vec<Instruction>> {
-    let consensus_values = extract_consensus_values(&price_signatures);
+    let consensus_values = extract_consensus_values(&price_signatures)?;
     tracing::info!("consensus_ix_data values: {consensus_values:#?}");
 
+    if let Some(expected_checksum) = &expected_checksum {
+        verify_consensus_checksums(
+            &price_signatures,
+            &params.queue,
+            expected_checksum,
+            &consensus_values,
+        )?;
+    }
+
     let consensus_ix_data = PullFeedSubmitResponseConsensusParams {
         slot,
         values: consensus_values,
     };
 
     let oracle_keys = extract_oracle_keys(&price_signatures)?;
-    let secp_signatures = build_secp_signatures(&price_signatures)?;
+    let secp_signatures = build_secp_signatures(&price_signatures, message_format)?;
+
+    verify_secp_signatures(&secp_signatures)?;
 
     tracing::info!("secp_signatures (length): {}", secp_signatures.len());
 
-    let instruction_index = 0;
     let secp_ix = Secp256k1InstructionUtils::build_secp256k1_instruction(
         &secp_signatures,
-        instruction_index as u8,
+        secp_instruction_index,
     )
     .map_err(|_| {
         AppError::ParsingError(
@@ -251,18 +1080,21 @@ pub fn get_update_consensus_ix(
         )
     })?;
 
-    let oracle = oracle_keys[instruction_index];
-    let remaining_accounts = build_consensus_instruction_accounts(&params, oracle);
+    if oracle_keys.is_empty() {
+        return Err(AppError::ParsingError("No oracle responses found".to_string()));
+    }
+    let remaining_accounts = build_consensus_instruction_accounts(&params, &oracle_keys);
 
     let mut submit_ix = Instruction {
-        program_id: ON_DEMAND_MAINNET_PID,
+        program_id: cluster.program_id(),
         data: consensus_ix_data.data(),
         accounts: PullFeedSubmitResponseConsensus {
             queue: params.queue,
-            program_state: State::get_pda(),
+            program_state: cluster.program_state(),
             recent_slothashes: solana_sdk::sysvar::slot_hashes::ID,
             payer: params.payer,
             system_program: program::ID,
+            // required by the program, see get_solana_submit_signatures_ix
             reward_vault: get_associated_token_address(&params.queue, &NATIVE_MINT),
             token_program: spl_token::ID,
             token_mint: *NATIVE_MINT,
@@ -271,25 +1103,21 @@ pub fn get_update_consensus_ix(
     };
 
     submit_ix.accounts.extend(remaining_accounts);
+    ensure_unique_account_metas(&submit_ix.accounts)?;
 
     Ok(vec![secp_ix, submit_ix])
 }
 
 pub async fn get_consensus_signatures(
     feed_data: &PullFeedAccountData,
-    gateway: &Gateway,
+    crossbar: &Crossbar,
+    gateway: &GatewayClient,
     recent_blockhash: Hash,
+    num_signatures_policy: NumSignaturesPolicy,
 ) -> AppResult<FetchSignaturesConsensusResponse> {
-    let crossbar = CrossbarClient::default();
+    validate_feed_config(feed_data)?;
 
-    let feed_hash = hex::encode(feed_data.feed_hash);
-
-    let jobs_data = crossbar
-        .fetch(&feed_hash)
-        .await
-        .map_err(|error| AppError::ParsingError(format!("{error:#?}")))?;
-
-    let jobs: Vec<OracleJob> = serde_json::from_value(jobs_data.get("jobs").unwrap().clone())?;
+    let jobs = crossbar.fetch_feed_jobs(feed_data).await?;
 
     let encoded_jobs = encode_jobs(&jobs);
 
@@ -302,8 +1130,7 @@ pub async fn get_consensus_signatures(
         min_responses: Some(min_responses),
     };
 
-    // let num_signatures = feed_data.min_sample_size as u32 + ((feed_data.min_sample_size as f64) / 3.0).ceil() as u32;
-    let num_signatures = 1;
+    let num_signatures = num_signatures_policy.num_signatures(feed_data);
 
     // Call the gateway consensus endpoint and fetch signatures
     let price_signatures = gateway
@@ -313,14 +1140,95 @@ pub async fn get_consensus_signatures(
             feed_configs: vec![feed_config],
             use_timestamp: Some(false),
         })
-        .await
-        .map_err(|error| AppError::ParsingError(format!("{error}")))?;
+        .await?;
+
+    detect_feed_source_unavailable(
+        price_signatures
+            .oracle_responses
+            .iter()
+            .flat_map(|oracle_response| &oracle_response.feed_responses)
+            .map(|feed_response| feed_response.failure_error.as_str()),
+    )?;
 
     Ok(price_signatures)
 }
 
-pub fn parse_swb_ignore_alignment(data: Ref<&mut [u8]>) -> AppResult<PullFeedAccountData> {
-    if data.len() < 8 {
+// Fetches consensus signatures from the first `cross_check` gateways concurrently and only returns
+// (the first gateway's) response when every gateway answered and all median values agree within
+// the feed's max_variance, guarding against a single misbehaving gateway.
+pub async fn get_cross_checked_consensus_signatures(
+    feed_data: &PullFeedAccountData,
+    crossbar: &Crossbar,
+    gateways: &[GatewayClient],
+    recent_blockhash: Hash,
+    cross_check: usize,
+    num_signatures_policy: NumSignaturesPolicy,
+) -> AppResult<FetchSignaturesConsensusResponse> {
+    if cross_check < 2 || gateways.len() < cross_check {
+        return Err(AppError::CrossCheckFailed(format!(
+            "Cross checking requires at least 2 gateways, requested {cross_check} out of {} available",
+            gateways.len()
+        )));
+    }
+
+    let responses = futures::future::join_all(
+        gateways[..cross_check]
+            .iter()
+            .map(|gateway| {
+                get_consensus_signatures(feed_data, crossbar, gateway, recent_blockhash, num_signatures_policy)
+            }),
+    )
+    .await
+    .into_iter()
+    .collect::<AppResult<Vec<_>>>()?;
+
+    // max_variance is stored as a percentage scaled by 1e9
+    let max_variance_percentage = Decimal::from(feed_data.max_variance) / Decimal::from(1_000_000_000);
+
+    let reference_values = extract_consensus_values(&responses[0])?;
+    for response in &responses[1..] {
+        let values = extract_consensus_values(response)?;
+
+        if values.len() != reference_values.len() {
+            return Err(AppError::CrossCheckFailed(format!(
+                "Gateways returned a different number of median values: {} vs {}",
+                reference_values.len(),
+                values.len()
+            )));
+        }
+
+        for (reference_value, value) in reference_values.iter().zip(values) {
+            // Decimal holds 96 bit mantissas, a wider median can't be compared
+            let to_decimal = |value: i128| {
+                Decimal::try_from_i128_with_scale(value, VALUE_SCALE).map_err(|_| {
+                    AppError::CrossCheckFailed(format!("Gateway median value {value} is out of range"))
+                })
+            };
+            let reference_value = to_decimal(*reference_value)?;
+            let value = to_decimal(value)?;
+
+            let diverges = if reference_value.is_zero() {
+                !value.is_zero()
+            } else {
+                (value - reference_value).abs() / reference_value.abs() * Decimal::ONE_HUNDRED
+                    > max_variance_percentage
+            };
+
+            if diverges {
+                return Err(AppError::CrossCheckFailed(format!(
+                    "Gateway median values diverge beyond max_variance ({max_variance_percentage}%): {reference_value} vs {value}"
+                )));
+            }
+        }
+    }
+
+    Ok(responses.into_iter().next().unwrap())
+}
+
+// Reads a PullFeedAccountData straight from the account bytes (discriminator included), whatever
+// their alignment, so callers can pass account.data as is.
+pub fn parse_swb_ignore_alignment(data: &[u8]) -> AppResult<PullFeedAccountData> {
+    if data.len() < 8 + std::mem::size_of::<PullFeedAccountData>() {
         return Err(AppError::SwitchboardInvalidAccount);
     }
 
@@ -336,6 +1244,290 @@ pub fn parse_swb_ignore_alignment(data: Ref<&mut [u8]>) -> AppResult<PullFeedAcc
     Ok(feed)
 }
 
+pub const COMPUTE_BUDGET_PROGRAM_ID: Pubkey =
+    Pubkey::from_str_const("ComputeBudget111111111111111111111111111111");
+
+// compute unit limit the runtime assigns to each instruction when none is requested
+pub const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+// Returns the compute unit limit the transaction built from `instructions` will run with,
+// either the one requested through SetComputeUnitLimit or the runtime default.
+pub fn get_compute_unit_limit(instructions: &[Instruction]) -> u32 {
+    let mut requested_limit = None;
+    let mut instruction_count: u32 = 0;
+
+    for instruction in instructions {
+        if instruction.program_id != COMPUTE_BUDGET_PROGRAM_ID {
+            instruction_count += 1;
+            continue;
+        }
+
+        // ComputeBudgetInstruction::SetComputeUnitLimit(u32)
+        if let [2, limit @ ..] = instruction.data.as_slice()
+            && let Ok(limit) = <[u8; 4]>::try_from(limit)
+        {
+            requested_limit = Some(u32::from_le_bytes(limit));
+        }
+    }
+
+    requested_limit
+        .unwrap_or(instruction_count.saturating_mul(DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT))
+        .min(MAX_COMPUTE_UNIT_LIMIT)
+}
+
+// Exponential backoff for the given (zero based) attempt, capped at max_delay. A jitter of 1.0
+// (full jitter) picks the delay uniformly in [0, backoff], 0.0 returns the plain backoff.
+pub fn backoff_with_jitter(
+    base_delay: Duration,
+    max_delay: Duration,
+    attempt: u32,
+    jitter: f64,
+) -> Duration {
+    let backoff = base_delay
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(max_delay);
+
+    let jitter = jitter.clamp(0.0, 1.0);
+    if jitter == 0.0 {
+        return backoff;
+    }
+
+    let scale = 1.0 - jitter * rand::thread_rng().gen_range(0.0..=1.0);
+
+    backoff.mul_f64(scale)
+}
+
+// Raises the compute unit price of every send retry, see AppClientBuilder::priority_fee_escalation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PriorityFeeEscalation {
+    // multiplier applied to the compute unit price on every retry
+    pub factor: f64,
+    // the escalated price never exceeds this, in micro-lamports
+    pub max_micro_lamports: u64,
+}
+
+impl PriorityFeeEscalation {
+    // Price of the next retry, at least one micro-lamport above the current one until the ceiling.
+    pub fn next_price(&self, micro_lamports: u64) -> u64 {
+        if micro_lamports >= self.max_micro_lamports {
+            return micro_lamports;
+        }
+
+        let escalated = (micro_lamports as f64 * self.factor).ceil() as u64;
+        escalated
+            .max(micro_lamports + 1)
+            .min(self.max_micro_lamports)
+    }
+}
+
+// Price of the instructions' SetComputeUnitPrice ix, None when they don't set one.
+pub fn get_compute_unit_price(instructions: &[Instruction]) -> Option<u64> {
+    instructions.iter().rev().find_map(|instruction| {
+        if instruction.program_id != COMPUTE_BUDGET_PROGRAM_ID {
+            return None;
+        }
+
+        // ComputeBudgetInstruction::SetComputeUnitPrice(u64)
+        match instruction.data.as_slice() {
+            [3, price @ ..] => <[u8; 8]>::try_from(price).ok().map(u64::from_le_bytes),
+            _ => None,
+        }
+    })
+}
+
+// Replaces the price of the instructions' SetComputeUnitPrice ix, or appends one (see
+// AppClient::with_priority_fee for why it goes last).
+pub fn set_compute_unit_price(instructions: &mut Vec<Instruction>, micro_lamports: u64) {
+    instructions.retain(|instruction| {
+        instruction.program_id != COMPUTE_BUDGET_PROGRAM_ID || instruction.data.first() != Some(&3)
+    });
+    instructions.push(build_compute_unit_price_ix(micro_lamports));
+}
+
+// ComputeBudgetInstruction::SetComputeUnitPrice(u64)
+pub fn build_compute_unit_price_ix(micro_lamports: u64) -> Instruction {
+    let mut data = vec![3];
+    data.extend_from_slice(&micro_lamports.to_le_bytes());
+
+    Instruction {
+        program_id: COMPUTE_BUDGET_PROGRAM_ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+// Writable, non-signer accounts referenced by the instructions, deduplicated. These are the
+// accounts whose contention drives the priority fee of a submission.
+pub fn get_writable_accounts(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut writable_accounts = instructions
+        .iter()
+        .flat_map(|instruction| &instruction.accounts)
+        .filter(|account_meta| account_meta.is_writable && !account_meta.is_signer)
+        .map(|account_meta| account_meta.pubkey)
+        .collect::<Vec<_>>();
+
+    writable_accounts.sort();
+    writable_accounts.dedup();
+
+    writable_accounts
+}
+
+pub fn parse_queue_account(data: &[u8]) -> AppResult<QueueAccountData> {
+    if data.len() < 8 + std::mem::size_of::<QueueAccountData>() || data[..8] != QUEUE_ACCOUNT_DISCRIMINATOR {
+        return Err(AppError::SwitchboardInvalidAccount);
+    }
+
+    let queue = bytemuck::try_pod_read_unaligned::<QueueAccountData>(
+        &data[8..8 + std::mem::size_of::<QueueAccountData>()],
+    )
+    .map_err(|_| AppError::SwitchboardInvalidAccount)?;
+
+    Ok(queue)
+}
+
+// Reads the OracleAccountData prefix, trailing bytes left by a grown account layout are ignored.
+pub fn parse_oracle_account(data: &[u8]) -> AppResult<OracleAccountData> {
+    let size = std::mem::size_of::<OracleAccountData>();
+
+    if data.len() < 8 + size || &data[..8] != OracleAccountData::DISCRIMINATOR {
+        return Err(AppError::SwitchboardInvalidAccount);
+    }
+
+    bytemuck::try_pod_read_unaligned::<OracleAccountData>(&data[8..8 + size])
+        .map_err(|_| AppError::SwitchboardInvalidAccount)
+}
+
+pub fn parse_oracle_stats_account(data: &[u8]) -> AppResult<OracleStatsAccountData> {
+    let size = std::mem::size_of::<OracleStatsAccountData>();
+
+    if data.len() < 8 + size || &data[..8] != OracleStatsAccountData::DISCRIMINATOR {
+        return Err(AppError::SwitchboardInvalidAccount);
+    }
+
+    bytemuck::try_pod_read_unaligned::<OracleStatsAccountData>(&data[8..8 + size])
+        .map_err(|_| AppError::SwitchboardInvalidAccount)
+}
+
+// compact debug output beyond this many characters is cut when logs are redacted
+pub const REDACTED_LOG_MAX_LEN: usize = 256;
+
+// A value about to be logged, see AppClientBuilder::redact_logs.
+pub enum LogValue<'a, T: ?Sized> {
+    // pretty printed debug output, the default
+    Full(&'a T),
+    // compact debug output cut at REDACTED_LOG_MAX_LEN
+    Truncated(&'a T),
+    // logged instead of the value, e.g. only the essential fields
+    Substitute(String),
+}
+
+impl<T: Debug + ?Sized> Display for LogValue<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogValue::Full(value) => write!(f, "{value:#?}"),
+            LogValue::Truncated(value) => {
+                let output = format!("{value:?}");
+
+                match output.char_indices().nth(REDACTED_LOG_MAX_LEN) {
+                    None => f.write_str(&output),
+                    Some((end, _)) => write!(
+                        f,
+                        "{}... ({} bytes truncated)",
+                        &output[..end],
+                        output.len() - end
+                    ),
+                }
+            }
+            LogValue::Substitute(substitute) => f.write_str(substitute),
+        }
+    }
+}
+
+// An instruction in the web3.js shape (base58 keys, base64 data), for pasting into external
+// transaction inspectors.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstructionDump {
+    pub program_id: String,
+    pub keys: Vec<AccountMetaDump>,
+    pub data: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountMetaDump {
+    pub pubkey: String,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl From<&Instruction> for InstructionDump {
+    fn from(instruction: &Instruction) -> Self {
+        Self {
+            program_id: instruction.program_id.to_string(),
+            keys: instruction
+                .accounts
+                .iter()
+                .map(|account_meta| AccountMetaDump {
+                    pubkey: account_meta.pubkey.to_string(),
+                    is_signer: account_meta.is_signer,
+                    is_writable: account_meta.is_writable,
+                })
+                .collect(),
+            data: base64.encode(&instruction.data),
+        }
+    }
+}
+
+impl TryFrom<&InstructionDump> for Instruction {
+    type Error = AppError;
+
+    fn try_from(instruction_dump: &InstructionDump) -> AppResult<Self> {
+        let parse_pubkey = |pubkey: &str| {
+            pubkey
+                .parse::<Pubkey>()
+                .map_err(|error| AppError::ParsingError(format!("Invalid pubkey {pubkey} - {error}")))
+        };
+
+        let accounts = instruction_dump
+            .keys
+            .iter()
+            .map(|account_meta_dump| {
+                Ok(AccountMeta {
+                    pubkey: parse_pubkey(&account_meta_dump.pubkey)?,
+                    is_signer: account_meta_dump.is_signer,
+                    is_writable: account_meta_dump.is_writable,
+                })
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+
+        Ok(Instruction {
+            program_id: parse_pubkey(&instruction_dump.program_id)?,
+            accounts,
+            data: base64.decode(&instruction_dump.data)?,
+        })
+    }
+}
+
+// Pretty printed JSON array of the instructions, see InstructionDump.
+pub fn dump_instructions(instructions: &[Instruction]) -> AppResult<String> {
+    let instruction_dumps = instructions
+        .iter()
+        .map(InstructionDump::from)
+        .collect::<Vec<_>>();
+
+    Ok(serde_json::to_string_pretty(&instruction_dumps)?)
+}
+
+// Inverse of dump_instructions.
+pub fn parse_instruction_dump(dump: &str) -> AppResult<Vec<Instruction>> {
+    serde_json::from_str::<Vec<InstructionDump>>(dump)?
+        .iter()
+        .map(Instruction::try_from)
+        .collect()
+}
+
 pub enum UrlType {
     SolscanAccount(String),
     SolscanToken(String),
@@ -353,3 +1545,685 @@ pub fn construct_url(url_type: UrlType) -> String {
         SolscanTx(tx_signature) => format!("{solscan_base_url}/tx/{tx_signature}"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Client, header::HeaderMap};
+    use solana_sdk::{
+        instruction::InstructionError, signer::Signer, transaction::TransactionError,
+    };
+
+    use super::*;
+    use crate::app::mock_rpc::serve_http;
+
+    fn oracle_response(value: Option<Decimal>) -> OracleResponse {
+        OracleResponse {
+            value,
+            error: String::new(),
+            oracle: Pubkey::new_unique(),
+            signature: [1; 64],
+            recovery_id: 0,
+        }
+    }
+
+    // values of the borsh encoded PullFeedSubmitResponseParams, each Submission is 82 bytes
+    fn submitted_values(data: &[u8]) -> Vec<i128> {
+        let count = u32::from_le_bytes(data[16..20].try_into().unwrap()) as usize;
+        (0..count)
+            .map(|index| {
+                let start = 20 + index * 82;
+                i128::from_le_bytes(data[start..start + 16].try_into().unwrap())
+            })
+            .collect()
+    }
+
+    fn consensus_response(medians: &[&str]) -> FetchSignaturesConsensusResponse {
+        serde_json::from_value(serde_json::json!({
+            "median_responses": medians
+                .iter()
+                .map(|value| serde_json::json!({ "value": value, "feed_hash": "00" }))
+                .collect::<Vec<_>>(),
+            "oracle_responses": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn checksum_signed_over_another_value_is_rejected() {
+        let queue = Pubkey::new_unique();
+        let mut feed_data: PullFeedAccountData = bytemuck::Zeroable::zeroed();
+        feed_data.feed_hash = [3; 32];
+        feed_data.max_variance = 1_000_000_000;
+        feed_data.min_responses = 1;
+        let expected_checksum = ExpectedChecksum { feed_data: &feed_data, signed_hash: Hash::new_unique() };
+
+        let value: i128 = 1_500_000_000_000_000_000;
+        let checksum = hashv(&[
+            queue.as_ref(),
+            &feed_data.feed_hash,
+            &feed_data.max_variance.to_le_bytes(),
+            &feed_data.min_responses.to_le_bytes(),
+            &expected_checksum.signed_hash.to_bytes(),
+            &value.to_le_bytes(),
+        ]);
+        let price_signatures: FetchSignaturesConsensusResponse = serde_json::from_value(serde_json::json!({
+            "median_responses": [],
+            "oracle_responses": [{
+                "oracle_pubkey": "00",
+                "eth_address": "00",
+                "signature": "",
+                "checksum": base64.encode(checksum),
+                "recovery_id": 0,
+                "feed_responses": [],
+                "errors": [],
+            }],
+        }))
+        .unwrap();
+
+        verify_consensus_checksums(&price_signatures, &queue, &expected_checksum, &[value]).unwrap();
+
+        let error = verify_consensus_checksums(&price_signatures, &queue, &expected_checksum, &[value + 1])
+            .unwrap_err();
+        assert!(matches!(error, AppError::ChecksumMismatch(_)), "{error}");
+    }
+
+    // A crossbar on a local server answering every feed with body.
+    async fn serve_crossbar(body: serde_json::Value) -> Crossbar {
+        let (crossbar_url, _) = serve_http("200 OK", body.to_string()).await;
+
+        Crossbar::new(vec![crossbar_url], Client::new()).max_retries(0)
+    }
+
+    // A crossbar serving a single valueTask job for every feed.
+    async fn serve_value_jobs() -> Crossbar {
+        serve_crossbar(serde_json::json!({ "jobs": [{ "tasks": [{ "valueTask": { "value": 1.5 } }] }] })).await
+    }
+
+    // A gateway on a local server answering every request with body.
+    async fn serve_gateway(body: serde_json::Value) -> GatewayClient {
+        let (gateway_url, _) = serve_http("200 OK", body.to_string()).await;
+
+        GatewayClient::new(gateway_url, Client::new(), HeaderMap::new())
+    }
+
+    // A feed accepting a single response, within 1% of each other.
+    fn single_response_feed() -> PullFeedAccountData {
+        let mut feed_data: PullFeedAccountData = bytemuck::Zeroable::zeroed();
+        feed_data.queue = Pubkey::new_unique();
+        feed_data.min_responses = 1;
+        feed_data.min_sample_size = 1;
+        feed_data.max_variance = 1_000_000_000;
+
+        feed_data
+    }
+
+    // One oracle's answer to fetch_signatures, failed when failure_error isn't empty.
+    fn feed_eval_response(oracle_pubkey: &str, failure_error: &str, signature: &str) -> serde_json::Value {
+        serde_json::json!({
+            "oracle_pubkey": oracle_pubkey,
+            "queue_pubkey": "00",
+            "oracle_signing_pubkey": "00",
+            "feed_hash": "00",
+            "recent_hash": "00",
+            "failure_error": failure_error,
+            "success_value": if failure_error.is_empty() { "1500000000000000000" } else { "" },
+            "msg": "",
+            "signature": signature,
+            "recovery_id": 0,
+            "recent_successes_if_failed": [],
+            "timestamp": null,
+        })
+    }
+
+    fn fetch_signatures_body(responses: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({ "responses": responses, "caller": "", "failures": [] })
+    }
+
+    #[tokio::test]
+    async fn malformed_oracle_signature_is_not_submitted_as_zeros() {
+        let crossbar = serve_value_jobs().await;
+        let feed_data = single_response_feed();
+
+        let oracle_submissions = async |signature: &str| {
+            let response = feed_eval_response(&hex::encode(Pubkey::new_unique()), "", signature);
+            let gateway = serve_gateway(fetch_signatures_body(vec![response])).await;
+
+            get_oracle_submissions(&feed_data, &crossbar, &gateway, Hash::new_unique()).await
+        };
+
+        let submissions = oracle_submissions(&base64.encode([7; 64])).await.unwrap();
+        assert_eq!(submissions[0].signature, [7; 64]);
+
+        for signature in ["not base64!", &base64.encode([7; 63])] {
+            let error = oracle_submissions(signature).await.unwrap_err();
+            assert!(matches!(error, AppError::ParsingError(_)), "{error}");
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_crossbar_and_gateway_data_are_errors() {
+        let feed_data = single_response_feed();
+        let signature = base64.encode([7; 64]);
+        let gateway = serve_gateway(fetch_signatures_body(vec![feed_eval_response(
+            &hex::encode(Pubkey::new_unique()),
+            "",
+            &signature,
+        )]))
+        .await;
+
+        // crossbar answering without a jobs list
+        let crossbar = serve_crossbar(serde_json::json!({ "error": "feed not found" })).await;
+        let result = get_oracle_submissions(&feed_data, &crossbar, &gateway, Hash::new_unique()).await;
+        assert!(matches!(result, Err(AppError::CrossbarError(_))));
+        let result = get_consensus_signatures(
+            &feed_data,
+            &crossbar,
+            &gateway,
+            Hash::new_unique(),
+            NumSignaturesPolicy::default(),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::CrossbarError(_))));
+
+        // oracle pubkeys that aren't 32 hex encoded bytes
+        let crossbar = serve_value_jobs().await;
+        for oracle_pubkey in ["not hex", "0102"] {
+            let gateway =
+                serve_gateway(fetch_signatures_body(vec![feed_eval_response(oracle_pubkey, "", &signature)])).await;
+            let result = get_oracle_submissions(&feed_data, &crossbar, &gateway, Hash::new_unique()).await;
+            assert!(matches!(result, Err(AppError::ParsingError(_))));
+        }
+    }
+
+    #[test]
+    fn failed_simulation_carries_the_program_error_logs() {
+        let simulation_result = |logs: &[&str]| -> RpcSimulateTransactionResult {
+            serde_json::from_value(serde_json::json!({
+                "err": { "InstructionError": [1, { "Custom": 6030 }] },
+                "logs": logs,
+            }))
+            .unwrap()
+        };
+
+        let result = simulation_result(&[
+            "Program ComputeBudget111111111111111111111111111111 success",
+            "Program SBondMDrcV3K4kxZR1HNVT7osZxAHVHgYXL5Ze1oMUv invoke [1]",
+            "Program log: AnchorError occurred. Error Code: StaleSample. Error Number: 6030.",
+            "Program SBondMDrcV3K4kxZR1HNVT7osZxAHVHgYXL5Ze1oMUv failed: custom program error: 0x178e",
+        ]);
+        match check_simulation_result(&result) {
+            Err(AppError::SimulationFailed { err, logs }) => {
+                assert_eq!(err, TransactionError::InstructionError(1, InstructionError::Custom(6030)));
+                assert_eq!(logs, result.logs.unwrap()[2..]);
+            }
+            other => panic!("{other:?}"),
+        }
+
+        // without a line explaining the failure every line is kept
+        let result = simulation_result(&["Program SBondMDrcV3K4kxZR1HNVT7osZxAHVHgYXL5Ze1oMUv invoke [1]"]);
+        assert!(matches!(
+            check_simulation_result(&result),
+            Err(AppError::SimulationFailed { logs, .. }) if logs == result.logs.unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn failed_oracle_responses_are_dropped() {
+        let crossbar = serve_value_jobs().await;
+        let signature = base64.encode([7; 64]);
+        let oracles = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let gateway = serve_gateway(fetch_signatures_body(vec![
+            feed_eval_response(&hex::encode(oracles[0]), "", &signature),
+            feed_eval_response(&hex::encode(oracles[1]), "rate limited", &signature),
+            feed_eval_response(&hex::encode(oracles[2]), "", &signature),
+        ]))
+        .await;
+        let mut feed_data = single_response_feed();
+
+        feed_data.min_responses = 2;
+        feed_data.min_sample_size = 2;
+        let submissions = get_oracle_submissions(&feed_data, &crossbar, &gateway, Hash::new_unique())
+            .await
+            .unwrap();
+        let submitted_oracles = submissions.iter().map(|submission| submission.oracle).collect::<Vec<_>>();
+        assert_eq!(submitted_oracles, [oracles[0], oracles[2]]);
+        assert!(submissions.iter().all(|submission| submission.value.is_some()));
+
+        feed_data.min_responses = 3;
+        feed_data.min_sample_size = 3;
+        let result = get_oracle_submissions(&feed_data, &crossbar, &gateway, Hash::new_unique()).await;
+        assert!(matches!(
+            result,
+            Err(AppError::InsufficientResponses { received: 2, min_responses: 3 })
+        ));
+    }
+
+    #[test]
+    fn instruction_dump_round_trips() {
+        let instructions = vec![
+            Instruction::new_with_bytes(
+                Pubkey::new_unique(),
+                &[1, 2, 3],
+                vec![
+                    AccountMeta::new(Pubkey::new_unique(), true),
+                    AccountMeta::new(Pubkey::new_unique(), false),
+                    AccountMeta::new_readonly(Pubkey::new_unique(), true),
+                    AccountMeta::new_readonly(Pubkey::new_unique(), false),
+                ],
+            ),
+            build_compute_unit_price_ix(100),
+        ];
+
+        let dump = dump_instructions(&instructions).unwrap();
+
+        assert_eq!(parse_instruction_dump(&dump).unwrap(), instructions);
+    }
+
+    #[test]
+    fn malformed_secp_instruction_is_rejected() {
+        // secp256k1 ix carrying two signatures followed by the submit ix
+        let instructions =
+            parse_instruction_dump(include_str!("../tests/fixtures/submit_fixture_instructions.json")).unwrap();
+        validate_secp256k1_instruction(&instructions, 0, 2).unwrap();
+
+        let is_invalid = |result: AppResult<()>| matches!(result, Err(AppError::InvalidSecpInstruction(_)));
+        assert!(is_invalid(validate_secp256k1_instruction(&instructions, 0, 3)));
+        assert!(is_invalid(validate_secp256k1_instruction(&instructions, 1, 2)));
+
+        // the offsets still point at instruction 0, which no longer holds the signatures
+        let mut shifted_instructions = instructions.clone();
+        shifted_instructions.insert(0, build_compute_unit_price_ix(100));
+        assert!(is_invalid(validate_secp256k1_instruction(&shifted_instructions, 1, 2)));
+
+        // the first signature offset points past the end of the data
+        let mut corrupted_instructions = instructions.clone();
+        corrupted_instructions[0].data[1..3].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert!(is_invalid(validate_secp256k1_instruction(&corrupted_instructions, 0, 2)));
+    }
+
+    // Gateways on a local server, each answering the consensus request with one median.
+    async fn cross_check(medians: &[&str]) -> AppResult<FetchSignaturesConsensusResponse> {
+        let crossbar = serve_value_jobs().await;
+
+        let mut gateways = vec![];
+        for median in medians {
+            gateways.push(serve_gateway(serde_json::to_value(consensus_response(&[median])).unwrap()).await);
+        }
+
+        let feed_data = single_response_feed();
+
+        get_cross_checked_consensus_signatures(
+            &feed_data,
+            &crossbar,
+            &gateways,
+            Hash::new_unique(),
+            gateways.len(),
+            NumSignaturesPolicy::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn diverging_gateways_fail_the_cross_check() {
+        let response = cross_check(&["100000000000000000000", "100500000000000000000"]).await.unwrap();
+        assert_eq!(response.median_responses[0].value, "100000000000000000000");
+
+        let error = cross_check(&["100000000000000000000", "102000000000000000000"]).await.unwrap_err();
+        assert!(matches!(error, AppError::CrossCheckFailed(_)), "{error}");
+    }
+
+    #[test]
+    fn missing_value_is_dropped_from_submit_instruction() {
+        let responses = vec![oracle_response(Some(Decimal::new(15, 1))), oracle_response(None)];
+        let dropped_oracle = responses[1].oracle;
+        let params = SolanaSubmitSignaturesParams {
+            queue: Pubkey::new_unique(),
+            feed: Pubkey::new_unique(),
+            payer: Pubkey::new_unique(),
+        };
+
+        let instruction = get_solana_submit_signatures_ix(
+            1,
+            responses,
+            params,
+            MissingValuePolicy::Drop,
+            None,
+            Cluster::Mainnet,
+        )
+        .unwrap();
+
+        assert_eq!(submitted_values(&instruction.data), vec![1_500_000_000_000_000_000]);
+        assert!(instruction.accounts.iter().all(|account| account.pubkey != dropped_oracle));
+    }
+
+    #[test]
+    fn missing_value_fails_submit_instruction_with_error_policy() {
+        let params = SolanaSubmitSignaturesParams {
+            queue: Pubkey::new_unique(),
+            feed: Pubkey::new_unique(),
+            payer: Pubkey::new_unique(),
+        };
+
+        let result = get_solana_submit_signatures_ix(
+            1,
+            vec![oracle_response(Some(Decimal::ONE)), oracle_response(None)],
+            params,
+            MissingValuePolicy::Error,
+            None,
+            Cluster::Mainnet,
+        );
+
+        assert!(matches!(result, Err(AppError::ParsingError(_))));
+    }
+
+    #[test]
+    fn unparsable_consensus_median_is_an_error() {
+        assert_eq!(extract_consensus_values(&consensus_response(&["42", "-7"])).unwrap(), vec![42, -7]);
+        assert!(matches!(
+            extract_consensus_values(&consensus_response(&["42", "NaN"])),
+            Err(AppError::ParsingError(_))
+        ));
+        assert!(matches!(
+            extract_consensus_values(&consensus_response(&[""])),
+            Err(AppError::ParsingError(_))
+        ));
+    }
+
+    #[test]
+    fn backoff_delay_falls_within_jittered_range() {
+        let base_delay = Duration::from_millis(100);
+        let max_delay = Duration::from_secs(1);
+
+        assert_eq!(backoff_with_jitter(base_delay, max_delay, 2, 0.0), Duration::from_millis(400));
+        assert_eq!(backoff_with_jitter(base_delay, max_delay, 10, 0.0), max_delay);
+
+        for _ in 0..1000 {
+            let delay = backoff_with_jitter(base_delay, max_delay, 2, 0.5);
+            assert!((Duration::from_millis(200)..=Duration::from_millis(400)).contains(&delay));
+
+            let delay = backoff_with_jitter(base_delay, max_delay, 10, 1.0);
+            assert!(delay <= max_delay);
+        }
+    }
+
+    #[test]
+    fn unparsable_consensus_median_builds_no_instruction() {
+        let params = SolanaSubmitSignaturesParams {
+            queue: Pubkey::new_unique(),
+            feed: Pubkey::new_unique(),
+            payer: Pubkey::new_unique(),
+        };
+
+        let result = get_update_consensus_ix(
+            params,
+            consensus_response(&["not a number"]),
+            1,
+            0,
+            None,
+            SecpMessageFormat::default(),
+            Cluster::Mainnet,
+        );
+
+        assert!(matches!(result, Err(AppError::ParsingError(_))));
+    }
+
+    fn oracle_account_data(len: usize) -> Vec<u8> {
+        let mut data = OracleAccountData::DISCRIMINATOR.to_vec();
+        data.extend((0..len - 8).map(|index| index as u8));
+        data
+    }
+
+    #[test]
+    fn parse_oracle_account_tolerates_grown_layouts() {
+        let size = std::mem::size_of::<OracleAccountData>();
+
+        for len in [8 + size, 8 + size + 64] {
+            let data = oracle_account_data(len);
+            let oracle = parse_oracle_account(&data).unwrap();
+            assert_eq!(bytemuck::bytes_of(&oracle), &data[8..8 + size]);
+        }
+
+        assert!(matches!(
+            parse_oracle_account(&oracle_account_data(8 + size - 1)),
+            Err(AppError::SwitchboardInvalidAccount)
+        ));
+    }
+
+    #[test]
+    fn parse_oracle_account_rejects_malformed_accounts() {
+        assert!(matches!(parse_oracle_account(&[]), Err(AppError::SwitchboardInvalidAccount)));
+        assert!(matches!(
+            parse_oracle_account(&OracleAccountData::DISCRIMINATOR[..4]),
+            Err(AppError::SwitchboardInvalidAccount)
+        ));
+
+        let mut data = oracle_account_data(8 + std::mem::size_of::<OracleAccountData>());
+        data[..8].copy_from_slice(PullFeedAccountData::DISCRIMINATOR);
+        assert!(matches!(parse_oracle_account(&data), Err(AppError::SwitchboardInvalidAccount)));
+    }
+
+    #[test]
+    fn load_keypair_accepts_a_file_base58_or_json_bytes() {
+        let keypair = Keypair::new();
+        let json_bytes = serde_json::to_string(&keypair.to_bytes().to_vec()).unwrap();
+
+        let path = std::env::temp_dir().join(format!("swb-ix-keypair-{}.json", keypair.pubkey()));
+        std::fs::write(&path, &json_bytes).unwrap();
+        let from_file = load_keypair(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(from_file.unwrap().pubkey(), keypair.pubkey());
+        assert_eq!(load_keypair(&keypair.to_base58_string()).unwrap().pubkey(), keypair.pubkey());
+        assert_eq!(load_keypair(&format!(" {json_bytes}\n")).unwrap().pubkey(), keypair.pubkey());
+    }
+
+    #[test]
+    fn load_keypair_rejects_malformed_input() {
+        for malformed in ["", "/does/not/exist.json", "0OIl", "3yZe7d", "[1, 2, 3]", "[1, 2"] {
+            assert!(load_keypair(malformed).is_err(), "{malformed}");
+        }
+    }
+
+    #[test]
+    fn devnet_selects_the_devnet_program() {
+        let params = SolanaSubmitSignaturesParams {
+            queue: Pubkey::new_unique(),
+            feed: Pubkey::new_unique(),
+            payer: Pubkey::new_unique(),
+        };
+
+        let instruction = get_solana_submit_signatures_ix(
+            1,
+            vec![oracle_response(Some(Decimal::ONE))],
+            params,
+            MissingValuePolicy::Drop,
+            None,
+            Cluster::Devnet,
+        )
+        .unwrap();
+
+        assert_eq!(instruction.program_id, ON_DEMAND_DEVNET_PID);
+        assert_ne!(Cluster::Devnet.program_state(), Cluster::Mainnet.program_state());
+        assert!(
+            instruction
+                .accounts
+                .iter()
+                .any(|account| account.pubkey == Cluster::Devnet.program_state())
+        );
+    }
+
+    fn transaction_with_data(len: usize) -> VersionedTransaction {
+        let payer = Keypair::new();
+        let instruction = Instruction::new_with_bytes(Pubkey::new_unique(), &vec![0; len], vec![]);
+        let message = solana_sdk::message::Message::new(&[instruction], Some(&payer.pubkey()));
+
+        VersionedTransaction::try_new(solana_sdk::message::VersionedMessage::Legacy(message), &[&payer]).unwrap()
+    }
+
+    #[test]
+    fn ensure_transaction_fits_checks_the_packet_limit() {
+        // everything but the instruction data, whose length prefix stays 2 bytes from 128 on
+        let overhead = ensure_transaction_fits(&transaction_with_data(200)).unwrap() - 200;
+        let fitting_len = PACKET_DATA_SIZE - overhead;
+
+        assert_eq!(ensure_transaction_fits(&transaction_with_data(fitting_len - 1)).unwrap(), PACKET_DATA_SIZE - 1);
+        assert_eq!(ensure_transaction_fits(&transaction_with_data(fitting_len)).unwrap(), PACKET_DATA_SIZE);
+        assert!(matches!(
+            ensure_transaction_fits(&transaction_with_data(fitting_len + 1)),
+            Err(AppError::TransactionTooLarge(size)) if size == PACKET_DATA_SIZE + 1
+        ));
+    }
+
+    #[test]
+    fn colliding_account_metas_are_rejected() {
+        let (feed, queue) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        // accounts are positional, a collision is reported rather than merged
+        assert!(matches!(
+            ensure_unique_account_metas(&[
+                AccountMeta::new(feed, false),
+                AccountMeta::new_readonly(queue, false),
+                AccountMeta::new_readonly(feed, true),
+            ]),
+            Err(AppError::DuplicateAccount(pubkey)) if pubkey == feed
+        ));
+        assert!(ensure_unique_account_metas(&[AccountMeta::new(feed, false), AccountMeta::new(queue, false)]).is_ok());
+
+        // a payer set to the queue collides with the queue account
+        let result = get_solana_submit_signatures_ix(
+            1,
+            vec![oracle_response(Some(Decimal::ONE))],
+            SolanaSubmitSignaturesParams { queue, feed, payer: queue },
+            MissingValuePolicy::Drop,
+            None,
+            Cluster::Mainnet,
+        );
+        assert!(matches!(result, Err(AppError::DuplicateAccount(pubkey)) if pubkey == queue));
+    }
+
+    #[test]
+    fn writable_accounts_are_the_writable_non_signers() {
+        let params = SolanaSubmitSignaturesParams {
+            queue: Pubkey::new_unique(),
+            feed: Pubkey::new_unique(),
+            payer: Pubkey::new_unique(),
+        };
+        let response = oracle_response(Some(Decimal::ONE));
+        let oracle = response.oracle;
+        let submit_ix = get_solana_submit_signatures_ix(
+            1,
+            vec![response],
+            params.clone(),
+            MissingValuePolicy::Drop,
+            None,
+            Cluster::Mainnet,
+        )
+        .unwrap();
+
+        // the payer signs, the queue, the oracle and the programs are read-only; repeats are
+        // counted once
+        let mut expected = vec![
+            params.feed,
+            get_associated_token_address(&params.queue, &NATIVE_MINT),
+            OracleAccountData::stats_key(&oracle),
+        ];
+        expected.sort();
+        assert_eq!(get_writable_accounts(&[submit_ix.clone(), submit_ix]), expected);
+    }
+
+    // A consensus on one median, signed by a secp256k1 key per oracle.
+    #[allow(deprecated)]
+    fn signed_consensus_response(oracles: &[Pubkey]) -> FetchSignaturesConsensusResponse {
+        let checksum = [9; 32];
+        let oracle_responses = oracles
+            .iter()
+            .enumerate()
+            .map(|(index, oracle)| {
+                let (signature, recovery_id) =
+                    solana_sdk::secp256k1_instruction::sign_message(&[index as u8 + 1; 32], &checksum).unwrap();
+                let signer = secp256k1_recover(keccak::hash(&checksum).as_ref(), recovery_id, &signature).unwrap();
+                let eth_address = &keccak::hash(&signer.to_bytes()).to_bytes()[12..];
+
+                serde_json::json!({
+                    "oracle_pubkey": hex::encode(oracle),
+                    "eth_address": hex::encode(eth_address),
+                    "signature": base64.encode(signature),
+                    "checksum": base64.encode(checksum),
+                    "recovery_id": recovery_id,
+                    "feed_responses": [{
+                        "oracle_pubkey": hex::encode(oracle),
+                        "queue_pubkey": "00",
+                        "oracle_signing_pubkey": hex::encode(eth_address),
+                        "feed_hash": "00",
+                        "recent_hash": "00",
+                        "failure_error": "",
+                        "success_value": "1500000000000000000",
+                        "msg": "",
+                        "signature": "",
+                        "recovery_id": 0,
+                        "recent_successes_if_failed": [],
+                        "timestamp": null,
+                    }],
+                    "errors": [null],
+                })
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::from_value(serde_json::json!({
+            "median_responses": [{ "value": "1500000000000000000", "feed_hash": "00" }],
+            "oracle_responses": oracle_responses,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn consensus_ix_carries_every_responding_oracle() {
+        let oracles = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let params = SolanaSubmitSignaturesParams {
+            queue: Pubkey::new_unique(),
+            feed: Pubkey::new_unique(),
+            payer: Pubkey::new_unique(),
+        };
+
+        let instructions = get_update_consensus_ix(
+            params,
+            signed_consensus_response(&oracles),
+            1,
+            0,
+            None,
+            SecpMessageFormat::default(),
+            Cluster::Mainnet,
+        )
+        .unwrap();
+
+        validate_secp256k1_instruction(&instructions, 0, oracles.len()).unwrap();
+        let accounts = instructions[1].accounts.iter().map(|meta| meta.pubkey).collect::<Vec<_>>();
+        for oracle in oracles {
+            assert!(accounts.contains(&oracle));
+            assert!(accounts.contains(&OracleAccountData::stats_key(&oracle)));
+        }
+    }
+
+    #[test]
+    fn tampered_secp_signature_fails_verification() {
+        let oracles = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let secp_signatures =
+            build_secp_signatures(&signed_consensus_response(&oracles), SecpMessageFormat::default()).unwrap();
+        verify_secp_signatures(&secp_signatures).unwrap();
+
+        let mut tampered_signatures = secp_signatures.clone();
+        tampered_signatures[1].signature[10] ^= 1;
+        let error = verify_secp_signatures(&tampered_signatures).unwrap_err();
+        assert!(
+            matches!(&error, AppError::SignatureVerificationFailed(reason) if reason.starts_with("signature 1")),
+            "{error}"
+        );
+
+        let mut tampered_addresses = secp_signatures;
+        tampered_addresses[2].eth_address = [0; 20];
+        assert!(matches!(
+            verify_secp_signatures(&tampered_addresses),
+            Err(AppError::SignatureVerificationFailed(_))
+        ));
+    }
+}